@@ -0,0 +1,12 @@
+#!/bin/sh
+# テスト用の鍵はリポジトリに含めず、起動時に生成してホストと共有する keys に書き出す
+set -e
+
+if [ ! -f /keys/id_ed25519 ]; then
+  ssh-keygen -q -t ed25519 -N "" -C table-snapshot-test -f /keys/id_ed25519
+fi
+# テストはホストのユーザで読み、コピーしてから権限を絞る
+chmod 644 /keys/id_ed25519
+install -o tunnel -g tunnel -m 600 /keys/id_ed25519.pub /home/tunnel/.ssh/authorized_keys
+
+exec /usr/sbin/sshd -D -e
@@ -4,10 +4,11 @@ create table project
     name       varchar(32),
     rdbms      varchar(16),
     user       varchar(32),
-    password   varchar(32),
+    password   varchar(256),
     host       varchar(32),
     port       varchar(8),
     `schema`   varchar(64),
+    options    json,
     primary key (project_id)
 );
 
@@ -30,14 +31,38 @@ create table table_snapshot
     foreign key (snapshot_id) references snapshot_summary (snapshot_id) on delete cascade
 );
 
+create table snapshot_schedule
+(
+    project_id char(36),
+    data       json,
+    primary key (project_id),
+    foreign key (project_id) references project (project_id) on delete cascade
+);
+
+create table scheduled_snapshot
+(
+    snapshot_id char(36),
+    primary key (snapshot_id),
+    foreign key (snapshot_id) references snapshot_summary (snapshot_id) on delete cascade
+);
+
 create table snapshot_diff
 (
     diff_id      char(36),
     snapshot_id1 char(36),
     snapshot_id2 char(36),
-    data         json,
+    summary      json,
     primary key (diff_id),
     unique (snapshot_id1, snapshot_id2),
     foreign key (snapshot_id1) references snapshot_summary (snapshot_id) on delete cascade,
     foreign key (snapshot_id2) references snapshot_summary (snapshot_id) on delete cascade
 );
+
+create table table_diff
+(
+    diff_id    char(36),
+    table_name varchar(256),
+    data       json,
+    primary key (diff_id, table_name),
+    foreign key (diff_id) references snapshot_diff (diff_id) on delete cascade
+);
@@ -0,0 +1,3 @@
+grant replication slave, replication client on *.* to 'user'@'%';
+-- テストごとに testdata_ から始まるスキーマを作る
+grant all on `testdata\_%`.* to 'user'@'%';
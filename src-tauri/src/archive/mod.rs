@@ -0,0 +1,177 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+use anyhow::anyhow;
+use itertools::Itertools;
+use mysql::Conn;
+
+use crate::db::project::{all_projects, insert_project};
+use crate::db::snapshot::{all_snapshot_summaries, find_table_snapshots, insert_snapshot_summary, insert_table_snapshot};
+use crate::db::transaction;
+use crate::domain::archive::{ArchivedSnapshot, SnapshotArchive};
+use crate::domain::project::{create_project_id, ProjectId};
+use crate::domain::snapshot::{create_snapshot_id, SnapshotId, SnapshotName, SnapshotSummary};
+
+pub struct ImportResult {
+    pub project_id: ProjectId,
+    pub imported_snapshot_ids: Vec<SnapshotId>,
+    pub skipped_snapshot_names: Vec<SnapshotName>,
+}
+
+// snapshot_ids が空の場合は、プロジェクトの全スナップショットを対象とする
+pub fn export_snapshots(conn: &mut Conn, project_id: &ProjectId, snapshot_ids: &[SnapshotId]) -> anyhow::Result<SnapshotArchive> {
+    let projects = all_projects(conn)?;
+    let project = projects.iter().find(|project| &project.project_id == project_id).ok_or_else(|| anyhow!("project not found: {project_id}"))?;
+
+    let snapshot_summaries = all_snapshot_summaries(conn, project_id)?
+        .into_iter()
+        .filter(|snapshot_summary| snapshot_ids.is_empty() || snapshot_ids.contains(&snapshot_summary.snapshot_id))
+        .collect_vec();
+
+    if let Some(snapshot_id) = snapshot_ids.iter().find(|&snapshot_id| snapshot_summaries.iter().all(|s| &s.snapshot_id != snapshot_id)) {
+        return Err(anyhow!("snapshot not found: {snapshot_id}"));
+    }
+
+    let mut archived_snapshots = vec![];
+    for snapshot_summary in snapshot_summaries {
+        let table_snapshots = find_table_snapshots(conn, &snapshot_summary.snapshot_id)?;
+        archived_snapshots.push(ArchivedSnapshot::new(snapshot_summary, table_snapshots));
+    }
+
+    Ok(SnapshotArchive::new(project, archived_snapshots))
+}
+
+// project_id が指定されなかった場合は、アーカイブのプロジェクト情報から新しいプロジェクトを作成する
+pub fn import_snapshots(conn: &mut Conn, archive: SnapshotArchive, project_id: Option<&ProjectId>) -> anyhow::Result<ImportResult> {
+    archive.validate()?;
+
+    // 途中で失敗した場合に、プロジェクトや一部のスナップショットだけが取り込まれないようにする
+    transaction(conn, |conn| {
+        let project_id = match project_id {
+            Some(project_id) => {
+                if all_projects(conn)?.iter().all(|project| &project.project_id != project_id) {
+                    return Err(anyhow!("project not found: {project_id}"));
+                }
+                project_id.clone()
+            }
+            None => {
+                let project = archive.project.into_project(&create_project_id())?;
+                insert_project(conn, &project)?;
+                project.project_id
+            }
+        };
+
+        let existing_snapshot_summaries = all_snapshot_summaries(conn, &project_id)?;
+
+        let mut imported_snapshot_ids = vec![];
+        let mut skipped_snapshot_names = vec![];
+
+        for archived_snapshot in archive.snapshots {
+            if is_imported(conn, &existing_snapshot_summaries, &archived_snapshot)? {
+                skipped_snapshot_names.push(archived_snapshot.snapshot_name);
+                continue;
+            }
+
+            // スナップショット ID はインポート先で振り直す
+            let snapshot_id = create_snapshot_id();
+            let snapshot_summary = SnapshotSummary::new(&snapshot_id, archived_snapshot.snapshot_name, archived_snapshot.create_at);
+            insert_snapshot_summary(conn, &project_id, &snapshot_summary)?;
+            for table_snapshot in &archived_snapshot.table_snapshots {
+                insert_table_snapshot(conn, &snapshot_id, table_snapshot)?;
+            }
+
+            imported_snapshot_ids.push(snapshot_id);
+        }
+
+        Ok(ImportResult { project_id, imported_snapshot_ids, skipped_snapshot_names })
+    })
+}
+
+fn is_imported(conn: &mut Conn, snapshot_summaries: &[SnapshotSummary], archived_snapshot: &ArchivedSnapshot) -> anyhow::Result<bool> {
+    for snapshot_summary in snapshot_summaries {
+        if archived_snapshot.snapshot_name == snapshot_summary.snapshot_name && archived_snapshot.create_at == snapshot_summary.create_at {
+            let table_snapshots = find_table_snapshots(conn, &snapshot_summary.snapshot_id)?;
+            if archived_snapshot.is_duplicate_of(snapshot_summary, &table_snapshots) {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+pub fn write_archive<P: AsRef<Path>>(path: P, archive: &SnapshotArchive) -> anyhow::Result<()> {
+    let writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer(writer, archive)?;
+    Ok(())
+}
+
+pub fn read_archive<P: AsRef<Path>>(path: P) -> anyhow::Result<SnapshotArchive> {
+    let reader = BufReader::new(File::open(path)?);
+    let archive: SnapshotArchive = serde_json::from_reader(reader)?;
+    archive.validate()?;
+    Ok(archive)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::archive::{export_snapshots, import_snapshots, read_archive, write_archive};
+    use crate::db::create_connection;
+    use crate::db::project::{all_projects, insert_project};
+    use crate::db::snapshot::{all_snapshot_summaries, find_table_snapshots, insert_snapshot_summary, insert_table_snapshot};
+    use crate::domain::fixture::{n, s};
+    use crate::domain::project::Rdbms::Mysql;
+    use crate::domain::project::{create_project_id, Project};
+    use crate::domain::snapshot::{create_snapshot_id, RowSnapshot, SnapshotSummary, TableSnapshot};
+
+    #[test]
+    fn export_and_import() -> anyhow::Result<()> {
+        // setup
+
+        let mut conn = create_connection()?;
+        conn.prep_exec("delete from project", ())?;
+
+        let project_id = create_project_id();
+
+        let project = Project::new(&project_id, "test-project", Mysql, "user", "password", "127.0.0.1", "3306", "test-db");
+        insert_project(&mut conn, &project)?;
+
+        let snapshot_id = create_snapshot_id();
+
+        let snapshot_summary = SnapshotSummary::new(&snapshot_id, "test", "2023-07-03 08:17:52");
+        insert_snapshot_summary(&mut conn, &project_id, &snapshot_summary)?;
+
+        let row_snapshot = RowSnapshot::new(vec![n("1"), s("John")]);
+        let table_snapshot = TableSnapshot::new(&"users".to_string(), "id".to_string(), vec!["name".to_string()], vec![row_snapshot]);
+        insert_table_snapshot(&mut conn, &snapshot_id, &table_snapshot)?;
+
+        // export
+        let archive = export_snapshots(&mut conn, &project_id, std::slice::from_ref(&snapshot_id))?;
+        assert_eq!(1, archive.snapshots.len());
+
+        let path = std::env::temp_dir().join(format!("{snapshot_id}.json"));
+        write_archive(&path, &archive)?;
+
+        // import as new project
+        let result = import_snapshots(&mut conn, read_archive(&path)?, None)?;
+        assert_ne!(project_id, result.project_id);
+        assert_eq!(1, result.imported_snapshot_ids.len());
+        assert_ne!(snapshot_id, result.imported_snapshot_ids[0]);
+
+        let projects = all_projects(&mut conn)?;
+        assert_eq!(2, projects.len());
+        assert_eq!("", projects.iter().find(|project| project.project_id == result.project_id).unwrap().password);
+
+        assert_eq!(vec![table_snapshot], find_table_snapshots(&mut conn, &result.imported_snapshot_ids[0])?);
+
+        // import again ( skipped )
+        let result = import_snapshots(&mut conn, read_archive(&path)?, Some(&result.project_id))?;
+        assert_eq!(0, result.imported_snapshot_ids.len());
+        assert_eq!(vec!["test".to_string()], result.skipped_snapshot_names);
+        assert_eq!(1, all_snapshot_summaries(&mut conn, &result.project_id)?.len());
+
+        std::fs::remove_file(path)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,202 @@
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use clap::{Parser, Subcommand};
+
+use crate::archive::{export_snapshots, import_snapshots, read_archive, write_archive};
+use crate::db::create_connection;
+use crate::diff::{export_changes, export_report, find_or_create_snapshot_diff, render_report, verify_expectation};
+use crate::domain::diff::ChangeFormat;
+use crate::domain::project::ProjectId;
+use crate::domain::report::{IgnoreRule, ReportFormat, ReportOptions};
+use crate::domain::schema::{PrimaryValue, TableName};
+use crate::domain::snapshot::{ColValue, SnapshotId};
+use crate::history::find_row_history;
+
+#[derive(Parser)]
+#[command(name = "table-snapshot", about = "Take and compare snapshots of database tables")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Export snapshots of a project to an archive file
+    Export {
+        #[arg(long)]
+        project: ProjectId,
+
+        /// Snapshots to export ( all snapshots of the project if omitted )
+        #[arg(long = "snapshot")]
+        snapshots: Vec<SnapshotId>,
+
+        #[arg(long, short)]
+        output: PathBuf,
+    },
+
+    /// Import snapshots from an archive file
+    Import {
+        input: PathBuf,
+
+        /// Project to import into ( a new project is created from the archive if omitted )
+        #[arg(long)]
+        project: Option<ProjectId>,
+    },
+
+    /// Show how a single row changed across all snapshots of a project
+    History {
+        #[arg(long)]
+        project: ProjectId,
+
+        #[arg(long)]
+        table: TableName,
+
+        /// Primary key value of the row
+        #[arg(long)]
+        key: PrimaryValue,
+    },
+
+    /// Render the diff between two snapshots as a report
+    Report {
+        #[arg(long)]
+        from: SnapshotId,
+
+        #[arg(long)]
+        to: SnapshotId,
+
+        /// markdown or html
+        #[arg(long, default_value = "markdown", value_parser = parse_report_format)]
+        format: ReportFormat,
+
+        /// Table or column to leave out of the report ( e.g. logs, users.updated_at, *.updated_at )
+        #[arg(long)]
+        ignore: Vec<String>,
+
+        /// Maximum number of rows per table
+        #[arg(long)]
+        max_rows: Option<usize>,
+
+        /// File or directory to write the report to ( printed to stdout if omitted, named <from>-<to>.<md|html> in a directory )
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+
+    /// Print the changed rows between two snapshots in a machine-readable format
+    Changes {
+        #[arg(long)]
+        from: SnapshotId,
+
+        #[arg(long)]
+        to: SnapshotId,
+
+        /// ndjson or json-patch
+        #[arg(long, default_value = "ndjson", value_parser = parse_change_format)]
+        format: ChangeFormat,
+
+        /// File to write the changes to ( printed to stdout if omitted )
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+
+    /// Compare the diff between two snapshots with an expectation file
+    Verify {
+        #[arg(long)]
+        from: SnapshotId,
+
+        #[arg(long)]
+        to: SnapshotId,
+
+        /// Expectation file ( JSON )
+        #[arg(long)]
+        expect: PathBuf,
+
+        /// Rewrite the expectation file to match the diff ( created if missing )
+        #[arg(long)]
+        update: bool,
+    },
+}
+
+fn parse_report_format(format: &str) -> Result<ReportFormat, String> {
+    match format {
+        "markdown" => Ok(ReportFormat::Markdown),
+        "html" => Ok(ReportFormat::Html),
+        _ => Err(format!("unknown report format: {format}")),
+    }
+}
+
+fn parse_change_format(format: &str) -> Result<ChangeFormat, String> {
+    match format {
+        "ndjson" => Ok(ChangeFormat::Ndjson),
+        "json-patch" => Ok(ChangeFormat::JsonPatch),
+        _ => Err(format!("unknown change format: {format}")),
+    }
+}
+
+pub fn run(command: Command) -> anyhow::Result<()> {
+    let mut conn = create_connection()?;
+
+    match command {
+        Command::Export { project, snapshots, output } => {
+            let archive = export_snapshots(&mut conn, &project, &snapshots)?;
+            write_archive(&output, &archive)?;
+            println!("exported {} snapshot(s) to {}", archive.snapshots.len(), output.display());
+        }
+        Command::Import { input, project } => {
+            let result = import_snapshots(&mut conn, read_archive(&input)?, project.as_ref())?;
+            println!("imported {} snapshot(s) into project {}", result.imported_snapshot_ids.len(), result.project_id);
+            for snapshot_name in result.skipped_snapshot_names {
+                println!("skipped duplicate snapshot: {snapshot_name}");
+            }
+        }
+        Command::History { project, table, key } => {
+            let row_history = find_row_history(&mut conn, &project, &table, &key)?;
+            if row_history.entries.is_empty() {
+                println!("no snapshot contains {table} {key}");
+            }
+            for entry in row_history.entries {
+                println!("{} {} {}", entry.snapshot_summary.create_at, entry.snapshot_summary.snapshot_name, entry.kind.as_str());
+                for col in entry.cols {
+                    let display = |value: Option<ColValue>| value.map(|value| value.as_display_value()).unwrap_or_else(|| "-".to_string());
+                    println!("    {}: {} -> {}", col.col_name, display(col.old_value), display(col.new_value));
+                }
+            }
+        }
+        Command::Report { from, to, format, ignore, max_rows, output } => {
+            let options = ReportOptions { ignore_rules: ignore.iter().map(|rule| IgnoreRule::parse(rule)).collect(), max_rows_per_table: max_rows };
+            match output {
+                Some(output) => {
+                    let output = match output.is_dir() {
+                        true => output.join(format!("{from}-{to}.{}", format.extension())),
+                        false => output,
+                    };
+                    export_report(&mut conn, &from, &to, format, &options, &output)?;
+                    println!("exported report to {}", output.display());
+                }
+                None => print!("{}", render_report(&mut conn, &from, &to, format, &options)?),
+            }
+        }
+        Command::Changes { from, to, format, output } => match output {
+            Some(output) => {
+                export_changes(&mut conn, &from, &to, format, &output)?;
+                println!("exported changes to {}", output.display());
+            }
+            None => print!("{}", format.emit(&find_or_create_snapshot_diff(&mut conn, &from, &to)?)?),
+        },
+        Command::Verify { from, to, expect, update } => {
+            let mismatches = verify_expectation(&mut conn, &from, &to, &expect, update)?;
+            for mismatch in &mismatches {
+                println!("{mismatch}");
+            }
+            if update {
+                println!("updated {}", expect.display());
+            } else if !mismatches.is_empty() {
+                return Err(anyhow!("{} mismatch(es) with {}", mismatches.len(), expect.display()));
+            } else {
+                println!("matched {}", expect.display());
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::archive::{export_snapshots, import_snapshots, read_archive, write_archive, ImportResult};
+use crate::command::state::AppState;
+use crate::domain::project::ProjectId;
+use crate::domain::snapshot::{SnapshotId, SnapshotName};
+use crate::error::{Error, Result};
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportResultJson {
+    pub project_id: ProjectId,
+    pub imported_snapshot_ids: Vec<SnapshotId>,
+    pub skipped_snapshot_names: Vec<SnapshotName>,
+}
+
+impl ImportResultJson {
+    fn from(import_result: ImportResult) -> Self {
+        Self {
+            project_id: import_result.project_id,
+            imported_snapshot_ids: import_result.imported_snapshot_ids,
+            skipped_snapshot_names: import_result.skipped_snapshot_names,
+        }
+    }
+}
+
+#[tauri::command]
+pub fn export_snapshots_command(app_state: State<'_, AppState>, project_id: ProjectId, snapshot_ids: Vec<SnapshotId>, path: String) -> Result<()> {
+    let mut conn = app_state.conn()?;
+
+    let archive = export_snapshots(&mut conn, &project_id, &snapshot_ids)?;
+
+    write_archive(path, &archive).map_err(Error::from)
+}
+
+#[tauri::command]
+pub fn import_snapshots_command(app_state: State<'_, AppState>, path: String, project_id: Option<ProjectId>) -> Result<ImportResultJson> {
+    let mut conn = app_state.conn()?;
+
+    let archive = read_archive(path)?;
+
+    import_snapshots(&mut conn, archive, project_id.as_ref()).map(ImportResultJson::from).map_err(Error::from)
+}
@@ -1,17 +1,31 @@
+use std::cmp::Ordering;
 use std::collections::HashMap;
-use std::ops::Deref;
+use std::sync::Arc;
 
 use itertools::Itertools;
+use mysql::Conn;
 use serde::{Deserialize, Serialize};
 use tauri::State;
 
 use crate::command::state::AppState;
-use crate::db::diff::{find_snapshot_diff, insert_snapshot_diff};
-use crate::db::snapshot::find_table_snapshots;
-use crate::domain::diff::ColDiff::{Added, Deleted, NoValue, Stay};
-use crate::domain::diff::{create_diff_id, create_table_diff, ColDiff, DiffId, SnapshotDiff, TableDiff};
+use crate::db::diff::{find_snapshot_diff_summary, find_table_diff, insert_snapshot_diff};
+use crate::db::project::find_project;
+use crate::db::snapshot::{
+    all_snapshot_summaries, find_foreign_keys, find_table_col_names, find_table_hashes, find_table_snapshots, insert_snapshot,
+};
+use crate::diff::{export_changes, export_report, find_changed_table_snapshots, find_or_create_snapshot_diff};
+use crate::domain::diff::ColDiff::{Added, Deleted, Stay};
+use crate::domain::diff::{
+    create_snapshot_diff, create_three_way_diff, find_unchanged_table_names, ChangeFormat, ColDiff, DiffId, DiffSummary, DiffTotal, RowChange,
+    SnapshotDiff, TableDiff, TableDiffSummary, ThreeWayChange, ThreeWayDiff, ThreeWayTableDiff,
+};
+use crate::domain::group::{group_changed_rows, GroupedRow};
+use crate::domain::project::ProjectId;
+use crate::domain::report::{IgnoreRule, ReportFormat, ReportOptions};
 use crate::domain::schema::{ColName, PrimaryValue, TableName};
-use crate::domain::snapshot::{SnapshotId, TableSnapshot};
+use crate::domain::snapshot::{create_snapshot_id, PrimaryColValue, SnapshotId, SnapshotName, SnapshotSummary};
+use crate::dump::{binlog_diff, current_binlog_position, dump_in_memory, BinlogDiff, BinlogPosition};
+use crate::error::{Error, Result};
 
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -20,6 +34,7 @@ pub struct SnapshotDiffJson {
     pub snapshot_id1: SnapshotId,
     pub snapshot_id2: SnapshotId,
     pub table_diffs: Vec<TableDiffJson>,
+    pub summary: DiffSummaryJson,
 }
 
 impl SnapshotDiffJson {
@@ -29,108 +44,988 @@ impl SnapshotDiffJson {
             snapshot_id1: snapshot_diff.snapshot_id1,
             snapshot_id2: snapshot_diff.snapshot_id2,
             table_diffs: snapshot_diff.table_diffs.into_iter().map(TableDiffJson::from).collect(),
+            summary: DiffSummaryJson::from(snapshot_diff.summary),
         }
     }
 }
 
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffSummaryJson {
+    pub tables: Vec<TableDiffSummaryJson>,
+    pub total: DiffTotalJson,
+}
+
+impl DiffSummaryJson {
+    fn from(summary: DiffSummary) -> Self {
+        Self { tables: summary.tables.into_iter().map(TableDiffSummaryJson::from).collect(), total: DiffTotalJson::from(summary.total) }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableDiffSummaryJson {
+    pub table_name: TableName,
+    pub unchanged: bool,
+    pub renamed_from: Option<TableName>,
+    pub inserted: usize,
+    pub deleted: usize,
+    pub updated: usize,
+    pub rekeyed: usize,
+    pub col_modifications: Vec<ColModificationJson>,
+}
+
+impl TableDiffSummaryJson {
+    fn from(table_diff_summary: TableDiffSummary) -> Self {
+        Self {
+            table_name: table_diff_summary.table_name,
+            unchanged: table_diff_summary.unchanged,
+            renamed_from: table_diff_summary.renamed_from,
+            inserted: table_diff_summary.inserted,
+            deleted: table_diff_summary.deleted,
+            updated: table_diff_summary.updated,
+            rekeyed: table_diff_summary.rekeyed,
+            col_modifications: table_diff_summary
+                .col_modifications
+                .into_iter()
+                .map(|col_modification| ColModificationJson { col_name: col_modification.col_name, count: col_modification.count })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColModificationJson {
+    pub col_name: ColName,
+    pub count: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffTotalJson {
+    pub changed_tables: usize,
+    pub unchanged_tables: usize,
+    pub inserted: usize,
+    pub deleted: usize,
+    pub updated: usize,
+    pub rekeyed: usize,
+}
+
+impl DiffTotalJson {
+    fn from(total: DiffTotal) -> Self {
+        Self {
+            changed_tables: total.changed_tables,
+            unchanged_tables: total.unchanged_tables,
+            inserted: total.inserted,
+            deleted: total.deleted,
+            updated: total.updated,
+            rekeyed: total.rekeyed,
+        }
+    }
+}
+
+// 変更のあった行だけを、列の並び順どおりの変更前と変更後の値の組で返す
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TableDiffJson {
-    pub primary_values: Vec<PrimaryValue>,
+    pub table_name: TableName,
+    // 名前が変わったとみなしたテーブルの、変更前の名前
+    pub renamed_from: Option<TableName>,
     pub primary_col_name: ColName,
     pub col_names: Vec<ColName>,
-    pub row_diffs1: HashMap<PrimaryValue, HashMap<ColName, HashMap<String, String>>>,
-    pub row_diffs2: HashMap<PrimaryValue, HashMap<ColName, HashMap<String, String>>>,
+    pub rows: Vec<RowDiffJson>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RowDiffJson {
+    pub primary_value: PrimaryValue,
+    // rekeyed の場合だけ、変更前の主キー値が入る
+    pub old_primary_value: Option<PrimaryValue>,
+    pub kind: RowChangeKind,
+    pub cols: Vec<ColDiffJson>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColDiffJson {
+    pub col_name: ColName,
+    pub kind: ColChangeKind,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Clone, Copy, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum RowChangeKind {
+    Added,
+    Removed,
+    Modified,
+    Rekeyed,
+}
+
+impl RowChangeKind {
+    fn from(change: RowChange) -> Self {
+        match change {
+            RowChange::Added => Self::Added,
+            RowChange::Removed => Self::Removed,
+            RowChange::Modified => Self::Modified,
+            RowChange::Rekeyed => Self::Rekeyed,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Clone, Copy, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColChangeKind {
+    Unchanged,
+    Modified,
+    Added,
+    Removed,
 }
 
 impl TableDiffJson {
     fn from(table_diff: TableDiff) -> Self {
-        let mut row_diffs1 = HashMap::new();
-        for (primary_value, col) in table_diff.row_diffs1 {
-            for (col_name, col_diff) in col {
-                row_diffs1.insert(primary_value.clone(), vec![(col_name.clone(), TableDiffJson::map(col_diff))].into_iter().collect());
-            }
+        let rows = changed_primary_values(&table_diff).map(|(primary_value, kind)| RowDiffJson::from(&table_diff, primary_value, kind)).collect();
+
+        Self {
+            table_name: table_diff.table_name,
+            renamed_from: table_diff.renamed_from,
+            primary_col_name: table_diff.primary_col_name,
+            col_names: table_diff.col_names,
+            rows,
         }
+    }
+}
+
+fn changed_primary_values(table_diff: &TableDiff) -> impl Iterator<Item = (PrimaryValue, RowChangeKind)> + '_ {
+    table_diff.changed_rows().map(|(primary_value, change)| (primary_value, RowChangeKind::from(change)))
+}
+
+impl RowDiffJson {
+    fn from(table_diff: &TableDiff, primary_value: PrimaryValue, kind: RowChangeKind) -> Self {
+        let old_primary_value = table_diff.find_old_primary_value(&primary_value).cloned();
 
-        let mut row_diffs2 = HashMap::new();
-        for (primary_value, col) in table_diff.row_diffs2 {
-            for (col_name, col_diff) in col {
-                row_diffs2.insert(primary_value.clone(), vec![(col_name.clone(), TableDiffJson::map(col_diff))].into_iter().collect());
+        let row_diff1 = table_diff.row_diffs1.get(old_primary_value.as_ref().unwrap_or(&primary_value));
+        let row_diff2 = table_diff.row_diffs2.get(&primary_value);
+
+        let cols = table_diff
+            .col_names
+            .iter()
+            .filter_map(|col_name| {
+                ColDiffJson::from(
+                    col_name,
+                    row_diff1.and_then(|row_diff| row_diff.get(col_name)),
+                    row_diff2.and_then(|row_diff| row_diff.get(col_name)),
+                )
+            })
+            .collect();
+
+        Self { primary_value, old_primary_value, kind, cols }
+    }
+}
+
+// 指定した場合は、削除された行と追加された行のうち異なる列が max_different_cols 以下のものを rekeyed として組にする
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RekeyOptionsJson {
+    #[serde(default)]
+    pub max_different_cols: usize,
+}
+
+// after を指定した場合は、その主キー値の行より後ろから offset 件を飛ばして limit 件を返す
+// kinds を指定した場合は、その種類の変更だけを数えて返す
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableDiffPageRequestJson {
+    #[serde(default)]
+    pub after: Option<PrimaryValue>,
+    #[serde(default)]
+    pub offset: usize,
+    pub limit: usize,
+    #[serde(default)]
+    pub kinds: Vec<RowChangeKind>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableDiffPageJson {
+    pub table_name: TableName,
+    pub renamed_from: Option<TableName>,
+    pub primary_col_name: ColName,
+    pub col_names: Vec<ColName>,
+    pub rows: Vec<RowDiffJson>,
+    // after と kinds で絞り込んだ行の数 (offset と limit は考慮しない)
+    pub total: usize,
+}
+
+impl TableDiffPageJson {
+    // after の行が絞り込みで除かれていたり変更がなくなっていたりしても続きから返せるよう、位置ではなく主キー値の並び順で比べる
+    // 数値の主キーは文字列ではなく数値として並べる
+    fn from(table_diff: &TableDiff, page: &TableDiffPageRequestJson) -> Result<Self> {
+        let after = match (&page.after, table_diff.primary_col_values.first()) {
+            (Some(after), Some(primary_col_value)) => {
+                Some(primary_col_value.parse_primary_value(after).ok_or_else(|| Error::InvalidInput(format!("invalid cursor: {after}")))?)
             }
+            _ => None,
+        };
+        let primary_col_values: HashMap<PrimaryValue, &PrimaryColValue> =
+            table_diff.primary_col_values.iter().map(|primary_col_value| (primary_col_value.as_primary_value(), primary_col_value)).collect();
+
+        let primary_values = changed_primary_values(table_diff)
+            .filter(|(primary_value, _)| after.as_ref().is_none_or(|after| primary_col_values[primary_value].cmp_value(after) == Ordering::Greater))
+            .filter(|(_, kind)| page.kinds.is_empty() || page.kinds.contains(kind))
+            .sorted_by(|(a, _), (b, _)| primary_col_values[a].cmp_value(primary_col_values[b]))
+            .collect_vec();
+
+        let rows = primary_values
+            .iter()
+            .skip(page.offset)
+            .take(page.limit)
+            .map(|(primary_value, kind)| RowDiffJson::from(table_diff, primary_value.clone(), *kind))
+            .collect();
+
+        Ok(Self {
+            table_name: table_diff.table_name.clone(),
+            renamed_from: table_diff.renamed_from.clone(),
+            primary_col_name: table_diff.primary_col_name.clone(),
+            col_names: table_diff.col_names.clone(),
+            rows,
+            total: primary_values.len(),
+        })
+    }
+}
+
+impl ColDiffJson {
+    fn from(col_name: &ColName, col_diff1: Option<&ColDiff>, col_diff2: Option<&ColDiff>) -> Option<Self> {
+        let old_value = match col_diff1 {
+            Some(Stay(v) | Deleted(v)) => Some(v),
+            _ => None,
+        };
+        let new_value = match col_diff2 {
+            Some(Stay(v) | Added(v)) => Some(v),
+            _ => None,
+        };
+
+        let kind = match (old_value, new_value) {
+            (Some(old_value), Some(new_value)) if old_value == new_value => ColChangeKind::Unchanged,
+            (Some(_), Some(_)) => ColChangeKind::Modified,
+            (None, Some(_)) => ColChangeKind::Added,
+            (Some(_), None) => ColChangeKind::Removed,
+            // どちらの行にもない列
+            (None, None) => return None,
+        };
+
+        Some(Self {
+            col_name: col_name.clone(),
+            kind,
+            old_value: old_value.map(|v| v.as_display_value()),
+            new_value: new_value.map(|v| v.as_display_value()),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThreeWayDiffJson {
+    pub snapshot_id_base: SnapshotId,
+    pub snapshot_id1: SnapshotId,
+    pub snapshot_id2: SnapshotId,
+    pub table_diffs: Vec<ThreeWayTableDiffJson>,
+}
+
+impl ThreeWayDiffJson {
+    fn from(three_way_diff: ThreeWayDiff) -> Self {
+        Self {
+            snapshot_id_base: three_way_diff.snapshot_id_base,
+            snapshot_id1: three_way_diff.snapshot_id1,
+            snapshot_id2: three_way_diff.snapshot_id2,
+            table_diffs: three_way_diff.table_diffs.into_iter().map(ThreeWayTableDiffJson::from).collect(),
         }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThreeWayTableDiffJson {
+    pub table_name: TableName,
+    pub primary_col_name: ColName,
+    pub col_names: Vec<ColName>,
+    pub rows: Vec<ThreeWayRowDiffJson>,
+}
 
+impl ThreeWayTableDiffJson {
+    fn from(table_diff: ThreeWayTableDiff) -> Self {
         Self {
-            primary_values: table_diff.primary_col_values.into_iter().map(|primary_col_value| primary_col_value.as_primary_value()).collect(),
+            table_name: table_diff.table_name,
             primary_col_name: table_diff.primary_col_name,
             col_names: table_diff.col_names,
-            row_diffs1,
-            row_diffs2,
+            rows: table_diff
+                .rows
+                .into_iter()
+                .map(|row| ThreeWayRowDiffJson {
+                    primary_value: row.primary_value,
+                    change: ThreeWayChangeJson::from(row.change),
+                    cols: row
+                        .cols
+                        .into_iter()
+                        .map(|col| ThreeWayColDiffJson {
+                            col_name: col.col_name,
+                            change: ThreeWayChangeJson::from(col.change),
+                            base_value: col.base_value.map(|v| v.as_display_value()),
+                            value1: col.value1.map(|v| v.as_display_value()),
+                            value2: col.value2.map(|v| v.as_display_value()),
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThreeWayRowDiffJson {
+    pub primary_value: PrimaryValue,
+    pub change: ThreeWayChangeJson,
+    pub cols: Vec<ThreeWayColDiffJson>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThreeWayColDiffJson {
+    pub col_name: ColName,
+    pub change: ThreeWayChangeJson,
+    pub base_value: Option<String>,
+    pub value1: Option<String>,
+    pub value2: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThreeWayChangeJson {
+    Only1,
+    Only2,
+    Both,
+    Conflict,
+}
+
+impl ThreeWayChangeJson {
+    fn from(change: ThreeWayChange) -> Self {
+        match change {
+            ThreeWayChange::Only1 => Self::Only1,
+            ThreeWayChange::Only2 => Self::Only2,
+            ThreeWayChange::Both => Self::Both,
+            ThreeWayChange::Conflict => Self::Conflict,
+        }
+    }
+}
+
+// 外部キーでつながった変更のまとまり
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffGroupJson {
+    pub description: String,
+    pub root: GroupedRowJson,
+}
+
+impl DiffGroupJson {
+    fn from(grouped_row: GroupedRow, table_diffs: &[TableDiff]) -> Self {
+        Self { description: grouped_row.describe(), root: GroupedRowJson::from(grouped_row, table_diffs) }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupedRowJson {
+    pub table_name: TableName,
+    pub primary_value: PrimaryValue,
+    pub kind: RowChangeKind,
+    pub cols: Vec<ColDiffJson>,
+    pub children: Vec<GroupedRowJson>,
+}
+
+impl GroupedRowJson {
+    fn from(grouped_row: GroupedRow, table_diffs: &[TableDiff]) -> Self {
+        let kind = RowChangeKind::from(grouped_row.kind);
+        let cols = table_diffs
+            .iter()
+            .find(|table_diff| table_diff.table_name == grouped_row.table_name)
+            .map(|table_diff| RowDiffJson::from(table_diff, grouped_row.primary_value.clone(), kind).cols)
+            .unwrap_or_default();
+
+        Self {
+            table_name: grouped_row.table_name,
+            primary_value: grouped_row.primary_value,
+            kind,
+            cols,
+            children: grouped_row.children.into_iter().map(|child| GroupedRowJson::from(child, table_diffs)).collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReportFormatJson {
+    Markdown,
+    Html,
+}
+
+impl ReportFormatJson {
+    fn into(self) -> ReportFormat {
+        match self {
+            Self::Markdown => ReportFormat::Markdown,
+            Self::Html => ReportFormat::Html,
         }
     }
+}
 
-    fn map(col_diff: ColDiff) -> HashMap<String, String> {
-        match col_diff {
-            NoValue => vec![],
-            Stay(v) => vec![("status".to_string(), "stay".to_string()), ("value".to_string(), v.as_display_value())],
-            Added(v) => vec![("status".to_string(), "added".to_string()), ("value".to_string(), v.as_display_value())],
-            Deleted(v) => vec![("status".to_string(), "deleted".to_string()), ("value".to_string(), v.as_display_value())],
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChangeFormatJson {
+    Ndjson,
+    JsonPatch,
+}
+
+impl ChangeFormatJson {
+    fn into(self) -> ChangeFormat {
+        match self {
+            Self::Ndjson => ChangeFormat::Ndjson,
+            Self::JsonPatch => ChangeFormat::JsonPatch,
+        }
+    }
+}
+
+// ignore_rules は orders, orders.updated_at, *.updated_at の形式で書く
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportOptionsJson {
+    #[serde(default)]
+    pub ignore_rules: Vec<String>,
+    #[serde(default)]
+    pub max_rows_per_table: Option<usize>,
+}
+
+impl ReportOptionsJson {
+    fn into(self) -> ReportOptions {
+        ReportOptions {
+            ignore_rules: self.ignore_rules.iter().map(|rule| IgnoreRule::parse(rule)).collect(),
+            max_rows_per_table: self.max_rows_per_table,
         }
-        .into_iter()
-        .collect()
     }
 }
 
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BinlogPositionJson {
+    pub file: String,
+    pub position: u64,
+}
+
+impl BinlogPositionJson {
+    fn from(binlog_position: BinlogPosition) -> Self {
+        Self { file: binlog_position.file, position: binlog_position.position }
+    }
+
+    fn into(self) -> BinlogPosition {
+        BinlogPosition::new(self.file, self.position)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BinlogDiffJson {
+    pub from: BinlogPositionJson,
+    pub to: BinlogPositionJson,
+    pub table_diffs: Vec<TableDiffJson>,
+    pub summary: DiffSummaryJson,
+}
+
+impl BinlogDiffJson {
+    fn from(binlog_diff: BinlogDiff) -> Self {
+        Self {
+            from: BinlogPositionJson::from(binlog_diff.from),
+            to: BinlogPositionJson::from(binlog_diff.to),
+            table_diffs: binlog_diff.table_diffs.into_iter().map(TableDiffJson::from).collect(),
+            summary: DiffSummaryJson::from(binlog_diff.summary),
+        }
+    }
+}
+
+// 主キーの変わった行の検出は表示の都度行い、保存する差分には含めない
 #[tauri::command]
 pub fn find_snapshot_diff_command(
     app_state: State<'_, AppState>,
     snapshot_id1: SnapshotId,
     snapshot_id2: SnapshotId,
-) -> Result<SnapshotDiffJson, String> {
-    let mut conn = app_state.conn.lock().unwrap();
+    rekey: Option<RekeyOptionsJson>,
+) -> Result<SnapshotDiffJson> {
+    let mut conn = app_state.conn()?;
+
+    let mut snapshot_diff = find_or_create_snapshot_diff(&mut conn, &snapshot_id1, &snapshot_id2)?;
+    if let Some(rekey) = rekey {
+        snapshot_diff.detect_rekeys(rekey.max_different_cols);
+    }
+
+    Ok(SnapshotDiffJson::from(snapshot_diff))
+}
+
+// 行ごとの差分は返さないので、差分を作成済みであれば画面に渡すデータが小さく済む
+#[tauri::command]
+pub fn find_snapshot_diff_summary_command(
+    app_state: State<'_, AppState>,
+    snapshot_id1: SnapshotId,
+    snapshot_id2: SnapshotId,
+    rekey: Option<RekeyOptionsJson>,
+) -> Result<DiffSummaryJson> {
+    let mut conn = app_state.conn()?;
 
-    let snapshot_diff = match find_snapshot_diff(&mut conn, &snapshot_id1, &snapshot_id2).map_err(|e| e.to_string())? {
-        Some(snapshot_diff) => snapshot_diff,
+    // 主キーの変わった行を数えるには、行ごとの差分が必要になる
+    let summary = match (find_snapshot_diff_summary(&mut conn, &snapshot_id1, &snapshot_id2)?, rekey) {
+        (Some(summary), None) => summary,
+        (_, rekey) => {
+            let mut snapshot_diff = find_or_create_snapshot_diff(&mut conn, &snapshot_id1, &snapshot_id2)?;
+            if let Some(rekey) = rekey {
+                snapshot_diff.detect_rekeys(rekey.max_different_cols);
+            }
+            snapshot_diff.summary
+        }
+    };
+
+    Ok(DiffSummaryJson::from(summary))
+}
+
+// 差分をテーブルごとに保存しているので、他のテーブルの差分を読み込まずに一部の行だけを返せる
+#[tauri::command]
+pub fn find_table_diff_page_command(
+    app_state: State<'_, AppState>,
+    snapshot_id1: SnapshotId,
+    snapshot_id2: SnapshotId,
+    table_name: TableName,
+    page: TableDiffPageRequestJson,
+    rekey: Option<RekeyOptionsJson>,
+) -> Result<TableDiffPageJson> {
+    let max_different_cols = rekey.map(|rekey| rekey.max_different_cols);
+    let key = (snapshot_id1, snapshot_id2, table_name, max_different_cols);
+
+    let cached =
+        app_state.last_table_diff.lock().unwrap().as_ref().filter(|(cached_key, _)| cached_key == &key).map(|(_, table_diff)| table_diff.clone());
+    let table_diff = match cached {
+        Some(table_diff) => table_diff,
+        None => {
+            let mut conn = app_state.conn()?;
+            let (snapshot_id1, snapshot_id2, table_name, _) = &key;
+            let table_diff = Arc::new(find_table_diff_to_page(&mut conn, snapshot_id1, snapshot_id2, table_name, max_different_cols)?);
+            *app_state.last_table_diff.lock().unwrap() = Some((key, table_diff.clone()));
+            table_diff
+        }
+    };
+
+    TableDiffPageJson::from(&table_diff, &page)
+}
+
+fn find_table_diff_to_page(
+    conn: &mut Conn,
+    snapshot_id1: &SnapshotId,
+    snapshot_id2: &SnapshotId,
+    table_name: &TableName,
+    max_different_cols: Option<usize>,
+) -> Result<TableDiff> {
+    let table_diff = match find_snapshot_diff_summary(conn, snapshot_id1, snapshot_id2)? {
+        Some(_) => find_table_diff(conn, snapshot_id1, snapshot_id2, table_name)?,
+        None => find_or_create_snapshot_diff(conn, snapshot_id1, snapshot_id2)?
+            .table_diffs
+            .into_iter()
+            .find(|table_diff| &table_diff.table_name == table_name),
+    };
+
+    // 変更のないテーブルは差分を保存していないので、行のない差分を返す
+    let mut table_diff = match table_diff {
+        Some(table_diff) => table_diff,
         None => {
-            let table_snapshots1 = find_table_snapshots(&mut conn, &snapshot_id1).map_err(|e| e.to_string())?;
-            let table_snapshots1: HashMap<&TableName, &TableSnapshot> = table_snapshots1
-                .iter()
-                .into_group_map_by(|table_snapshot| &table_snapshot.table_name)
-                .iter()
-                .map(|(&table_name, table_snapshots)| (table_name, table_snapshots[0]))
-                .collect();
-
-            let table_snapshots2 = find_table_snapshots(&mut conn, &snapshot_id2).map_err(|e| e.to_string())?;
-            let table_snapshots2: HashMap<&TableName, &TableSnapshot> = table_snapshots2
-                .iter()
-                .into_group_map_by(|table_snapshot| &table_snapshot.table_name)
-                .iter()
-                .map(|(&table_name, table_snapshots)| (table_name, table_snapshots[0]))
-                .collect();
-
-            let mut table_names1 = table_snapshots1.keys().cloned().collect_vec();
-            let mut table_names2 = table_snapshots2.keys().cloned().collect_vec();
-            table_names1.append(&mut table_names2);
-
-            let snapshot_diff = SnapshotDiff::new(
-                &create_diff_id(),
-                &snapshot_id1,
-                &snapshot_id2,
-                table_names1
-                    .into_iter()
-                    .map(|table_name| {
-                        create_table_diff(
-                            table_snapshots1.get(table_name).map(|table_snapshot| table_snapshot.deref()),
-                            table_snapshots2.get(table_name).map(|table_snapshot| table_snapshot.deref()),
-                        )
-                    })
-                    .collect(),
-            );
-
-            insert_snapshot_diff(&mut conn, &snapshot_diff).map_err(|e| e.to_string())?;
-
-            snapshot_diff
+            let (primary_col_name, col_names) = find_table_col_names(conn, snapshot_id2, table_name)?
+                .ok_or_else(|| Error::NotFound(format!("table {table_name} of snapshot {snapshot_id2}")))?;
+            TableDiff::init(table_name, &[], &primary_col_name, col_names.iter().collect())
         }
     };
+    if let Some(max_different_cols) = max_different_cols {
+        table_diff.detect_rekeys(max_different_cols);
+    }
+
+    Ok(table_diff)
+}
+
+// 差分は保存せず、3 つのスナップショットのハッシュが一致するテーブルは読み込まない
+#[tauri::command]
+pub fn find_three_way_diff_command(
+    app_state: State<'_, AppState>,
+    snapshot_id_base: SnapshotId,
+    snapshot_id1: SnapshotId,
+    snapshot_id2: SnapshotId,
+) -> Result<ThreeWayDiffJson> {
+    let mut conn = app_state.conn()?;
+
+    let table_hashes_base = find_table_hashes(&mut conn, &snapshot_id_base)?;
+    let table_hashes1 = find_table_hashes(&mut conn, &snapshot_id1)?;
+    let table_hashes2 = find_table_hashes(&mut conn, &snapshot_id2)?;
+    let unchanged_table_names2 = find_unchanged_table_names(&table_hashes_base, &table_hashes2);
+    let unchanged_table_names = find_unchanged_table_names(&table_hashes_base, &table_hashes1)
+        .into_iter()
+        .filter(|table_name| unchanged_table_names2.contains(table_name))
+        .collect_vec();
+
+    let table_snapshots_base = find_changed_table_snapshots(&mut conn, &snapshot_id_base, &table_hashes_base, &unchanged_table_names)?;
+    let table_snapshots1 = find_changed_table_snapshots(&mut conn, &snapshot_id1, &table_hashes1, &unchanged_table_names)?;
+    let table_snapshots2 = find_changed_table_snapshots(&mut conn, &snapshot_id2, &table_hashes2, &unchanged_table_names)?;
+
+    let three_way_diff =
+        create_three_way_diff(&snapshot_id_base, &snapshot_id1, &snapshot_id2, &table_snapshots_base, &table_snapshots1, &table_snapshots2);
+
+    Ok(ThreeWayDiffJson::from(three_way_diff))
+}
+
+// 外部キーは、どちらかのスナップショットで記録されていれば使う
+#[tauri::command]
+pub fn find_grouped_diff_command(app_state: State<'_, AppState>, snapshot_id1: SnapshotId, snapshot_id2: SnapshotId) -> Result<Vec<DiffGroupJson>> {
+    let mut conn = app_state.conn()?;
+
+    let snapshot_diff = find_or_create_snapshot_diff(&mut conn, &snapshot_id1, &snapshot_id2)?;
+
+    let foreign_keys = find_foreign_keys(&mut conn, &snapshot_id1)?
+        .into_iter()
+        .chain(find_foreign_keys(&mut conn, &snapshot_id2)?)
+        .flat_map(|(table_name, foreign_keys)| foreign_keys.into_iter().map(move |foreign_key| (table_name.clone(), foreign_key)))
+        .unique()
+        .collect_vec();
+
+    Ok(group_changed_rows(&snapshot_diff.table_diffs, &foreign_keys)
+        .into_iter()
+        .map(|grouped_row| DiffGroupJson::from(grouped_row, &snapshot_diff.table_diffs))
+        .collect())
+}
+
+#[tauri::command]
+pub fn export_diff_report_command(
+    app_state: State<'_, AppState>,
+    snapshot_id1: SnapshotId,
+    snapshot_id2: SnapshotId,
+    format: ReportFormatJson,
+    options: ReportOptionsJson,
+    path: String,
+) -> Result<()> {
+    let mut conn = app_state.conn()?;
+
+    export_report(&mut conn, &snapshot_id1, &snapshot_id2, format.into(), &options.into(), path).map_err(Error::from)
+}
+
+#[tauri::command]
+pub fn export_diff_changes_command(
+    app_state: State<'_, AppState>,
+    snapshot_id1: SnapshotId,
+    snapshot_id2: SnapshotId,
+    format: ChangeFormatJson,
+    path: String,
+) -> Result<()> {
+    let mut conn = app_state.conn()?;
+
+    export_changes(&mut conn, &snapshot_id1, &snapshot_id2, format.into(), path).map_err(Error::from)
+}
+
+// 対象のデータベースをメモリ上にダンプして、保存済みのスナップショットと比べる
+// keep_as を指定した場合だけ、その名前でダンプと差分を保存する
+// 保存しない場合の diffId と snapshotId2 はこの結果の中だけの一時的な ID で、ページ単位の取得などには使えないため、すべての行を含めて返す
+#[tauri::command]
+pub fn find_live_diff_command(
+    app_state: State<'_, AppState>,
+    project_id: ProjectId,
+    snapshot_id: SnapshotId,
+    keep_as: Option<SnapshotName>,
+) -> Result<SnapshotDiffJson> {
+    let (project, table_snapshots1) = {
+        let mut conn = app_state.conn()?;
+        let project = find_project(&mut conn, &project_id)?;
+        if !all_snapshot_summaries(&mut conn, &project_id)?.iter().any(|snapshot_summary| snapshot_summary.snapshot_id == snapshot_id) {
+            return Err(Error::NotFound(format!("snapshot {snapshot_id}")));
+        }
+        (project, find_table_snapshots(&mut conn, &snapshot_id)?)
+    };
+
+    // ダンプ中は保存先の接続を使わないので、プールに返しておく
+    let table_snapshots2 = dump_in_memory(&project, &app_state.key)?;
+
+    let live_snapshot_id = create_snapshot_id();
+    let snapshot_diff = create_snapshot_diff(&snapshot_id, &live_snapshot_id, &table_snapshots1, &table_snapshots2, &[]);
+
+    if let Some(snapshot_name) = keep_as {
+        let mut conn = app_state.conn()?;
+        insert_snapshot(&mut conn, &project_id, &SnapshotSummary::create(&live_snapshot_id, &snapshot_name), &table_snapshots2)?;
+        insert_snapshot_diff(&mut conn, &snapshot_diff)?;
+    }
 
     Ok(SnapshotDiffJson::from(snapshot_diff))
 }
+
+#[tauri::command]
+pub fn current_binlog_position_command(app_state: State<'_, AppState>, project_id: ProjectId) -> Result<BinlogPositionJson> {
+    let project = {
+        let mut conn = app_state.conn()?;
+        find_project(&mut conn, &project_id)?
+    };
+
+    Ok(BinlogPositionJson::from(current_binlog_position(&project, &app_state.key)?))
+}
+
+// binlog から作った差分はスナップショットに紐づかないので保存しない
+// to を省略した場合は現在の位置までの差分を返す
+// アプリを動かすマシンに mysqlbinlog コマンドが必要
+#[tauri::command]
+pub fn find_binlog_diff_command(
+    app_state: State<'_, AppState>,
+    project_id: ProjectId,
+    from: BinlogPositionJson,
+    to: Option<BinlogPositionJson>,
+) -> Result<BinlogDiffJson> {
+    let project = {
+        let mut conn = app_state.conn()?;
+        find_project(&mut conn, &project_id)?
+    };
+
+    let to = match to {
+        Some(to) => to.into(),
+        None => current_binlog_position(&project, &app_state.key)?,
+    };
+
+    Ok(BinlogDiffJson::from(binlog_diff(&project, &app_state.key, &from.into(), &to)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::command::diff::{DiffGroupJson, RowChangeKind, TableDiffJson, TableDiffPageJson, TableDiffPageRequestJson};
+    use crate::domain::diff::create_table_diff;
+    use crate::domain::fixture::{self, n, s};
+    use crate::domain::group::group_changed_rows;
+    use crate::domain::schema::ForeignKey;
+    use crate::domain::snapshot::ColValue::Null;
+    use crate::domain::snapshot::{ColValue, RowSnapshot, TableSnapshot};
+
+    fn mk_table_snapshot(col_names: Vec<&str>, rows: Vec<Vec<ColValue>>) -> TableSnapshot {
+        fixture::mk_table_snapshot("users", col_names, rows)
+    }
+
+    #[test]
+    fn rows() {
+        let table_snapshot1 = mk_table_snapshot(
+            vec!["name", "age", "email"],
+            vec![vec![n("1"), s("John"), n("29"), Null], vec![n("2"), s("Jack"), n("31"), Null], vec![n("3"), s("Jill"), n("20"), Null]],
+        );
+        let table_snapshot2 = mk_table_snapshot(
+            vec!["name", "age", "email"],
+            vec![
+                vec![n("1"), s("Jane"), n("29"), s("jane@example.com")],
+                vec![n("3"), s("Jill"), n("20"), Null],
+                vec![n("4"), s("Joe"), n("15"), Null],
+            ],
+        );
+
+        let act = TableDiffJson::from(create_table_diff(Some(&table_snapshot1), Some(&table_snapshot2)));
+
+        // 変更のない行は含めず、すべての列を列の順に返す
+        assert_eq!(
+            json!({
+                "tableName": "users",
+                "renamedFrom": null,
+                "primaryColName": "id",
+                "colNames": ["name", "age", "email"],
+                "rows": [
+                    {
+                        "primaryValue": "1",
+                        "oldPrimaryValue": null,
+                        "kind": "modified",
+                        "cols": [
+                            { "colName": "name",  "kind": "modified",  "oldValue": r#""John""#, "newValue": r#""Jane""# },
+                            { "colName": "age",   "kind": "unchanged", "oldValue": "29",        "newValue": "29" },
+                            { "colName": "email", "kind": "modified",  "oldValue": "<null>",    "newValue": r#""jane@example.com""# },
+                        ],
+                    },
+                    {
+                        "primaryValue": "2",
+                        "oldPrimaryValue": null,
+                        "kind": "removed",
+                        "cols": [
+                            { "colName": "name",  "kind": "removed", "oldValue": r#""Jack""#, "newValue": null },
+                            { "colName": "age",   "kind": "removed", "oldValue": "31",        "newValue": null },
+                            { "colName": "email", "kind": "removed", "oldValue": "<null>",    "newValue": null },
+                        ],
+                    },
+                    {
+                        "primaryValue": "4",
+                        "oldPrimaryValue": null,
+                        "kind": "added",
+                        "cols": [
+                            { "colName": "name",  "kind": "added", "oldValue": null, "newValue": r#""Joe""# },
+                            { "colName": "age",   "kind": "added", "oldValue": null, "newValue": "15" },
+                            { "colName": "email", "kind": "added", "oldValue": null, "newValue": "<null>" },
+                        ],
+                    },
+                ],
+            }),
+            serde_json::to_value(act).unwrap()
+        );
+    }
+
+    #[test]
+    fn cols() {
+        let table_snapshot1 = mk_table_snapshot(vec!["name", "age"], vec![vec![n("1"), s("John"), n("29")]]);
+        let table_snapshot2 = mk_table_snapshot(vec!["name", "email"], vec![vec![n("1"), s("John"), s("john@example.com")]]);
+
+        let act = TableDiffJson::from(create_table_diff(Some(&table_snapshot1), Some(&table_snapshot2)));
+
+        // 片方にしかない列は追加または削除として返す
+        assert_eq!(
+            json!({
+                "tableName": "users",
+                "renamedFrom": null,
+                "primaryColName": "id",
+                "colNames": ["name", "age", "email"],
+                "rows": [
+                    {
+                        "primaryValue": "1",
+                        "oldPrimaryValue": null,
+                        "kind": "modified",
+                        "cols": [
+                            { "colName": "name",  "kind": "unchanged", "oldValue": r#""John""#, "newValue": r#""John""# },
+                            { "colName": "age",   "kind": "removed",   "oldValue": "29",        "newValue": null },
+                            { "colName": "email", "kind": "added",     "oldValue": null,        "newValue": r#""john@example.com""# },
+                        ],
+                    },
+                ],
+            }),
+            serde_json::to_value(act).unwrap()
+        );
+    }
+
+    fn primary_values(act: &TableDiffPageJson) -> Vec<&str> {
+        act.rows.iter().map(|row| row.primary_value.as_str()).collect()
+    }
+
+    #[test]
+    fn page() {
+        let table_snapshot1 = mk_table_snapshot(
+            vec!["name"],
+            vec![vec![n("1"), s("John")], vec![n("2"), s("Jack")], vec![n("3"), s("Jill")], vec![n("4"), s("Joe")], vec![n("5"), s("Jim")]],
+        );
+        let table_snapshot2 =
+            mk_table_snapshot(vec!["name"], vec![vec![n("1"), s("Jane")], vec![n("3"), s("Jill")], vec![n("4"), s("Jo")], vec![n("6"), s("Jeff")]]);
+        let table_diff = || create_table_diff(Some(&table_snapshot1), Some(&table_snapshot2));
+
+        // 変更のない行は数えない
+        let act = TableDiffPageJson::from(&table_diff(), &TableDiffPageRequestJson { after: None, offset: 0, limit: 2, kinds: vec![] }).unwrap();
+        assert_eq!((vec!["1", "2"], 5), (primary_values(&act), act.total));
+
+        let act = TableDiffPageJson::from(&table_diff(), &TableDiffPageRequestJson { after: None, offset: 2, limit: 2, kinds: vec![] }).unwrap();
+        assert_eq!((vec!["4", "5"], 5), (primary_values(&act), act.total));
+
+        // after の行より後ろだけを返す
+        let after = |after: &str, kinds: Vec<RowChangeKind>| TableDiffPageRequestJson { after: Some(after.to_string()), offset: 0, limit: 10, kinds };
+        let act = TableDiffPageJson::from(&table_diff(), &after("2", vec![])).unwrap();
+        assert_eq!((vec!["4", "5", "6"], 3), (primary_values(&act), act.total));
+
+        // after の行が変更のない行や絞り込みで除かれた行でも、その後ろから返す
+        let act = TableDiffPageJson::from(&table_diff(), &after("3", vec![])).unwrap();
+        assert_eq!((vec!["4", "5", "6"], 3), (primary_values(&act), act.total));
+        let act = TableDiffPageJson::from(&table_diff(), &after("1", vec![RowChangeKind::Removed])).unwrap();
+        assert_eq!((vec!["2", "5"], 2), (primary_values(&act), act.total));
+
+        // 変更の種類で絞り込む
+        let act = TableDiffPageJson::from(
+            &table_diff(),
+            &TableDiffPageRequestJson { after: None, offset: 0, limit: 10, kinds: vec![RowChangeKind::Removed, RowChangeKind::Added] },
+        )
+        .unwrap();
+        assert_eq!((vec!["2", "5", "6"], 3), (primary_values(&act), act.total));
+
+        // 数値の主キーは数値として並べる
+        let table_snapshot1 = mk_table_snapshot(vec!["name"], vec![vec![n("9"), s("John")], vec![n("10"), s("Jack")]]);
+        let table_snapshot2 = mk_table_snapshot(vec!["name"], vec![vec![n("9"), s("Jane")], vec![n("10"), s("Jill")], vec![n("100"), s("Jeff")]]);
+        let table_diff = create_table_diff(Some(&table_snapshot1), Some(&table_snapshot2));
+
+        let act = TableDiffPageJson::from(&table_diff, &TableDiffPageRequestJson { after: None, offset: 0, limit: 10, kinds: vec![] }).unwrap();
+        assert_eq!(vec!["9", "10", "100"], primary_values(&act));
+        let act = TableDiffPageJson::from(&table_diff, &after("9", vec![])).unwrap();
+        assert_eq!(vec!["10", "100"], primary_values(&act));
+    }
+
+    #[test]
+    fn rekeyed() {
+        let table_snapshot1 = mk_table_snapshot(vec!["name", "age"], vec![vec![n("1"), s("John"), n("29")], vec![n("2"), s("Jack"), n("31")]]);
+        let table_snapshot2 = mk_table_snapshot(vec!["name", "age"], vec![vec![n("1"), s("John"), n("29")], vec![n("3"), s("Jack"), n("32")]]);
+
+        let mut table_diff = create_table_diff(Some(&table_snapshot1), Some(&table_snapshot2));
+        table_diff.detect_rekeys(1);
+        let act = TableDiffJson::from(table_diff);
+
+        // 削除された行と追加された行をひとつにまとめる
+        assert_eq!(
+            json!([
+                {
+                    "primaryValue": "3",
+                    "oldPrimaryValue": "2",
+                    "kind": "rekeyed",
+                    "cols": [
+                        { "colName": "name", "kind": "unchanged", "oldValue": r#""Jack""#, "newValue": r#""Jack""# },
+                        { "colName": "age",  "kind": "modified",  "oldValue": "31",        "newValue": "32" },
+                    ],
+                },
+            ]),
+            serde_json::to_value(act.rows).unwrap()
+        );
+    }
+
+    #[test]
+    fn grouped() {
+        let orders =
+            TableSnapshot::new(&"orders".to_string(), "id".to_string(), vec!["status".to_string()], vec![RowSnapshot::new(vec![n("17"), s("new")])]);
+        let payments = TableSnapshot::new(
+            &"payments".to_string(),
+            "id".to_string(),
+            vec!["order_id".to_string()],
+            vec![RowSnapshot::new(vec![n("6"), n("17")])],
+        );
+        let table_diffs = vec![create_table_diff(None, Some(&orders)), create_table_diff(None, Some(&payments))];
+        let foreign_key =
+            ForeignKey { col_name: "order_id".to_string(), referenced_table_name: "orders".to_string(), referenced_col_name: "id".to_string() };
+
+        let act = group_changed_rows(&table_diffs, &[("payments".to_string(), foreign_key)])
+            .into_iter()
+            .map(|grouped_row| DiffGroupJson::from(grouped_row, &table_diffs))
+            .collect::<Vec<_>>();
+
+        // 行ごとに変更された列も返す
+        assert_eq!(
+            json!([
+                {
+                    "description": "orders 17 added with 1 payments",
+                    "root": {
+                        "tableName": "orders",
+                        "primaryValue": "17",
+                        "kind": "added",
+                        "cols": [{ "colName": "status", "kind": "added", "oldValue": null, "newValue": r#""new""# }],
+                        "children": [
+                            {
+                                "tableName": "payments",
+                                "primaryValue": "6",
+                                "kind": "added",
+                                "cols": [{ "colName": "order_id", "kind": "added", "oldValue": null, "newValue": "17" }],
+                                "children": [],
+                            },
+                        ],
+                    },
+                },
+            ]),
+            serde_json::to_value(act).unwrap()
+        );
+    }
+}
@@ -0,0 +1,184 @@
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::command::state::AppState;
+use crate::domain::history::{ColChange, ColStats, ColStatsPoint, ColStatsSeries, RowHistory, RowHistoryEntry, RowHistoryKind};
+use crate::domain::project::ProjectId;
+use crate::domain::schema::{ColName, PrimaryValue, TableName};
+use crate::domain::snapshot::{SnapshotId, SnapshotName};
+use crate::error::Result;
+use crate::history::{find_col_stats_series, find_row_history};
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RowHistoryJson {
+    pub table_name: TableName,
+    pub primary_value: PrimaryValue,
+    pub entries: Vec<RowHistoryEntryJson>,
+}
+
+impl RowHistoryJson {
+    fn from(row_history: RowHistory) -> Self {
+        Self {
+            table_name: row_history.table_name,
+            primary_value: row_history.primary_value,
+            entries: row_history.entries.into_iter().map(RowHistoryEntryJson::from).collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RowHistoryEntryJson {
+    pub snapshot_id: SnapshotId,
+    pub snapshot_name: SnapshotName,
+    pub create_at: String,
+    pub kind: RowHistoryKindJson,
+    pub cols: Vec<ColChangeJson>,
+}
+
+impl RowHistoryEntryJson {
+    fn from(entry: RowHistoryEntry) -> Self {
+        Self {
+            snapshot_id: entry.snapshot_summary.snapshot_id,
+            snapshot_name: entry.snapshot_summary.snapshot_name,
+            create_at: entry.snapshot_summary.create_at,
+            kind: RowHistoryKindJson::from(entry.kind),
+            cols: entry.cols.into_iter().map(ColChangeJson::from).collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RowHistoryKindJson {
+    Appeared,
+    Changed,
+    Disappeared,
+}
+
+impl RowHistoryKindJson {
+    fn from(kind: RowHistoryKind) -> Self {
+        match kind {
+            RowHistoryKind::Appeared => Self::Appeared,
+            RowHistoryKind::Changed => Self::Changed,
+            RowHistoryKind::Disappeared => Self::Disappeared,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColChangeJson {
+    pub col_name: ColName,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+}
+
+impl ColChangeJson {
+    fn from(col_change: ColChange) -> Self {
+        Self {
+            col_name: col_change.col_name,
+            old_value: col_change.old_value.map(|v| v.as_display_value()),
+            new_value: col_change.new_value.map(|v| v.as_display_value()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColStatsSeriesJson {
+    pub table_name: TableName,
+    pub col_name: ColName,
+    pub points: Vec<ColStatsPointJson>,
+}
+
+impl ColStatsSeriesJson {
+    fn from(col_stats_series: ColStatsSeries) -> Self {
+        Self {
+            table_name: col_stats_series.table_name,
+            col_name: col_stats_series.col_name,
+            points: col_stats_series.points.into_iter().map(ColStatsPointJson::from).collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColStatsPointJson {
+    pub snapshot_id: SnapshotId,
+    pub snapshot_name: SnapshotName,
+    pub create_at: String,
+    pub stats: Option<ColStatsJson>,
+}
+
+impl ColStatsPointJson {
+    fn from(point: ColStatsPoint) -> Self {
+        Self {
+            snapshot_id: point.snapshot_summary.snapshot_id,
+            snapshot_name: point.snapshot_summary.snapshot_name,
+            create_at: point.snapshot_summary.create_at,
+            stats: point.stats.map(ColStatsJson::from),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColStatsJson {
+    pub row_count: usize,
+    pub null_count: usize,
+    pub distinct_count: usize,
+    pub min: Option<String>,
+    pub max: Option<String>,
+    pub value_counts: Vec<ValueCountJson>,
+}
+
+impl ColStatsJson {
+    fn from(stats: ColStats) -> Self {
+        Self {
+            row_count: stats.row_count,
+            null_count: stats.null_count,
+            distinct_count: stats.distinct_count,
+            min: stats.min.map(|v| v.as_display_value()),
+            max: stats.max.map(|v| v.as_display_value()),
+            value_counts: stats.value_counts.into_iter().map(|(value, count)| ValueCountJson { value: value.as_display_value(), count }).collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValueCountJson {
+    pub value: String,
+    pub count: usize,
+}
+
+const DEFAULT_TOP_VALUES: usize = 10;
+
+#[tauri::command]
+pub fn find_row_history_command(
+    app_state: State<'_, AppState>,
+    project_id: ProjectId,
+    table_name: TableName,
+    primary_value: PrimaryValue,
+) -> Result<RowHistoryJson> {
+    let mut conn = app_state.conn()?;
+
+    Ok(RowHistoryJson::from(find_row_history(&mut conn, &project_id, &table_name, &primary_value)?))
+}
+
+#[tauri::command]
+pub fn find_col_stats_series_command(
+    app_state: State<'_, AppState>,
+    project_id: ProjectId,
+    table_name: TableName,
+    col_name: ColName,
+    top: Option<usize>,
+) -> Result<ColStatsSeriesJson> {
+    let mut conn = app_state.conn()?;
+
+    let col_stats_series = find_col_stats_series(&mut conn, &project_id, &table_name, &col_name, top.unwrap_or(DEFAULT_TOP_VALUES))?;
+
+    Ok(ColStatsSeriesJson::from(col_stats_series))
+}
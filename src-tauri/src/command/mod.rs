@@ -1,4 +1,7 @@
+pub mod archive;
 pub mod diff;
+pub mod history;
 pub mod project;
+pub mod schedule;
 pub mod snapshot;
 pub mod state;
@@ -1,11 +1,17 @@
+use std::collections::HashMap;
+
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use tauri::State;
 
 use crate::command::state::AppState;
-use crate::db::project::{all_projects, delete_project, insert_project, update_project};
+use crate::db::project::{all_projects, delete_project, find_project, insert_project, update_project, update_project_password};
+use crate::domain::diagnosis::{ConnectionReport, TableReport, UnsupportedCol};
 use crate::domain::project::Rdbms::Mysql;
-use crate::domain::project::{Project, ProjectId};
+use crate::domain::project::{ConnectionOptions, Project, ProjectId, SshJump, SslMode};
+use crate::domain::schema::{ColName, TableName};
+use crate::dump::test_connection;
+use crate::error::{Error, Result};
 
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -14,10 +20,13 @@ pub struct ProjectJson {
     pub rdbms: String,
     pub name: String,
     pub user: String,
-    pub password: String,
+    #[serde(default)]
+    pub has_password: bool,
     pub host: String,
     pub port: String,
     pub schema: String,
+    #[serde(default)]
+    pub options: ConnectionOptionsJson,
 }
 
 impl ProjectJson {
@@ -29,61 +38,231 @@ impl ProjectJson {
                 Mysql => "MySQL".to_string(),
             },
             user: project.user,
-            password: project.password,
+            has_password: !project.password.is_empty(),
             host: project.host,
             port: project.port,
             schema: project.schema,
+            options: ConnectionOptionsJson::from(project.options),
         }
     }
 
-    fn into(self) -> Project {
-        Project::new(
-            &self.project_id,
-            &self.name,
-            match self.rdbms.as_ref() {
-                "MySQL" => Mysql,
-                _ => unreachable!(),
-            },
-            &self.user,
-            &self.password,
-            &self.host,
-            &self.port,
-            &self.schema,
-        )
+    // パスワードは画面に返さないため、暗号化済みのものを別途受け取る
+    fn into(self, password: String) -> Result<Project> {
+        let rdbms = match self.rdbms.as_ref() {
+            "MySQL" => Mysql,
+            rdbms => return Err(Error::InvalidInput(format!("unsupported rdbms: {rdbms}"))),
+        };
+        Ok(Project::new(&self.project_id, &self.name, rdbms, &self.user, &password, &self.host, &self.port, &self.schema)
+            .with_options(self.options.into()))
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionOptionsJson {
+    pub url: Option<String>,
+    pub ssl_mode: SslMode,
+    pub ssl_ca_path: Option<String>,
+    pub socket: Option<String>,
+    pub connect_timeout_sec: Option<u64>,
+    pub read_timeout_sec: Option<u64>,
+    pub charset: Option<String>,
+    pub init_sql: Vec<String>,
+    pub ssh: Option<SshJumpJson>,
+    #[serde(default)]
+    pub watermark_cols: HashMap<TableName, ColName>,
+}
+
+impl ConnectionOptionsJson {
+    fn from(options: ConnectionOptions) -> Self {
+        Self {
+            url: options.url,
+            ssl_mode: options.ssl_mode,
+            ssl_ca_path: options.ssl_ca_path,
+            socket: options.socket,
+            connect_timeout_sec: options.connect_timeout_sec,
+            read_timeout_sec: options.read_timeout_sec,
+            charset: options.charset,
+            init_sql: options.init_sql,
+            ssh: options.ssh.map(SshJumpJson::from),
+            watermark_cols: options.watermark_cols,
+        }
+    }
+
+    fn into(self) -> ConnectionOptions {
+        // 画面の空欄は未指定として扱う
+        let non_empty = |value: Option<String>| value.filter(|value| !value.trim().is_empty());
+        ConnectionOptions {
+            url: non_empty(self.url),
+            ssl_mode: self.ssl_mode,
+            ssl_ca_path: non_empty(self.ssl_ca_path),
+            socket: non_empty(self.socket),
+            connect_timeout_sec: self.connect_timeout_sec,
+            read_timeout_sec: self.read_timeout_sec,
+            charset: non_empty(self.charset),
+            init_sql: self.init_sql.into_iter().filter(|sql| !sql.trim().is_empty()).collect(),
+            ssh: self.ssh.map(SshJumpJson::into),
+            watermark_cols: self
+                .watermark_cols
+                .into_iter()
+                .filter(|(table_name, col_name)| !table_name.trim().is_empty() && !col_name.trim().is_empty())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SshJumpJson {
+    pub host: String,
+    pub port: u16,
+    pub user: String,
+    pub key_path: String,
+}
+
+impl SshJumpJson {
+    fn from(ssh: SshJump) -> Self {
+        Self { host: ssh.host, port: ssh.port, user: ssh.user, key_path: ssh.key_path }
+    }
+
+    fn into(self) -> SshJump {
+        SshJump::new(self.host, self.port, self.user, self.key_path)
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionReportJson {
+    pub ok: bool,
+    pub connected: bool,
+    pub error: Option<String>,
+    pub server_version: Option<String>,
+    pub schema_visible: bool,
+    pub tables: Vec<TableReportJson>,
+}
+
+impl ConnectionReportJson {
+    fn from(report: ConnectionReport) -> Self {
+        Self {
+            ok: report.is_ok(),
+            connected: report.connected,
+            error: report.error,
+            server_version: report.server_version,
+            schema_visible: report.schema_visible,
+            tables: report.tables.into_iter().map(TableReportJson::from).collect_vec(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableReportJson {
+    pub table_name: String,
+    pub estimated_rows: u64,
+    pub selectable: bool,
+    pub has_primary_key: bool,
+    pub unsupported_cols: Vec<UnsupportedColJson>,
+}
+
+impl TableReportJson {
+    fn from(report: TableReport) -> Self {
+        Self {
+            table_name: report.table_name,
+            estimated_rows: report.estimated_rows,
+            selectable: report.selectable,
+            has_primary_key: report.has_primary_key,
+            unsupported_cols: report.unsupported_cols.into_iter().map(UnsupportedColJson::from).collect_vec(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnsupportedColJson {
+    pub col_name: String,
+    pub data_type: String,
+}
+
+impl UnsupportedColJson {
+    fn from(col: UnsupportedCol) -> Self {
+        Self { col_name: col.col_name, data_type: col.data_type }
     }
 }
 
 #[tauri::command]
-pub fn all_projects_command(app_state: State<'_, AppState>) -> Result<Vec<ProjectJson>, String> {
-    let mut conn = app_state.conn.lock().unwrap();
+pub fn all_projects_command(app_state: State<'_, AppState>) -> Result<Vec<ProjectJson>> {
+    let mut conn = app_state.conn()?;
+
+    all_projects(&mut conn).map(|projects| projects.into_iter().map(ProjectJson::from).collect_vec()).map_err(Error::from)
+}
+
+#[tauri::command]
+pub fn insert_project_command(app_state: State<'_, AppState>, project_json: ProjectJson, password: String) -> Result<()> {
+    let mut conn = app_state.conn()?;
+
+    let password = app_state.key.encrypt(&password)?;
+    let project = project_json.into(password)?;
+    project.options.validate().map_err(Error::invalid_input)?;
 
-    all_projects(&mut conn).map(|projects| projects.into_iter().map(ProjectJson::from).collect_vec()).map_err(|e| e.to_string())
+    insert_project(&mut conn, &project).map_err(Error::from)
 }
 
 #[tauri::command]
-pub fn select_project_command(app_state: State<'_, AppState>, project_id: ProjectId) -> Result<(), String> {
-    app_state.set_project_id(project_id);
+pub fn update_project_command(app_state: State<'_, AppState>, project_json: ProjectJson) -> Result<()> {
+    let mut conn = app_state.conn()?;
 
-    Ok(())
+    let project = project_json.into(String::new())?;
+    project.options.validate().map_err(Error::invalid_input)?;
+
+    update_project(&mut conn, &project).map_err(Error::from)
 }
 
 #[tauri::command]
-pub fn insert_project_command(app_state: State<'_, AppState>, project_json: ProjectJson) -> Result<(), String> {
-    let mut conn = app_state.conn.lock().unwrap();
+pub fn set_project_password_command(app_state: State<'_, AppState>, project_id: ProjectId, password: String) -> Result<()> {
+    let mut conn = app_state.conn()?;
+
+    let password = app_state.key.encrypt(&password)?;
 
-    insert_project(&mut conn, &project_json.into()).map_err(|e| e.to_string())
+    update_project_password(&mut conn, &project_id, &password).map_err(Error::from)
 }
 
 #[tauri::command]
-pub fn update_project_command(app_state: State<'_, AppState>, project_json: ProjectJson) -> Result<(), String> {
-    let mut conn = app_state.conn.lock().unwrap();
+pub fn delete_project_command(app_state: State<'_, AppState>, project_id: ProjectId) -> Result<()> {
+    let mut conn = app_state.conn()?;
 
-    update_project(&mut conn, &project_json.into()).map_err(|e| e.to_string())
+    delete_project(&mut conn, &project_id).map_err(Error::from)
 }
 
 #[tauri::command]
-pub fn delete_project_command(app_state: State<'_, AppState>, project_id: ProjectId) -> Result<(), String> {
-    let mut conn = app_state.conn.lock().unwrap();
+pub fn test_project_connection_command(app_state: State<'_, AppState>, project_id: ProjectId) -> Result<ConnectionReportJson> {
+    let project = {
+        let mut conn = app_state.conn()?;
+        find_project(&mut conn, &project_id)?
+    };
+
+    Ok(ConnectionReportJson::from(test_connection(&project, &app_state.key)))
+}
 
-    delete_project(&mut conn, &project_id).map_err(|e| e.to_string())
+#[cfg(test)]
+mod tests {
+    use crate::command::project::{ConnectionOptionsJson, ProjectJson};
+    use crate::error::Error;
+
+    #[test]
+    fn into() {
+        let project_json = |rdbms: &str| ProjectJson {
+            project_id: "1".to_string(),
+            rdbms: rdbms.to_string(),
+            name: "test-project".to_string(),
+            user: "user".to_string(),
+            has_password: false,
+            host: "127.0.0.1".to_string(),
+            port: "3306".to_string(),
+            schema: "testdata".to_string(),
+            options: ConnectionOptionsJson::default(),
+        };
+
+        assert!(project_json("MySQL").into(String::new()).is_ok());
+        assert_eq!(Err(Error::InvalidInput("unsupported rdbms: PostgreSQL".to_string())), project_json("PostgreSQL").into(String::new()));
+    }
 }
@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::thread;
+use std::time::Duration;
+
+use chrono::{Local, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::command::state::AppState;
+use crate::db::project::find_project;
+use crate::db::schedule::{all_snapshot_schedules, delete_snapshot_schedule, save_snapshot_schedule};
+use crate::domain::project::ProjectId;
+use crate::domain::schedule::{SnapshotSchedule, Trigger};
+use crate::domain::snapshot::SnapshotId;
+use crate::error::{Error, Result};
+use crate::scheduler::{take_auto_snapshot, AutoSnapshot};
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotScheduleJson {
+    pub project_id: ProjectId,
+    pub trigger: TriggerJson,
+    pub name_prefix: String,
+    pub retention: usize,
+    pub enabled: bool,
+}
+
+impl SnapshotScheduleJson {
+    fn from(schedule: SnapshotSchedule) -> Self {
+        Self {
+            project_id: schedule.project_id,
+            trigger: TriggerJson::from(schedule.trigger),
+            name_prefix: schedule.name_prefix,
+            retention: schedule.retention,
+            enabled: schedule.enabled,
+        }
+    }
+
+    fn into(self) -> SnapshotSchedule {
+        SnapshotSchedule {
+            project_id: self.project_id,
+            trigger: self.trigger.into(),
+            name_prefix: self.name_prefix,
+            retention: self.retention,
+            enabled: self.enabled,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum TriggerJson {
+    Interval { seconds: u64 },
+    Cron { expression: String },
+}
+
+impl TriggerJson {
+    fn from(trigger: Trigger) -> Self {
+        match trigger {
+            Trigger::Interval { seconds } => TriggerJson::Interval { seconds },
+            Trigger::Cron { expression } => TriggerJson::Cron { expression },
+        }
+    }
+
+    fn into(self) -> Trigger {
+        match self {
+            TriggerJson::Interval { seconds } => Trigger::Interval { seconds },
+            TriggerJson::Cron { expression } => Trigger::Cron { expression },
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AutoSnapshotJson {
+    pub project_id: ProjectId,
+    pub snapshot_id: Option<SnapshotId>,
+    pub skipped: bool,
+    pub deleted_snapshot_ids: Vec<SnapshotId>,
+    pub error: Option<Error>,
+}
+
+impl AutoSnapshotJson {
+    fn from(project_id: ProjectId, result: Result<AutoSnapshot>) -> Self {
+        match result {
+            Ok(AutoSnapshot::Created { snapshot_id, deleted_snapshot_ids }) => {
+                Self { project_id, snapshot_id: Some(snapshot_id), skipped: false, deleted_snapshot_ids, error: None }
+            }
+            Ok(AutoSnapshot::Skipped) => Self { project_id, snapshot_id: None, skipped: true, deleted_snapshot_ids: vec![], error: None },
+            Err(e) => Self { project_id, snapshot_id: None, skipped: false, deleted_snapshot_ids: vec![], error: Some(e) },
+        }
+    }
+}
+
+pub const AUTO_SNAPSHOT_EVENT: &str = "auto-snapshot";
+// スケジュールを読めなかった場合は、プロジェクトに関係なく通知する
+pub const SCHEDULER_ERROR_EVENT: &str = "scheduler-error";
+
+const SCHEDULER_TICK: Duration = Duration::from_secs(1);
+
+// スケジュールは毎回ストアから読み直すので、保存や削除はすぐに反映される
+// ダンプは順番に実行するため、時間のかかるダンプの間は他のプロジェクトの実行が遅れる
+pub fn start_scheduler(app_handle: AppHandle) {
+    thread::spawn(move || {
+        let mut last_runs: HashMap<ProjectId, NaiveDateTime> = HashMap::new();
+        loop {
+            thread::sleep(SCHEDULER_TICK);
+
+            let app_state = app_handle.state::<AppState>();
+            let schedules = match app_state.conn().and_then(|mut conn| all_snapshot_schedules(&mut conn).map_err(Error::from)) {
+                Ok(schedules) => schedules,
+                Err(e) => {
+                    let _ = app_handle.emit_all(SCHEDULER_ERROR_EVENT, e);
+                    continue;
+                }
+            };
+
+            for schedule in schedules {
+                let now = Local::now().naive_local();
+                if !schedule.is_due(last_runs.get(&schedule.project_id).copied(), now) {
+                    continue;
+                }
+                last_runs.insert(schedule.project_id.clone(), now);
+
+                let result = app_state.conn().and_then(|mut conn| {
+                    let project = find_project(&mut conn, &schedule.project_id)?;
+                    take_auto_snapshot(&mut conn, &project, &app_state.key, &schedule, now).map_err(Error::from)
+                });
+                let _ = app_handle.emit_all(AUTO_SNAPSHOT_EVENT, AutoSnapshotJson::from(schedule.project_id, result));
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub fn find_snapshot_schedule_command(app_state: State<'_, AppState>, project_id: ProjectId) -> Result<Option<SnapshotScheduleJson>> {
+    let mut conn = app_state.conn()?;
+
+    let schedules = all_snapshot_schedules(&mut conn)?;
+
+    Ok(schedules.into_iter().find(|schedule| schedule.project_id == project_id).map(SnapshotScheduleJson::from))
+}
+
+#[tauri::command]
+pub fn save_snapshot_schedule_command(app_state: State<'_, AppState>, snapshot_schedule_json: SnapshotScheduleJson) -> Result<()> {
+    let mut conn = app_state.conn()?;
+
+    let schedule = snapshot_schedule_json.into();
+    schedule.validate().map_err(Error::invalid_input)?;
+
+    save_snapshot_schedule(&mut conn, &schedule).map_err(Error::from)
+}
+
+#[tauri::command]
+pub fn delete_snapshot_schedule_command(app_state: State<'_, AppState>, project_id: ProjectId) -> Result<()> {
+    let mut conn = app_state.conn()?;
+
+    delete_snapshot_schedule(&mut conn, &project_id).map_err(Error::from)
+}
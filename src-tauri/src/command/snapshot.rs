@@ -1,12 +1,19 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
-use tauri::State;
+use tauri::{AppHandle, Manager, State};
 
 use crate::command::state::AppState;
-use crate::db::project::all_projects;
+use crate::db::project::find_project;
 use crate::db::snapshot::{all_snapshot_summaries, delete_snapshot_summary, update_snapshot_summary};
+use crate::domain::project::ProjectId;
+use crate::domain::schema::TableName;
 use crate::domain::snapshot::{SnapshotId, SnapshotName, SnapshotSummary};
-use crate::dump::dump;
+use crate::dump::{create_job_id, dump, DumpProgress, JobId};
+use crate::error::{Error, Result};
 
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -27,40 +34,121 @@ impl SnapshotSummaryJson {
 }
 
 #[tauri::command]
-pub fn all_snapshot_summaries_command(app_state: State<'_, AppState>) -> Result<Vec<SnapshotSummaryJson>, String> {
-    let mut conn = app_state.conn.lock().unwrap();
-    let project_id = app_state.project_id.lock().unwrap();
-    let project_id = project_id.as_ref().unwrap();
+pub fn all_snapshot_summaries_command(app_state: State<'_, AppState>, project_id: ProjectId) -> Result<Vec<SnapshotSummaryJson>> {
+    let mut conn = app_state.conn()?;
 
-    all_snapshot_summaries(&mut conn, project_id)
+    all_snapshot_summaries(&mut conn, &project_id)
         .map(|snapshot_summaries| snapshot_summaries.into_iter().map(SnapshotSummaryJson::from).collect_vec())
-        .map_err(|e| e.to_string())
+        .map_err(Error::from)
 }
 
 #[tauri::command]
-pub fn update_snapshot_summary_command(app_state: State<'_, AppState>, snapshot_summary_json: SnapshotSummaryJson) -> Result<(), String> {
-    let mut conn = app_state.conn.lock().unwrap();
+pub fn update_snapshot_summary_command(app_state: State<'_, AppState>, snapshot_summary_json: SnapshotSummaryJson) -> Result<()> {
+    let mut conn = app_state.conn()?;
 
-    update_snapshot_summary(&mut conn, &snapshot_summary_json.into()).map_err(|e| e.to_string())
+    update_snapshot_summary(&mut conn, &snapshot_summary_json.into()).map_err(Error::from)
 }
 
 #[tauri::command]
-pub fn delete_snapshot_summary_command(app_state: State<'_, AppState>, snapshot_id: SnapshotId) -> Result<(), String> {
-    let mut conn = app_state.conn.lock().unwrap();
+pub fn delete_snapshot_summary_command(app_state: State<'_, AppState>, snapshot_id: SnapshotId) -> Result<()> {
+    let mut conn = app_state.conn()?;
+
+    delete_snapshot_summary(&mut conn, &snapshot_id).map_err(Error::from)
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DumpProgressJson {
+    pub job_id: JobId,
+    pub snapshot_id: SnapshotId,
+    pub table_count: usize,
+    pub tables_done: usize,
+    pub rows_fetched: usize,
+    pub current_table: Option<TableName>,
+}
+
+impl DumpProgressJson {
+    fn from(job_id: &JobId, progress: &DumpProgress) -> Self {
+        Self {
+            job_id: job_id.clone(),
+            snapshot_id: progress.snapshot_id.clone(),
+            table_count: progress.table_count,
+            tables_done: progress.tables_done,
+            rows_fetched: progress.rows_fetched,
+            current_table: progress.current_table.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DumpFinishedJson {
+    pub job_id: JobId,
+    pub status: String,
+    pub snapshot_id: Option<SnapshotId>,
+    pub error: Option<Error>,
+}
 
-    delete_snapshot_summary(&mut conn, &snapshot_id).map_err(|e| e.to_string())
+impl DumpFinishedJson {
+    fn from(job_id: JobId, result: Result<SnapshotId>) -> Self {
+        match result {
+            Ok(snapshot_id) => Self { job_id, status: "completed".to_string(), snapshot_id: Some(snapshot_id), error: None },
+            Err(Error::Cancelled) => Self { job_id, status: "cancelled".to_string(), snapshot_id: None, error: None },
+            Err(e) => Self { job_id, status: "failed".to_string(), snapshot_id: None, error: Some(e) },
+        }
+    }
 }
 
+pub const DUMP_PROGRESS_EVENT: &str = "dump-progress";
+pub const DUMP_FINISHED_EVENT: &str = "dump-finished";
+
+// ダンプはプールから取った専用の接続でスレッドを立てて実行し、進捗はイベントで通知する
 #[tauri::command]
-pub fn dump_snapshot_command(app_state: State<'_, AppState>, snapshot_name: SnapshotName) -> Result<(), String> {
-    let mut conn = app_state.conn.lock().unwrap();
-    let project_id = app_state.project_id.lock().unwrap();
-    let project_id = project_id.as_ref().unwrap();
+pub fn dump_snapshot_command(
+    app_handle: AppHandle,
+    app_state: State<'_, AppState>,
+    project_id: ProjectId,
+    snapshot_name: SnapshotName,
+) -> Result<JobId> {
+    let project = {
+        let mut conn = app_state.conn()?;
+        find_project(&mut conn, &project_id)?
+    };
+
+    let job_id = create_job_id();
+    let cancelled = Arc::new(AtomicBool::new(false));
+    app_state.dump_jobs.lock().unwrap().insert(job_id.clone(), cancelled.clone());
+
+    thread::spawn({
+        let job_id = job_id.clone();
+        move || {
+            let app_state = app_handle.state::<AppState>();
+
+            let result = app_state.conn().and_then(|mut conn| {
+                dump(&mut conn, &project, &app_state.key, snapshot_name, &cancelled, |progress| {
+                    let _ = app_handle.emit_all(DUMP_PROGRESS_EVENT, DumpProgressJson::from(&job_id, progress));
+                })
+                .map_err(Error::from)
+            });
 
-    let projects = all_projects(&mut conn).map_err(|e| e.to_string())?;
-    let project = projects.iter().find(|project| &project.project_id == project_id).unwrap();
+            app_state.dump_jobs.lock().unwrap().remove(&job_id);
+            let _ = app_handle.emit_all(DUMP_FINISHED_EVENT, DumpFinishedJson::from(job_id, result));
+        }
+    });
 
-    dump(&mut conn, project, snapshot_name).map_err(|e| e.to_string())?;
+    Ok(job_id)
+}
+
+#[tauri::command]
+pub fn cancel_dump_command(app_state: State<'_, AppState>, job_id: JobId) -> Result<()> {
+    let dump_jobs = app_state.dump_jobs.lock().unwrap();
+    let cancelled = dump_jobs.get(&job_id).ok_or_else(|| Error::NotFound(format!("dump job {job_id}")))?;
+    cancelled.store(true, Ordering::SeqCst);
 
     Ok(())
 }
+
+#[tauri::command]
+pub fn running_dump_jobs_command(app_state: State<'_, AppState>) -> Result<Vec<JobId>> {
+    Ok(app_state.dump_jobs.lock().unwrap().keys().cloned().sorted().collect_vec())
+}
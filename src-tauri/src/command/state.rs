@@ -1,22 +1,43 @@
-use std::sync::Mutex;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
 
-use mysql::Conn;
+use crate::credential::{migrate_project_passwords, CredentialKey};
+use crate::db::{create_pool, migrate, ConnPool, PooledConn};
+use crate::domain::diff::TableDiff;
+use crate::domain::schema::TableName;
+use crate::domain::snapshot::SnapshotId;
+use crate::dump::JobId;
+use crate::error::Error;
 
-use crate::db::create_connection;
-use crate::domain::project::ProjectId;
+// 比べた二つのスナップショット、テーブル名、主キーの変わった行を検出するときの異なる列数の上限
+pub type TableDiffKey = (SnapshotId, SnapshotId, TableName, Option<usize>);
 
 pub struct AppState {
-    pub conn: Mutex<Conn>,
-    pub project_id: Mutex<Option<ProjectId>>,
+    pub pool: ConnPool,
+    pub key: CredentialKey,
+    // 実行中のダンプのキャンセルフラグ
+    pub dump_jobs: Mutex<HashMap<JobId, Arc<AtomicBool>>>,
+    // ページを移るたびに差分を読み直さないよう、最後に読んだテーブルの差分を持っておく
+    // スナップショットは変更されないので、同じ組み合わせの差分が変わることはない
+    pub last_table_diff: Mutex<Option<(TableDiffKey, Arc<TableDiff>)>>,
 }
 
 impl AppState {
-    pub fn new() -> anyhow::Result<Self> {
-        Ok(Self { conn: Mutex::new(create_connection()?), project_id: Mutex::new(None) })
+    // 暗号化キーはアプリの設定ディレクトリに置く
+    pub fn new(config_dir: &Path) -> anyhow::Result<Self> {
+        let pool = create_pool()?;
+        let key = CredentialKey::load_or_create(config_dir)?;
+
+        let mut conn = pool.get()?;
+        migrate(&mut conn)?;
+        migrate_project_passwords(&mut conn, &key)?;
+
+        Ok(Self { pool, key, dump_jobs: Mutex::new(HashMap::new()), last_table_diff: Mutex::new(None) })
     }
 
-    pub fn set_project_id(&self, selected_project_id: ProjectId) {
-        let mut project_id = self.project_id.lock().unwrap();
-        *project_id = Some(selected_project_id);
+    pub fn conn(&self) -> crate::error::Result<PooledConn> {
+        self.pool.get().map_err(|e| Error::Connection(e.to_string()))
     }
 }
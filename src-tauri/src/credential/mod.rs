@@ -0,0 +1,150 @@
+use std::fs::{create_dir_all, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use aes_gcm::aead::{Aead, KeyInit, OsRng};
+use aes_gcm::{AeadCore, Aes256Gcm, Key, Nonce};
+use anyhow::anyhow;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use mysql::Conn;
+
+use crate::db::project::{all_projects, update_project_password};
+
+const KEY_FILE_NAME: &str = "credential.key";
+
+const ENCRYPTED_PREFIX: &str = "enc:v1:";
+
+const NONCE_LEN: usize = 12;
+
+pub struct CredentialKey {
+    cipher: Aes256Gcm,
+}
+
+impl CredentialKey {
+    pub fn load_or_create(dir: &Path) -> anyhow::Result<Self> {
+        let path = dir.join(KEY_FILE_NAME);
+        let key = if path.exists() { read_key(&path)? } else { create_key(dir, &path)? };
+
+        Ok(Self { cipher: Aes256Gcm::new(&key) })
+    }
+
+    // 未設定のパスワードは空文字のまま保存する
+    pub fn encrypt(&self, password: &str) -> anyhow::Result<String> {
+        if password.is_empty() {
+            return Ok(String::new());
+        }
+
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let encrypted = self.cipher.encrypt(&nonce, password.as_bytes()).map_err(|_| anyhow!("failed to encrypt password"))?;
+
+        let mut bytes = nonce.to_vec();
+        bytes.extend(encrypted);
+
+        Ok(format!("{ENCRYPTED_PREFIX}{}", STANDARD.encode(bytes)))
+    }
+
+    pub fn decrypt(&self, credential: &str) -> anyhow::Result<String> {
+        if credential.is_empty() {
+            return Ok(String::new());
+        }
+
+        let encoded = credential.strip_prefix(ENCRYPTED_PREFIX).ok_or_else(|| anyhow!("password is not encrypted"))?;
+        let bytes = STANDARD.decode(encoded)?;
+        if bytes.len() < NONCE_LEN {
+            return Err(anyhow!("broken credential"));
+        }
+
+        let (nonce, encrypted) = bytes.split_at(NONCE_LEN);
+        let decrypted = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), encrypted)
+            .map_err(|_| anyhow!("failed to decrypt password, the credential key may have been changed"))?;
+
+        Ok(String::from_utf8(decrypted)?)
+    }
+}
+
+pub fn is_encrypted(credential: &str) -> bool {
+    credential.is_empty() || credential.starts_with(ENCRYPTED_PREFIX)
+}
+
+// 平文で保存されているパスワードを暗号化する
+pub fn migrate_project_passwords(conn: &mut Conn, key: &CredentialKey) -> anyhow::Result<()> {
+    for project in all_projects(conn)? {
+        if !is_encrypted(&project.password) {
+            update_project_password(conn, &project.project_id, &key.encrypt(&project.password)?)?;
+        }
+    }
+    Ok(())
+}
+
+fn read_key(path: &PathBuf) -> anyhow::Result<Key<Aes256Gcm>> {
+    let mut encoded = String::new();
+    File::open(path)?.read_to_string(&mut encoded)?;
+
+    let bytes = STANDARD.decode(encoded.trim())?;
+    if bytes.len() != 32 {
+        return Err(anyhow!("broken credential key: {}", path.display()));
+    }
+
+    Ok(*Key::<Aes256Gcm>::from_slice(&bytes))
+}
+
+fn create_key(dir: &Path, path: &PathBuf) -> anyhow::Result<Key<Aes256Gcm>> {
+    create_dir_all(dir)?;
+
+    let key = Aes256Gcm::generate_key(OsRng);
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(path)?;
+    file.write_all(STANDARD.encode(key).as_bytes())?;
+    file.flush()?;
+
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::credential::{is_encrypted, CredentialKey};
+
+    #[test]
+    fn encrypt_and_decrypt() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+
+        let key = CredentialKey::load_or_create(&dir)?;
+
+        let encrypted = key.encrypt("p@ss/w:rd")?;
+        assert!(is_encrypted(&encrypted));
+        assert!(!encrypted.contains("p@ss/w:rd"));
+        assert_ne!(encrypted, key.encrypt("p@ss/w:rd")?);
+
+        // 再読み込みした鍵でも復号できる
+        let key = CredentialKey::load_or_create(&dir)?;
+        assert_eq!("p@ss/w:rd", key.decrypt(&encrypted)?);
+
+        assert_eq!("", key.encrypt("")?);
+        assert_eq!("", key.decrypt("")?);
+        assert!(!is_encrypted("password"));
+        assert!(key.decrypt("password").is_err());
+
+        // 別の鍵では復号できない
+        let other = CredentialKey::load_or_create(&std::env::temp_dir().join(uuid::Uuid::new_v4().to_string()))?;
+        assert!(other.decrypt(&encrypted).is_err());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(0o600, std::fs::metadata(dir.join("credential.key"))?.permissions().mode() & 0o777);
+        }
+
+        Ok(())
+    }
+}
@@ -1,36 +1,106 @@
-use anyhow::anyhow;
-use mysql::{from_row, Conn};
+use mysql::{from_row_opt, Conn};
 
-use crate::domain::diff::SnapshotDiff;
+use crate::db::transaction;
+use crate::domain::diff::{DiffId, DiffSummary, SnapshotDiff, TableDiff};
+use crate::domain::schema::TableName;
 use crate::domain::snapshot::SnapshotId;
+use crate::error::Error;
 
+// 集計のない古い差分は、作り直せるように見つからなかったものとして扱う
 pub fn find_snapshot_diff(conn: &mut Conn, snapshot_id1: &SnapshotId, snapshot_id2: &SnapshotId) -> anyhow::Result<Option<SnapshotDiff>> {
-    conn.query(format!("select data from snapshot_diff where snapshot_id1 = '{snapshot_id1}' and snapshot_id2 = '{snapshot_id2}'"))
-        .map(|result| {
-            result
-                .map(|x| x.unwrap())
-                .map(|row| {
-                    let data = from_row::<String>(row);
-                    let snapshot_diff: SnapshotDiff = serde_json::from_str(&data).unwrap();
-                    snapshot_diff
-                })
-                .next()
+    let row = conn
+        .prep_exec(
+            "select diff_id, summary from snapshot_diff where snapshot_id1 = ? and snapshot_id2 = ? and summary is not null",
+            (snapshot_id1, snapshot_id2),
+        )?
+        .next()
+        .transpose()?;
+
+    match row {
+        Some(row) => {
+            let (diff_id, summary) = from_row_opt::<(DiffId, String)>(row)?;
+            let summary: DiffSummary = serde_json::from_str(&summary)
+                .map_err(|e| Error::CorruptSnapshot(format!("diff summary of snapshot {snapshot_id1} and {snapshot_id2}: {e}")))?;
+            let table_diffs = find_table_diffs(conn, &diff_id)?;
+            Ok(Some(SnapshotDiff { diff_id, snapshot_id1: snapshot_id1.clone(), snapshot_id2: snapshot_id2.clone(), table_diffs, summary }))
+        }
+        None => Ok(None),
+    }
+}
+
+// 行ごとの差分を読み込まずに、集計だけを取得する
+pub fn find_snapshot_diff_summary(conn: &mut Conn, snapshot_id1: &SnapshotId, snapshot_id2: &SnapshotId) -> anyhow::Result<Option<DiffSummary>> {
+    conn.prep_exec(
+        "select summary from snapshot_diff where snapshot_id1 = ? and snapshot_id2 = ? and summary is not null",
+        (snapshot_id1, snapshot_id2),
+    )?
+    .map(|row| {
+        let summary = from_row_opt::<String>(row?)?;
+        let summary: DiffSummary = serde_json::from_str(&summary)
+            .map_err(|e| Error::CorruptSnapshot(format!("diff summary of snapshot {snapshot_id1} and {snapshot_id2}: {e}")))?;
+        Ok(summary)
+    })
+    .next()
+    .transpose()
+}
+
+fn find_table_diffs(conn: &mut Conn, diff_id: &DiffId) -> anyhow::Result<Vec<TableDiff>> {
+    conn.prep_exec("select table_name, data from table_diff where diff_id = ? order by table_name", (diff_id,))?
+        .map(|row| {
+            let (table_name, data) = from_row_opt::<(TableName, String)>(row?)?;
+            let table_diff: TableDiff =
+                serde_json::from_str(&data).map_err(|e| Error::CorruptSnapshot(format!("table {table_name} of diff {diff_id}: {e}")))?;
+            Ok(table_diff)
         })
-        .map_err(|e| anyhow!(e))
+        .collect()
 }
 
-pub fn insert_snapshot_diff(conn: &mut Conn, snapshot_diff: &SnapshotDiff) -> anyhow::Result<()> {
+// 他のテーブルの差分を読み込まずに、ひとつのテーブルの差分だけを取得する
+pub fn find_table_diff(
+    conn: &mut Conn,
+    snapshot_id1: &SnapshotId,
+    snapshot_id2: &SnapshotId,
+    table_name: &TableName,
+) -> anyhow::Result<Option<TableDiff>> {
     conn.prep_exec(
-        "insert into snapshot_diff values (?, ?, ?, ?)",
-        (&snapshot_diff.diff_id, &snapshot_diff.snapshot_id1, &snapshot_diff.snapshot_id2, serde_json::to_string(snapshot_diff).unwrap()),
-    )?;
-    Ok(())
+        "select t.data from table_diff t join snapshot_diff s on t.diff_id = s.diff_id where s.snapshot_id1 = ? and s.snapshot_id2 = ? and s.summary is not null and t.table_name = ?",
+        (snapshot_id1, snapshot_id2, table_name),
+    )?
+    .map(|row| {
+        let data = from_row_opt::<String>(row?)?;
+        let table_diff: TableDiff = serde_json::from_str(&data)
+            .map_err(|e| Error::CorruptSnapshot(format!("table {table_name} of diff of snapshot {snapshot_id1} and {snapshot_id2}: {e}")))?;
+        Ok(table_diff)
+    })
+    .next()
+    .transpose()
+}
+
+// 集計のない古い差分が残っている場合は置き換える
+pub fn insert_snapshot_diff(conn: &mut Conn, snapshot_diff: &SnapshotDiff) -> anyhow::Result<()> {
+    transaction(conn, |conn| {
+        conn.prep_exec(
+            "delete from snapshot_diff where snapshot_id1 = ? and snapshot_id2 = ?",
+            (&snapshot_diff.snapshot_id1, &snapshot_diff.snapshot_id2),
+        )?;
+        conn.prep_exec(
+            "insert into snapshot_diff (diff_id, snapshot_id1, snapshot_id2, summary) values (?, ?, ?, ?)",
+            (&snapshot_diff.diff_id, &snapshot_diff.snapshot_id1, &snapshot_diff.snapshot_id2, serde_json::to_string(&snapshot_diff.summary)?),
+        )?;
+        for table_diff in &snapshot_diff.table_diffs {
+            conn.prep_exec(
+                "insert into table_diff (diff_id, table_name, data) values (?, ?, ?)",
+                (&snapshot_diff.diff_id, &table_diff.table_name, serde_json::to_string(table_diff)?),
+            )?;
+        }
+        Ok(())
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use crate::db::create_connection;
-    use crate::db::diff::{find_snapshot_diff, insert_snapshot_diff};
+    use crate::db::diff::{find_snapshot_diff, find_snapshot_diff_summary, find_table_diff, insert_snapshot_diff};
     use crate::db::project::insert_project;
     use crate::db::snapshot::insert_snapshot_summary;
     use crate::domain::diff::ColDiff::{Deleted, NoValue};
@@ -68,8 +138,6 @@ mod tests {
         insert_snapshot_summary(&mut conn, &project_id, &snapshot_summary1)?;
         insert_snapshot_summary(&mut conn, &project_id, &snapshot_summary2)?;
 
-        let _table_name = "items".to_string();
-
         // find
         let table_snapshot_opt = find_snapshot_diff(&mut conn, &snapshot_id1, &snapshot_id2)?;
         assert_eq!(None, table_snapshot_opt);
@@ -83,7 +151,26 @@ mod tests {
         insert_snapshot_diff(&mut conn, &snapshot_diff)?;
 
         let table_snapshot_opt = find_snapshot_diff(&mut conn, &snapshot_id1, &snapshot_id2)?;
-        assert_eq!(Some(snapshot_diff), table_snapshot_opt);
+        assert_eq!(Some(&snapshot_diff), table_snapshot_opt.as_ref());
+
+        // table
+        let table_diff_opt = find_table_diff(&mut conn, &snapshot_id1, &snapshot_id2, &"user".to_string())?;
+        assert_eq!(Some(&snapshot_diff.table_diffs[0]), table_diff_opt.as_ref());
+        assert_eq!(None, find_table_diff(&mut conn, &snapshot_id1, &snapshot_id2, &"item".to_string())?);
+
+        // summary
+        let summary_opt = find_snapshot_diff_summary(&mut conn, &snapshot_id1, &snapshot_id2)?;
+        assert_eq!(Some(snapshot_diff.summary), summary_opt);
+
+        // 集計のない古い差分は見つからないものとして扱い、保存し直せる
+        conn.prep_exec("update snapshot_diff set summary = null where snapshot_id1 = ? and snapshot_id2 = ?", (&snapshot_id1, &snapshot_id2))?;
+        assert_eq!(None, find_snapshot_diff(&mut conn, &snapshot_id1, &snapshot_id2)?);
+        assert_eq!(None, find_snapshot_diff_summary(&mut conn, &snapshot_id1, &snapshot_id2)?);
+
+        let snapshot_diff = SnapshotDiff::new(&create_diff_id(), &snapshot_id1, &snapshot_id2, vec![]);
+        insert_snapshot_diff(&mut conn, &snapshot_diff)?;
+        assert_eq!(Some(snapshot_diff), find_snapshot_diff(&mut conn, &snapshot_id1, &snapshot_id2)?);
+        assert_eq!(None, find_table_diff(&mut conn, &snapshot_id1, &snapshot_id2, &"user".to_string())?);
 
         Ok(())
     }
@@ -1,31 +1,38 @@
 use anyhow::anyhow;
-use mysql::{from_row, Conn};
+use mysql::{from_row_opt, Conn};
 
 use crate::domain::project::Rdbms::Mysql;
-use crate::domain::project::{Project, ProjectId};
+use crate::domain::project::{ConnectionOptions, Project, ProjectId};
+use crate::error::Error;
 
 pub fn all_projects(conn: &mut Conn) -> anyhow::Result<Vec<Project>> {
-    conn.query("select project_id, name, rdbms, user, password, host, port, `schema` from project order by project_id")
-        .map(|result| {
-            result
-                .map(|x| x.unwrap())
-                .map(|row| {
-                    let (project_id, name, rdbms, user, password, host, port, schema) =
-                        from_row::<(ProjectId, String, String, String, String, String, String, String)>(row);
-                    let rdbms = match rdbms.as_ref() {
-                        "MySQL" => Mysql,
-                        _ => unreachable!(),
-                    };
-                    Project::new(&project_id, name, rdbms, user, password, host, port, schema)
-                })
-                .collect()
+    conn.query("select project_id, name, rdbms, user, password, host, port, `schema`, options from project order by project_id")?
+        .map(|row| {
+            let (project_id, name, rdbms, user, password, host, port, schema, options) =
+                from_row_opt::<(ProjectId, String, String, String, String, String, String, String, Option<String>)>(row?)?;
+            let rdbms = match rdbms.as_ref() {
+                "MySQL" => Mysql,
+                _ => return Err(anyhow!(Error::UnsupportedType(format!("rdbms {rdbms} of project {project_id}")))),
+            };
+            let options: ConnectionOptions = match options {
+                Some(options) => serde_json::from_str(&options).map_err(|e| anyhow!("invalid options of project {project_id}: {e}"))?,
+                None => ConnectionOptions::default(),
+            };
+            Ok(Project::new(&project_id, name, rdbms, user, password, host, port, schema).with_options(options))
         })
-        .map_err(|e| anyhow!(e))
+        .collect()
+}
+
+pub fn find_project(conn: &mut Conn, project_id: &ProjectId) -> anyhow::Result<Project> {
+    all_projects(conn)?
+        .into_iter()
+        .find(|project| &project.project_id == project_id)
+        .ok_or_else(|| anyhow!(Error::NotFound(format!("project {project_id}"))))
 }
 
 pub fn insert_project(conn: &mut Conn, project: &Project) -> anyhow::Result<()> {
     conn.prep_exec(
-        "insert into project values (?, ?, ?, ?, ?, ?, ?, ?)",
+        "insert into project values (?, ?, ?, ?, ?, ?, ?, ?, ?)",
         (
             &project.project_id,
             &project.name,
@@ -37,6 +44,7 @@ pub fn insert_project(conn: &mut Conn, project: &Project) -> anyhow::Result<()>
             &project.host,
             &project.port,
             &project.schema,
+            serde_json::to_string(&project.options)?,
         ),
     )?;
     Ok(())
@@ -44,23 +52,28 @@ pub fn insert_project(conn: &mut Conn, project: &Project) -> anyhow::Result<()>
 
 pub fn update_project(conn: &mut Conn, project: &Project) -> anyhow::Result<()> {
     conn.prep_exec(
-        "update project set name = ?, rdbms = ?, user = ?, password = ?, host = ?, port = ?, `schema` = ? where project_id = ?",
+        "update project set name = ?, rdbms = ?, user = ?, host = ?, port = ?, `schema` = ?, options = ? where project_id = ?",
         (
             &project.name,
             match project.rdbms {
                 Mysql => "MySQL",
             },
             &project.user,
-            &project.password,
             &project.host,
             &project.port,
             &project.schema,
+            serde_json::to_string(&project.options)?,
             &project.project_id,
         ),
     )?;
     Ok(())
 }
 
+pub fn update_project_password(conn: &mut Conn, project_id: &ProjectId, password: &str) -> anyhow::Result<()> {
+    conn.prep_exec("update project set password = ? where project_id = ?", (password, project_id))?;
+    Ok(())
+}
+
 pub fn delete_project(conn: &mut Conn, project_id: &ProjectId) -> anyhow::Result<()> {
     conn.prep_exec("delete from project where project_id = ?", vec![project_id])?;
     Ok(())
@@ -69,9 +82,9 @@ pub fn delete_project(conn: &mut Conn, project_id: &ProjectId) -> anyhow::Result
 #[cfg(test)]
 mod tests {
     use crate::db::create_connection;
-    use crate::db::project::{all_projects, delete_project, insert_project, update_project};
-    use crate::domain::project::Project;
+    use crate::db::project::{all_projects, delete_project, insert_project, update_project, update_project_password};
     use crate::domain::project::Rdbms::Mysql;
+    use crate::domain::project::{ConnectionOptions, Project, SslMode};
     use crate::domain::snapshot::create_snapshot_id;
 
     #[test]
@@ -96,9 +109,21 @@ mod tests {
         assert_eq!(&project1, &projects[0]);
 
         // update
-        let project2 = Project::new(&project_id, "test-project-2", Mysql, "user2", "password2", "127.0.0.2", "3307", "test-db2");
+        let options = ConnectionOptions { ssl_mode: SslMode::VerifyCa, ssl_ca_path: Some("/etc/ca.pem".to_string()), ..Default::default() };
+        let project2 =
+            Project::new(&project_id, "test-project-2", Mysql, "user2", "password2", "127.0.0.2", "3307", "test-db2").with_options(options.clone());
         update_project(&mut conn, &project2)?;
 
+        let projects = all_projects(&mut conn)?;
+        assert_eq!(1, projects.len());
+        assert_eq!(
+            &Project::new(&project_id, "test-project-2", Mysql, "user2", "password", "127.0.0.2", "3307", "test-db2").with_options(options),
+            &projects[0]
+        );
+
+        // update password
+        update_project_password(&mut conn, &project_id, "password2")?;
+
         let projects = all_projects(&mut conn)?;
         assert_eq!(1, projects.len());
         assert_eq!(&project2, &projects[0]);
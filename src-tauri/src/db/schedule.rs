@@ -0,0 +1,96 @@
+use mysql::{from_row_opt, Conn};
+
+use crate::domain::project::ProjectId;
+use crate::domain::schedule::SnapshotSchedule;
+use crate::domain::snapshot::SnapshotId;
+use crate::error::Error;
+
+pub fn all_snapshot_schedules(conn: &mut Conn) -> anyhow::Result<Vec<SnapshotSchedule>> {
+    conn.query("select project_id, data from snapshot_schedule order by project_id")?
+        .map(|row| {
+            let (project_id, data) = from_row_opt::<(ProjectId, String)>(row?)?;
+            let snapshot_schedule: SnapshotSchedule =
+                serde_json::from_str(&data).map_err(|e| Error::Internal(format!("invalid schedule of project {project_id}: {e}")))?;
+            Ok(snapshot_schedule)
+        })
+        .collect()
+}
+
+pub fn save_snapshot_schedule(conn: &mut Conn, snapshot_schedule: &SnapshotSchedule) -> anyhow::Result<()> {
+    conn.prep_exec(
+        "insert into snapshot_schedule values (?, ?) on duplicate key update data = values(data)",
+        (&snapshot_schedule.project_id, serde_json::to_string(snapshot_schedule)?),
+    )?;
+    Ok(())
+}
+
+pub fn delete_snapshot_schedule(conn: &mut Conn, project_id: &ProjectId) -> anyhow::Result<()> {
+    conn.prep_exec("delete from snapshot_schedule where project_id = ?", vec![project_id])?;
+    Ok(())
+}
+
+pub fn insert_scheduled_snapshot(conn: &mut Conn, snapshot_id: &SnapshotId) -> anyhow::Result<()> {
+    conn.prep_exec("insert into scheduled_snapshot values (?)", vec![snapshot_id])?;
+    Ok(())
+}
+
+// 作成日時の古い順に返す
+pub fn all_scheduled_snapshot_ids(conn: &mut Conn, project_id: &ProjectId) -> anyhow::Result<Vec<SnapshotId>> {
+    conn.prep_exec(
+        "select s.snapshot_id from scheduled_snapshot s join snapshot_summary ss on s.snapshot_id = ss.snapshot_id where ss.project_id = ? order by ss.create_at",
+        vec![project_id],
+    )?
+    .map(|row| Ok(from_row_opt::<SnapshotId>(row?)?))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::create_connection;
+    use crate::db::project::insert_project;
+    use crate::db::schedule::{all_snapshot_schedules, delete_snapshot_schedule, save_snapshot_schedule};
+    use crate::domain::project::Rdbms::Mysql;
+    use crate::domain::project::{create_project_id, Project};
+    use crate::domain::schedule::{SnapshotSchedule, Trigger};
+
+    #[test]
+    fn snapshot_schedule() -> anyhow::Result<()> {
+        // setup
+
+        let mut conn = create_connection()?;
+        conn.prep_exec("delete from project", ())?;
+
+        let project_id = create_project_id();
+
+        let project = Project::new(&project_id, "test-project", Mysql, "user", "password", "127.0.0.1", "3306", "test-db");
+        insert_project(&mut conn, &project)?;
+
+        // all
+        assert_eq!(0, all_snapshot_schedules(&mut conn)?.len());
+
+        // save
+        let schedule1 = SnapshotSchedule {
+            project_id: project_id.clone(),
+            trigger: Trigger::Interval { seconds: 30 },
+            name_prefix: "auto-".to_string(),
+            retention: 3,
+            enabled: true,
+        };
+        save_snapshot_schedule(&mut conn, &schedule1)?;
+
+        assert_eq!(vec![schedule1.clone()], all_snapshot_schedules(&mut conn)?);
+
+        // save (update)
+        let schedule2 = SnapshotSchedule { trigger: Trigger::Cron { expression: "0 * * * *".to_string() }, ..schedule1 };
+        save_snapshot_schedule(&mut conn, &schedule2)?;
+
+        assert_eq!(vec![schedule2], all_snapshot_schedules(&mut conn)?);
+
+        // delete
+        delete_snapshot_schedule(&mut conn, &project_id)?;
+
+        assert_eq!(0, all_snapshot_schedules(&mut conn)?.len());
+
+        Ok(())
+    }
+}
@@ -1,22 +1,25 @@
-use anyhow::anyhow;
-use itertools::Itertools;
-use mysql::{from_row, Conn};
+use mysql::{from_row_opt, Conn};
 
+use crate::db::transaction;
 use crate::domain::project::ProjectId;
-use crate::domain::snapshot::{SnapshotId, SnapshotSummary, TableSnapshot};
+use crate::domain::schema::{ColName, ForeignKey, Hash, PrimaryColName, TableName};
+use crate::domain::snapshot::{SnapshotId, SnapshotName, SnapshotSummary, TableSnapshot};
+use crate::error::Error;
 
 pub fn all_snapshot_summaries(conn: &mut Conn, project_id: &ProjectId) -> anyhow::Result<Vec<SnapshotSummary>> {
-    conn.query(format!("select snapshot_id, snapshot_name, create_at from snapshot_summary where project_id = '{project_id}' order by create_at"))
-        .map(|result| {
-            result
-                .map(|x| x.unwrap())
-                .map(|row| {
-                    let (snapshot_id, snapshot_name, create_at) = from_row::<(SnapshotId, String, String)>(row);
-                    SnapshotSummary::new(&snapshot_id, snapshot_name, create_at)
-                })
-                .collect()
+    conn.query(format!("select snapshot_id, snapshot_name, create_at from snapshot_summary where project_id = '{project_id}' order by create_at"))?
+        .map(|row| {
+            let (snapshot_id, snapshot_name, create_at) = from_row_opt::<(SnapshotId, String, String)>(row?)?;
+            Ok(SnapshotSummary::new(&snapshot_id, snapshot_name, create_at))
         })
-        .map_err(|e| anyhow!(e))
+        .collect()
+}
+
+pub fn find_snapshot_name(conn: &mut Conn, snapshot_id: &SnapshotId) -> anyhow::Result<Option<SnapshotName>> {
+    conn.prep_exec("select snapshot_name from snapshot_summary where snapshot_id = ?", (snapshot_id,))?
+        .next()
+        .map(|row| Ok(from_row_opt::<SnapshotName>(row?)?))
+        .transpose()
 }
 
 pub fn insert_snapshot_summary(conn: &mut Conn, project_id: &ProjectId, snapshot_summary: &SnapshotSummary) -> anyhow::Result<()> {
@@ -27,6 +30,21 @@ pub fn insert_snapshot_summary(conn: &mut Conn, project_id: &ProjectId, snapshot
     Ok(())
 }
 
+pub fn insert_snapshot(
+    conn: &mut Conn,
+    project_id: &ProjectId,
+    snapshot_summary: &SnapshotSummary,
+    table_snapshots: &[TableSnapshot],
+) -> anyhow::Result<()> {
+    transaction(conn, |conn| {
+        insert_snapshot_summary(conn, project_id, snapshot_summary)?;
+        for table_snapshot in table_snapshots {
+            insert_table_snapshot(conn, &snapshot_summary.snapshot_id, table_snapshot)?;
+        }
+        Ok(())
+    })
+}
+
 pub fn update_snapshot_summary(conn: &mut Conn, snapshot_summary: &SnapshotSummary) -> anyhow::Result<()> {
     conn.prep_exec(
         "update snapshot_summary set snapshot_name = ? where snapshot_id = ?",
@@ -41,24 +59,75 @@ pub fn delete_snapshot_summary(conn: &mut Conn, snapshot_id: &SnapshotId) -> any
 }
 
 pub fn find_table_snapshots(conn: &mut Conn, snapshot_id: &SnapshotId) -> anyhow::Result<Vec<TableSnapshot>> {
-    conn.query(format!("select data from table_snapshot where snapshot_id = '{snapshot_id}'"))
-        .map(|result| {
-            result
-                .map(|x| x.unwrap())
-                .map(|row| {
-                    let data = from_row::<String>(row);
-                    let table_snapshot: TableSnapshot = serde_json::from_str(&data).unwrap();
-                    table_snapshot
-                })
-                .collect_vec()
+    conn.query(format!("select table_name, data from table_snapshot where snapshot_id = '{snapshot_id}'"))?
+        .map(|row| {
+            let (table_name, data) = from_row_opt::<(String, String)>(row?)?;
+            let table_snapshot: TableSnapshot =
+                serde_json::from_str(&data).map_err(|e| Error::CorruptSnapshot(format!("table {table_name} of snapshot {snapshot_id}: {e}")))?;
+            Ok(table_snapshot)
         })
-        .map_err(|e| anyhow!(e))
+        .collect()
+}
+
+pub fn find_table_snapshot(conn: &mut Conn, snapshot_id: &SnapshotId, table_name: &TableName) -> anyhow::Result<Option<TableSnapshot>> {
+    conn.prep_exec("select data from table_snapshot where snapshot_id = ? and table_name = ?", (snapshot_id, table_name))?
+        .next()
+        .map(|row| {
+            let data = from_row_opt::<String>(row?)?;
+            let table_snapshot: TableSnapshot =
+                serde_json::from_str(&data).map_err(|e| Error::CorruptSnapshot(format!("table {table_name} of snapshot {snapshot_id}: {e}")))?;
+            Ok(table_snapshot)
+        })
+        .transpose()
+}
+
+// data を読み込まずに、テーブルごとのハッシュだけを取得する
+pub fn find_table_hashes(conn: &mut Conn, snapshot_id: &SnapshotId) -> anyhow::Result<Vec<(TableName, Hash)>> {
+    conn.prep_exec("select table_name, json_unquote(json_extract(data, '$.hash')) from table_snapshot where snapshot_id = ?", (snapshot_id,))?
+        .map(|row| Ok(from_row_opt::<(TableName, Hash)>(row?)?))
+        .collect()
+}
+
+// data を読み込まずに、テーブルごとの外部キーだけを取得する
+// 外部キーを記録する前に作成したスナップショットでは空になる
+pub fn find_foreign_keys(conn: &mut Conn, snapshot_id: &SnapshotId) -> anyhow::Result<Vec<(TableName, Vec<ForeignKey>)>> {
+    conn.prep_exec("select table_name, json_extract(data, '$.foreign_keys') from table_snapshot where snapshot_id = ?", (snapshot_id,))?
+        .map(|row| {
+            let (table_name, data) = from_row_opt::<(TableName, Option<String>)>(row?)?;
+            let foreign_keys = match data {
+                Some(data) => serde_json::from_str(&data)
+                    .map_err(|e| Error::CorruptSnapshot(format!("foreign keys of table {table_name} of snapshot {snapshot_id}: {e}")))?,
+                None => vec![],
+            };
+            Ok((table_name, foreign_keys))
+        })
+        .collect()
+}
+
+// data を読み込まずに、テーブルの主キーと列の名前だけを取得する
+pub fn find_table_col_names(
+    conn: &mut Conn,
+    snapshot_id: &SnapshotId,
+    table_name: &TableName,
+) -> anyhow::Result<Option<(PrimaryColName, Vec<ColName>)>> {
+    conn.prep_exec(
+        "select json_unquote(json_extract(data, '$.primary_col_name')), json_extract(data, '$.col_names') from table_snapshot where snapshot_id = ? and table_name = ?",
+        (snapshot_id, table_name),
+    )?
+    .next()
+    .map(|row| {
+        let (primary_col_name, col_names) = from_row_opt::<(PrimaryColName, String)>(row?)?;
+        let col_names = serde_json::from_str(&col_names)
+            .map_err(|e| Error::CorruptSnapshot(format!("col names of table {table_name} of snapshot {snapshot_id}: {e}")))?;
+        Ok((primary_col_name, col_names))
+    })
+    .transpose()
 }
 
 pub fn insert_table_snapshot(conn: &mut Conn, snapshot_id: &SnapshotId, table_snapshot: &TableSnapshot) -> anyhow::Result<()> {
     conn.prep_exec(
         "insert into table_snapshot values (?, ?, ?)",
-        (&snapshot_id, &table_snapshot.table_name, &serde_json::to_string(table_snapshot).unwrap()),
+        (&snapshot_id, &table_snapshot.table_name, &serde_json::to_string(table_snapshot)?),
     )?;
 
     Ok(())
@@ -69,13 +138,15 @@ mod tests {
     use crate::db::create_connection;
     use crate::db::project::insert_project;
     use crate::db::snapshot::{
-        all_snapshot_summaries, delete_snapshot_summary, find_table_snapshots, insert_snapshot_summary, insert_table_snapshot,
-        update_snapshot_summary,
+        all_snapshot_summaries, delete_snapshot_summary, find_foreign_keys, find_snapshot_name, find_table_snapshots, insert_snapshot_summary,
+        insert_table_snapshot, update_snapshot_summary,
     };
     use crate::domain::project::Rdbms::Mysql;
     use crate::domain::project::{create_project_id, Project};
+    use crate::domain::schema::ForeignKey;
     use crate::domain::snapshot::ColValue::{SimpleNumber, SimpleString};
     use crate::domain::snapshot::{create_snapshot_id, ColValue, RowSnapshot, SnapshotSummary, TableSnapshot};
+    use crate::error::Error;
 
     #[test]
     fn snapshot_summary() -> anyhow::Result<()> {
@@ -110,12 +181,14 @@ mod tests {
         let snapshot_summaries = all_snapshot_summaries(&mut conn, &project_id)?;
         assert_eq!(1, snapshot_summaries.len());
         assert_eq!(&snapshot_summary2, &snapshot_summaries[0]);
+        assert_eq!(Some("test2".to_string()), find_snapshot_name(&mut conn, &snapshot_id)?);
 
         // delete
         delete_snapshot_summary(&mut conn, &snapshot_id)?;
 
         let snapshot_summaries = all_snapshot_summaries(&mut conn, &project_id)?;
         assert_eq!(0, snapshot_summaries.len());
+        assert_eq!(None, find_snapshot_name(&mut conn, &snapshot_id)?);
 
         Ok(())
     }
@@ -156,11 +229,23 @@ mod tests {
         let row_snapshot2 = RowSnapshot::new(vec![n("2"), s("456"), n("560")]);
         let table_snapshot =
             TableSnapshot::new(&table_name, "id".to_string(), vec!["code".to_string(), "price".to_string()], vec![row_snapshot1, row_snapshot2]);
+        let foreign_key =
+            ForeignKey { col_name: "code".to_string(), referenced_table_name: "codes".to_string(), referenced_col_name: "code".to_string() };
+        let table_snapshot = table_snapshot.with_foreign_keys(vec![foreign_key.clone()]);
         insert_table_snapshot(&mut conn, &snapshot_id, &table_snapshot)?;
 
         let table_snapshots = find_table_snapshots(&mut conn, &snapshot_id)?;
         assert_eq!(vec![table_snapshot], table_snapshots);
 
+        // foreign keys
+        assert_eq!(vec![(table_name.clone(), vec![foreign_key])], find_foreign_keys(&mut conn, &snapshot_id)?);
+
+        // corrupt (data 列は json 型なので、壊れた形は構造の不一致で表す)
+        conn.prep_exec("update table_snapshot set data = '{\"table_name\": 1}' where snapshot_id = ?", (&snapshot_id,))?;
+
+        let e = Error::from(find_table_snapshots(&mut conn, &snapshot_id).unwrap_err());
+        assert_eq!("CORRUPT_SNAPSHOT", e.code());
+
         Ok(())
     }
 }
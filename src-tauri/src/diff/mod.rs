@@ -0,0 +1,137 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::anyhow;
+use mysql::Conn;
+
+use crate::db::diff::{find_snapshot_diff, insert_snapshot_diff};
+use crate::db::snapshot::{find_snapshot_name, find_table_hashes, find_table_snapshot};
+use crate::domain::diff::{create_snapshot_diff, find_unchanged_table_names, ChangeFormat, SnapshotDiff};
+use crate::domain::expectation::{Expectation, Mismatch};
+use crate::domain::report::{ReportFormat, ReportOptions};
+use crate::domain::schema::{Hash, TableName};
+use crate::domain::snapshot::{SnapshotId, TableSnapshot};
+use crate::error::Error;
+
+// 保存済みの差分があればそれを返し、なければ作成して保存する
+pub fn find_or_create_snapshot_diff(conn: &mut Conn, snapshot_id1: &SnapshotId, snapshot_id2: &SnapshotId) -> anyhow::Result<SnapshotDiff> {
+    match find_snapshot_diff(conn, snapshot_id1, snapshot_id2)? {
+        Some(snapshot_diff) => Ok(snapshot_diff),
+        None => {
+            // ハッシュの一致するテーブルは読み込まず、それ以外のテーブルだけを読み込んで比べる
+            let table_hashes1 = find_table_hashes(conn, snapshot_id1)?;
+            let table_hashes2 = find_table_hashes(conn, snapshot_id2)?;
+            let unchanged_table_names = find_unchanged_table_names(&table_hashes1, &table_hashes2);
+
+            let table_snapshots1 = find_changed_table_snapshots(conn, snapshot_id1, &table_hashes1, &unchanged_table_names)?;
+            let table_snapshots2 = find_changed_table_snapshots(conn, snapshot_id2, &table_hashes2, &unchanged_table_names)?;
+
+            let snapshot_diff = create_snapshot_diff(snapshot_id1, snapshot_id2, &table_snapshots1, &table_snapshots2, &unchanged_table_names);
+
+            insert_snapshot_diff(conn, &snapshot_diff)?;
+
+            Ok(snapshot_diff)
+        }
+    }
+}
+
+pub fn find_changed_table_snapshots(
+    conn: &mut Conn,
+    snapshot_id: &SnapshotId,
+    table_hashes: &[(TableName, Hash)],
+    unchanged_table_names: &[TableName],
+) -> anyhow::Result<Vec<TableSnapshot>> {
+    let mut table_snapshots = vec![];
+    for (table_name, _) in table_hashes.iter().filter(|(table_name, _)| !unchanged_table_names.contains(table_name)) {
+        if let Some(table_snapshot) = find_table_snapshot(conn, snapshot_id, table_name)? {
+            table_snapshots.push(table_snapshot);
+        }
+    }
+    Ok(table_snapshots)
+}
+
+// 差分のレポートを作成する ( 見出しにはスナップショットの名前を出す )
+pub fn render_report(
+    conn: &mut Conn,
+    snapshot_id1: &SnapshotId,
+    snapshot_id2: &SnapshotId,
+    format: ReportFormat,
+    options: &ReportOptions,
+) -> anyhow::Result<String> {
+    let snapshot_diff = find_or_create_snapshot_diff(conn, snapshot_id1, snapshot_id2)?;
+
+    let snapshot_name1 = find_snapshot_name(conn, snapshot_id1)?.ok_or_else(|| anyhow!(Error::NotFound(format!("snapshot {snapshot_id1}"))))?;
+    let snapshot_name2 = find_snapshot_name(conn, snapshot_id2)?.ok_or_else(|| anyhow!(Error::NotFound(format!("snapshot {snapshot_id2}"))))?;
+
+    Ok(format.render(&snapshot_diff, &snapshot_name1, &snapshot_name2, options))
+}
+
+// 差分のレポートをファイルに書き出す
+pub fn export_report<P: AsRef<Path>>(
+    conn: &mut Conn,
+    snapshot_id1: &SnapshotId,
+    snapshot_id2: &SnapshotId,
+    format: ReportFormat,
+    options: &ReportOptions,
+    path: P,
+) -> anyhow::Result<()> {
+    fs::write(path, render_report(conn, snapshot_id1, snapshot_id2, format, options)?)?;
+
+    Ok(())
+}
+
+// 差分の変更を、機械で読める形式でファイルに書き出す
+pub fn export_changes<P: AsRef<Path>>(
+    conn: &mut Conn,
+    snapshot_id1: &SnapshotId,
+    snapshot_id2: &SnapshotId,
+    format: ChangeFormat,
+    path: P,
+) -> anyhow::Result<()> {
+    let snapshot_diff = find_or_create_snapshot_diff(conn, snapshot_id1, snapshot_id2)?;
+
+    fs::write(path, format.emit(&snapshot_diff)?)?;
+
+    Ok(())
+}
+
+// 期待値ファイルと差分を比べて、一致しなかったものを返す
+// update の場合は、比べたあとで期待値ファイルを差分に合わせて書き直す ( ファイルがなければ作成する )
+pub fn verify_expectation<P: AsRef<Path>>(
+    conn: &mut Conn,
+    snapshot_id1: &SnapshotId,
+    snapshot_id2: &SnapshotId,
+    path: P,
+    update: bool,
+) -> anyhow::Result<Vec<Mismatch>> {
+    let snapshot_diff = find_or_create_snapshot_diff(conn, snapshot_id1, snapshot_id2)?;
+
+    let expectation = match path.as_ref().exists() {
+        true => Some(read_expectation(&path)?),
+        false if update => None,
+        false => return Err(anyhow!("expectation file not found: {}", path.as_ref().display())),
+    };
+
+    let mismatches = expectation.as_ref().map(|expectation| expectation.verify(&snapshot_diff)).unwrap_or_default();
+
+    if update {
+        let updated = match &expectation {
+            Some(expectation) => expectation.update(&snapshot_diff),
+            None => Expectation::create(&snapshot_diff, vec![]),
+        };
+        write_expectation(&path, &updated)?;
+    }
+
+    Ok(mismatches)
+}
+
+pub fn read_expectation<P: AsRef<Path>>(path: P) -> anyhow::Result<Expectation> {
+    let expectation: Expectation = serde_json::from_str(&fs::read_to_string(path)?)?;
+    Ok(expectation)
+}
+
+// リポジトリで差分を見やすいよう、整形して書き出す
+pub fn write_expectation<P: AsRef<Path>>(path: P, expectation: &Expectation) -> anyhow::Result<()> {
+    fs::write(path, format!("{}\n", serde_json::to_string_pretty(expectation)?))?;
+    Ok(())
+}
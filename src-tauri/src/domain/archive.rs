@@ -0,0 +1,162 @@
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+use crate::domain::project::Rdbms::Mysql;
+use crate::domain::project::{ConnectionOptions, Project, ProjectId};
+use crate::domain::schema::{Hash, TableName};
+use crate::domain::snapshot::{SnapshotId, SnapshotName, SnapshotSummary, TableSnapshot};
+
+pub const ARCHIVE_FORMAT: &str = "table-snapshot-archive";
+
+pub const ARCHIVE_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Debug)]
+pub struct SnapshotArchive {
+    pub format: String,
+    pub version: u32,
+    pub project: ArchivedProject,
+    pub snapshots: Vec<ArchivedSnapshot>,
+}
+
+impl SnapshotArchive {
+    pub fn new(project: &Project, snapshots: Vec<ArchivedSnapshot>) -> Self {
+        Self { format: ARCHIVE_FORMAT.to_string(), version: ARCHIVE_VERSION, project: ArchivedProject::from(project), snapshots }
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.format != ARCHIVE_FORMAT {
+            return Err(anyhow!("not a table-snapshot archive: format = {}", self.format));
+        }
+        if self.version > ARCHIVE_VERSION {
+            return Err(anyhow!("unsupported archive version: {} ( supported <= {} )", self.version, ARCHIVE_VERSION));
+        }
+        Ok(())
+    }
+}
+
+// パスワードはアーカイブに含めない
+#[derive(Serialize, Deserialize, Eq, PartialEq, Debug)]
+pub struct ArchivedProject {
+    pub name: String,
+    pub rdbms: String,
+    pub user: String,
+    pub host: String,
+    pub port: String,
+    pub schema: String,
+    #[serde(default)]
+    pub options: ConnectionOptions,
+}
+
+impl ArchivedProject {
+    fn from(project: &Project) -> Self {
+        Self {
+            name: project.name.clone(),
+            rdbms: match project.rdbms {
+                Mysql => "MySQL".to_string(),
+            },
+            user: project.user.clone(),
+            host: project.host.clone(),
+            port: project.port.clone(),
+            schema: project.schema.clone(),
+            options: project.options.clone(),
+        }
+    }
+
+    pub fn into_project(self, project_id: &ProjectId) -> anyhow::Result<Project> {
+        let rdbms = match self.rdbms.as_ref() {
+            "MySQL" => Mysql,
+            rdbms => return Err(anyhow!("unsupported rdbms: {rdbms}")),
+        };
+        Ok(Project::new(project_id, self.name, rdbms, self.user, "".to_string(), self.host, self.port, self.schema).with_options(self.options))
+    }
+}
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Debug)]
+pub struct ArchivedSnapshot {
+    pub snapshot_id: SnapshotId,
+    pub snapshot_name: SnapshotName,
+    pub create_at: String,
+    pub table_snapshots: Vec<TableSnapshot>,
+}
+
+impl ArchivedSnapshot {
+    pub fn new(snapshot_summary: SnapshotSummary, table_snapshots: Vec<TableSnapshot>) -> Self {
+        Self {
+            snapshot_id: snapshot_summary.snapshot_id,
+            snapshot_name: snapshot_summary.snapshot_name,
+            create_at: snapshot_summary.create_at,
+            table_snapshots,
+        }
+    }
+
+    // 名前と作成日時が同じで、全テーブルの Hash が一致していれば同一のスナップショットとみなす
+    pub fn is_duplicate_of(&self, snapshot_summary: &SnapshotSummary, table_snapshots: &[TableSnapshot]) -> bool {
+        self.snapshot_name == snapshot_summary.snapshot_name
+            && self.create_at == snapshot_summary.create_at
+            && table_hashes(&self.table_snapshots) == table_hashes(table_snapshots)
+    }
+}
+
+fn table_hashes(table_snapshots: &[TableSnapshot]) -> Vec<(&TableName, &Hash)> {
+    let mut hashes: Vec<(&TableName, &Hash)> =
+        table_snapshots.iter().map(|table_snapshot| (&table_snapshot.table_name, &table_snapshot.hash)).collect();
+    hashes.sort();
+    hashes
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::archive::{ArchivedSnapshot, SnapshotArchive, ARCHIVE_VERSION};
+    use crate::domain::project::Rdbms::Mysql;
+    use crate::domain::project::{create_project_id, Project};
+    use crate::domain::snapshot::ColValue::{SimpleNumber, SimpleString};
+    use crate::domain::snapshot::{create_snapshot_id, RowSnapshot, SnapshotSummary, TableSnapshot};
+
+    fn mk_table_snapshot(table_name: &str, name: &str) -> TableSnapshot {
+        let row_snapshot = RowSnapshot::new(vec![SimpleNumber("1".to_string()), SimpleString(name.to_string())]);
+        TableSnapshot::new(&table_name.to_string(), "id".to_string(), vec!["name".to_string()], vec![row_snapshot])
+    }
+
+    #[test]
+    fn password_is_not_archived() -> anyhow::Result<()> {
+        let project = Project::new(&create_project_id(), "test-project", Mysql, "user", "password", "127.0.0.1", "3306", "test-db");
+
+        let archive = SnapshotArchive::new(&project, vec![]);
+        let json = serde_json::to_string(&archive)?;
+        assert!(!json.contains("password"));
+
+        let imported = archive.project.into_project(&project.project_id)?;
+        assert_eq!("", imported.password);
+        assert_eq!(project.host, imported.host);
+
+        Ok(())
+    }
+
+    #[test]
+    fn validate() {
+        let project = Project::new(&create_project_id(), "test-project", Mysql, "user", "password", "127.0.0.1", "3306", "test-db");
+
+        let mut archive = SnapshotArchive::new(&project, vec![]);
+        assert!(archive.validate().is_ok());
+
+        archive.version = ARCHIVE_VERSION + 1;
+        assert!(archive.validate().is_err());
+
+        archive.version = ARCHIVE_VERSION;
+        archive.format = "unknown".to_string();
+        assert!(archive.validate().is_err());
+    }
+
+    #[test]
+    fn is_duplicate_of() {
+        let snapshot_summary = SnapshotSummary::new(&create_snapshot_id(), "test", "2023-07-03 08:17:52");
+        let archived_snapshot = ArchivedSnapshot::new(
+            SnapshotSummary::new(&create_snapshot_id(), "test", "2023-07-03 08:17:52"),
+            vec![mk_table_snapshot("items", "John"), mk_table_snapshot("users", "Jack")],
+        );
+
+        assert!(archived_snapshot.is_duplicate_of(&snapshot_summary, &[mk_table_snapshot("users", "Jack"), mk_table_snapshot("items", "John")]));
+        assert!(!archived_snapshot.is_duplicate_of(&snapshot_summary, &[mk_table_snapshot("users", "Jane"), mk_table_snapshot("items", "John")]));
+        assert!(!archived_snapshot.is_duplicate_of(&snapshot_summary, &[mk_table_snapshot("items", "John")]));
+    }
+}
@@ -0,0 +1,67 @@
+use crate::domain::schema::{ColName, TableName};
+
+#[derive(Eq, PartialEq, Debug)]
+pub struct ConnectionReport {
+    pub connected: bool,
+    pub error: Option<String>,
+    pub server_version: Option<String>,
+    pub schema_visible: bool,
+    pub tables: Vec<TableReport>,
+}
+
+impl ConnectionReport {
+    pub fn connected(server_version: String, schema_visible: bool, tables: Vec<TableReport>) -> Self {
+        Self { connected: true, error: None, server_version: Some(server_version), schema_visible, tables }
+    }
+
+    pub fn failed(error: String) -> Self {
+        Self { connected: false, error: Some(error), server_version: None, schema_visible: false, tables: vec![] }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.connected && self.schema_visible && self.tables.iter().all(|table| table.is_ok())
+    }
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub struct TableReport {
+    pub table_name: TableName,
+    pub estimated_rows: u64,
+    pub selectable: bool,
+    pub has_primary_key: bool,
+    pub unsupported_cols: Vec<UnsupportedCol>,
+}
+
+impl TableReport {
+    pub fn is_ok(&self) -> bool {
+        self.selectable && self.has_primary_key && self.unsupported_cols.is_empty()
+    }
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub struct UnsupportedCol {
+    pub col_name: ColName,
+    pub data_type: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::diagnosis::{ConnectionReport, TableReport, UnsupportedCol};
+
+    fn mk_table_report(selectable: bool, has_primary_key: bool, unsupported_cols: Vec<UnsupportedCol>) -> TableReport {
+        TableReport { table_name: "users".to_string(), estimated_rows: 42, selectable, has_primary_key, unsupported_cols }
+    }
+
+    #[test]
+    fn is_ok() {
+        assert!(ConnectionReport::connected("8.0.33".to_string(), true, vec![mk_table_report(true, true, vec![])]).is_ok());
+
+        assert!(!ConnectionReport::failed("Access denied".to_string()).is_ok());
+        assert!(!ConnectionReport::connected("8.0.33".to_string(), false, vec![]).is_ok());
+        assert!(!ConnectionReport::connected("8.0.33".to_string(), true, vec![mk_table_report(false, true, vec![])]).is_ok());
+        assert!(!ConnectionReport::connected("8.0.33".to_string(), true, vec![mk_table_report(true, false, vec![])]).is_ok());
+
+        let unsupported_col = UnsupportedCol { col_name: "location".to_string(), data_type: "geometry".to_string() };
+        assert!(!ConnectionReport::connected("8.0.33".to_string(), true, vec![mk_table_report(true, true, vec![unsupported_col])]).is_ok());
+    }
+}
@@ -1,6 +1,8 @@
-use std::collections::HashMap;
+use std::cmp::max;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
 use itertools::Itertools;
+use serde_json::{json, Value};
 use uuid::Uuid;
 
 use crate::domain::diff::ColDiff::*;
@@ -20,11 +22,158 @@ pub struct SnapshotDiff {
     pub snapshot_id1: SnapshotId,
     pub snapshot_id2: SnapshotId,
     pub table_diffs: Vec<TableDiff>,
+    // 集計がなかった頃に保存された差分も読めるようにする
+    #[serde(default)]
+    pub summary: DiffSummary,
 }
 
 impl SnapshotDiff {
+    #[cfg(test)]
     pub fn new(diff_id: &DiffId, snapshot_id1: &SnapshotId, snapshot_id2: &SnapshotId, table_diffs: Vec<TableDiff>) -> Self {
-        Self { diff_id: diff_id.clone(), snapshot_id1: snapshot_id1.clone(), snapshot_id2: snapshot_id2.clone(), table_diffs }
+        let summary = DiffSummary::create(&table_diffs, &[]);
+        Self { diff_id: diff_id.clone(), snapshot_id1: snapshot_id1.clone(), snapshot_id2: snapshot_id2.clone(), table_diffs, summary }
+    }
+
+    // 集計も主キーの変わった行を含めて作り直す
+    pub fn detect_rekeys(&mut self, max_different_cols: usize) {
+        for table_diff in &mut self.table_diffs {
+            table_diff.detect_rekeys(max_different_cols);
+        }
+        let unchanged_table_names = self.summary.tables.iter().filter(|table| table.unchanged).map(|table| &table.table_name).collect_vec();
+        self.summary = DiffSummary::create(&self.table_diffs, &unchanged_table_names);
+    }
+}
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Clone, Default, Debug)]
+pub struct DiffSummary {
+    pub tables: Vec<TableDiffSummary>,
+    pub total: DiffTotal,
+}
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Clone, Debug)]
+pub struct TableDiffSummary {
+    pub table_name: TableName,
+    // テーブルのハッシュが一致した
+    pub unchanged: bool,
+    #[serde(default)]
+    pub renamed_from: Option<TableName>,
+    pub inserted: usize,
+    pub deleted: usize,
+    pub updated: usize,
+    // 主キーだけが変わったとみなした行数 (inserted と deleted には含めない)
+    #[serde(default)]
+    pub rekeyed: usize,
+    // 変更された行数を列の順に並べる (変更のない列は含めない)
+    pub col_modifications: Vec<ColModification>,
+}
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Clone, Debug)]
+pub struct ColModification {
+    pub col_name: ColName,
+    pub count: usize,
+}
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Clone, Default, Debug)]
+pub struct DiffTotal {
+    pub changed_tables: usize,
+    pub unchanged_tables: usize,
+    pub inserted: usize,
+    pub deleted: usize,
+    pub updated: usize,
+    #[serde(default)]
+    pub rekeyed: usize,
+}
+
+impl DiffSummary {
+    pub fn create(table_diffs: &[TableDiff], unchanged_table_names: &[&TableName]) -> Self {
+        let tables = table_diffs
+            .iter()
+            .map(TableDiffSummary::create)
+            .chain(unchanged_table_names.iter().map(|&table_name| TableDiffSummary::unchanged(table_name)))
+            .sorted_by(|table1, table2| table1.table_name.cmp(&table2.table_name))
+            .collect_vec();
+
+        let total = DiffTotal {
+            changed_tables: tables.iter().filter(|table| !table.unchanged).count(),
+            unchanged_tables: tables.iter().filter(|table| table.unchanged).count(),
+            inserted: tables.iter().map(|table| table.inserted).sum(),
+            deleted: tables.iter().map(|table| table.deleted).sum(),
+            updated: tables.iter().map(|table| table.updated).sum(),
+            rekeyed: tables.iter().map(|table| table.rekeyed).sum(),
+        };
+
+        Self { tables, total }
+    }
+}
+
+impl TableDiffSummary {
+    fn unchanged(table_name: &TableName) -> Self {
+        Self {
+            table_name: table_name.clone(),
+            unchanged: true,
+            renamed_from: None,
+            inserted: 0,
+            deleted: 0,
+            updated: 0,
+            rekeyed: 0,
+            col_modifications: vec![],
+        }
+    }
+
+    fn create(table_diff: &TableDiff) -> Self {
+        let mut summary = Self { unchanged: false, renamed_from: table_diff.renamed_from.clone(), ..Self::unchanged(&table_diff.table_name) };
+        let mut counts = vec![0; table_diff.col_names.len()];
+
+        let primary_values = table_diff.row_diffs1.keys().chain(table_diff.row_diffs2.keys()).unique();
+        for primary_value in primary_values {
+            match (table_diff.row_diffs1.get(primary_value), table_diff.row_diffs2.get(primary_value)) {
+                (Some(_), None) => summary.deleted += 1,
+                (None, Some(_)) => summary.inserted += 1,
+                (Some(cols1), Some(cols2)) => {
+                    let mut modified = false;
+                    for (i, col_name) in table_diff.col_names.iter().enumerate() {
+                        let is_modified = !matches!(
+                            (cols1.get(col_name), cols2.get(col_name)),
+                            (Some(Stay(_)), Some(Stay(_))) | (None | Some(NoValue), None | Some(NoValue))
+                        );
+                        if is_modified {
+                            counts[i] += 1;
+                            modified = true;
+                        }
+                    }
+                    if modified {
+                        summary.updated += 1;
+                    }
+                }
+                (None, None) => unreachable!(),
+            }
+        }
+
+        // 主キーの変わった行は、変更前と変更後の行で異なる列を数える
+        for rekey in &table_diff.rekeys {
+            if let (Some(cols1), Some(cols2)) = (table_diff.row_diffs1.get(&rekey.primary_value1), table_diff.row_diffs2.get(&rekey.primary_value2)) {
+                let values2 = row_values(&table_diff.col_names, cols2);
+                for (i, (v1, v2)) in row_values(&table_diff.col_names, cols1).into_iter().zip(values2).enumerate() {
+                    if v1 != v2 {
+                        counts[i] += 1;
+                    }
+                }
+            }
+        }
+
+        summary.rekeyed = table_diff.rekeys.len();
+        summary.deleted -= summary.rekeyed;
+        summary.inserted -= summary.rekeyed;
+
+        summary.col_modifications = table_diff
+            .col_names
+            .iter()
+            .zip(counts)
+            .filter(|(_, count)| *count > 0)
+            .map(|(col_name, count)| ColModification { col_name: col_name.clone(), count })
+            .collect();
+
+        summary
     }
 }
 
@@ -36,6 +185,27 @@ pub struct TableDiff {
     pub col_names: Vec<ColName>,
     pub row_diffs1: HashMap<PrimaryValue, HashMap<ColName, ColDiff>>,
     pub row_diffs2: HashMap<PrimaryValue, HashMap<ColName, ColDiff>>,
+    // 削除された行と追加された行のうち、主キー以外の内容が同じか近いものの組
+    #[serde(default)]
+    pub rekeys: Vec<Rekey>,
+    // 名前が変わったとみなしたテーブルの、変更前の名前
+    #[serde(default)]
+    pub renamed_from: Option<TableName>,
+    // 変更後のスナップショットにだけあるテーブル
+    #[serde(default)]
+    pub created: bool,
+    // 変更前のスナップショットにだけあるテーブル
+    #[serde(default)]
+    pub dropped: bool,
+}
+
+// 近い行を探すときに比べる、削除された行と追加された行の組の最大数
+const MAX_NEAR_MATCH_COMPARISONS: usize = 1_000_000;
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Clone, Debug)]
+pub struct Rekey {
+    pub primary_value1: PrimaryValue,
+    pub primary_value2: PrimaryValue,
 }
 
 impl TableDiff {
@@ -52,6 +222,114 @@ impl TableDiff {
             col_names: col_names.into_iter().cloned().collect(),
             row_diffs1: HashMap::new(),
             row_diffs2: HashMap::new(),
+            rekeys: vec![],
+            renamed_from: None,
+            created: false,
+            dropped: false,
+        }
+    }
+
+    // 削除された行ごとに、異なる列が max_different_cols 以下で最も少ない追加された行と組にする
+    // 内容の一致する行を先に組にして、近い行を探すのは残った行どうしだけにする
+    // 主キー以外の列がないテーブルは行を区別できないので、組にしない
+    // すべての列が異なる行は組にせず、近い行を探す組み合わせが多すぎる場合は内容の一致する行だけを組にする
+    pub fn detect_rekeys(&mut self, max_different_cols: usize) {
+        if self.col_names.is_empty() {
+            self.rekeys = vec![];
+            return;
+        }
+        let max_different_cols = max_different_cols.min(self.col_names.len() - 1);
+
+        let primary_values = self.primary_col_values.iter().map(|primary_col_value| primary_col_value.as_primary_value()).collect_vec();
+        let deleted = primary_values.iter().filter(|&pv| self.row_diffs1.contains_key(pv) && !self.row_diffs2.contains_key(pv)).collect_vec();
+        let added = primary_values.iter().filter(|&pv| !self.row_diffs1.contains_key(pv) && self.row_diffs2.contains_key(pv)).collect_vec();
+
+        let values = |cols| row_values(&self.col_names, cols);
+
+        let mut added_by_values: HashMap<Vec<Option<&ColValue>>, Vec<&PrimaryValue>> = HashMap::new();
+        for &pv in added.iter().rev() {
+            added_by_values.entry(values(&self.row_diffs2[pv])).or_default().push(pv);
+        }
+
+        let mut rekeys = vec![];
+        let mut paired = HashSet::new();
+        let mut unpaired = vec![];
+        for &pv1 in &deleted {
+            match added_by_values.get_mut(&values(&self.row_diffs1[pv1])).and_then(|pvs| pvs.pop()) {
+                Some(pv2) => {
+                    paired.insert(pv2);
+                    rekeys.push(Rekey { primary_value1: pv1.clone(), primary_value2: pv2.clone() });
+                }
+                None => unpaired.push(pv1),
+            }
+        }
+
+        if max_different_cols > 0 && unpaired.len() * (added.len() - paired.len()) <= MAX_NEAR_MATCH_COMPARISONS {
+            for pv1 in unpaired {
+                let values1 = values(&self.row_diffs1[pv1]);
+                let nearest = added
+                    .iter()
+                    .filter(|&&pv2| !paired.contains(pv2))
+                    .map(|&pv2| (pv2, values1.iter().zip(values(&self.row_diffs2[pv2])).filter(|(v1, v2)| v1 != &v2).count()))
+                    .filter(|(_, count)| *count <= max_different_cols)
+                    .min_by_key(|(_, count)| *count);
+                if let Some((pv2, _)) = nearest {
+                    paired.insert(pv2);
+                    rekeys.push(Rekey { primary_value1: pv1.clone(), primary_value2: pv2.clone() });
+                }
+            }
+        }
+
+        self.rekeys = rekeys;
+    }
+
+    // 変更のあった行の主キー値と変更の種類を、主キー値の順に返す
+    // 主キーの変わった行は、変更後の主キー値の位置にまとめる
+    pub fn changed_rows(&self) -> impl Iterator<Item = (PrimaryValue, RowChange)> + '_ {
+        let rekeyed1: HashSet<&PrimaryValue> = self.rekeys.iter().map(|rekey| &rekey.primary_value1).collect();
+        let rekeyed2: HashSet<&PrimaryValue> = self.rekeys.iter().map(|rekey| &rekey.primary_value2).collect();
+
+        self.primary_col_values.iter().map(|primary_col_value| primary_col_value.as_primary_value()).filter_map(move |primary_value| {
+            if rekeyed1.contains(&primary_value) {
+                return None;
+            }
+            if rekeyed2.contains(&primary_value) {
+                return Some((primary_value, RowChange::Rekeyed));
+            }
+
+            // ハッシュが一致した行はどちらにも含まれない
+            let change = match (self.row_diffs1.contains_key(&primary_value), self.row_diffs2.contains_key(&primary_value)) {
+                (false, true) => RowChange::Added,
+                (true, false) => RowChange::Removed,
+                (true, true) => RowChange::Modified,
+                (false, false) => return None,
+            };
+            Some((primary_value, change))
+        })
+    }
+
+    // 主キーの変わった行の、変更前の主キー値を返す
+    pub fn find_old_primary_value(&self, primary_value: &PrimaryValue) -> Option<&PrimaryValue> {
+        self.rekeys.iter().find(|rekey| &rekey.primary_value2 == primary_value).map(|rekey| &rekey.primary_value1)
+    }
+}
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Clone, Copy, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum RowChange {
+    Added,
+    Removed,
+    Modified,
+    Rekeyed,
+}
+
+impl RowChange {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RowChange::Added => "added",
+            RowChange::Removed => "removed",
+            RowChange::Modified => "modified",
+            RowChange::Rekeyed => "rekeyed",
         }
     }
 }
@@ -64,6 +342,114 @@ pub enum ColDiff {
     Deleted(ColValue),
 }
 
+fn row_values<'a>(col_names: &[ColName], cols: &'a HashMap<ColName, ColDiff>) -> Vec<Option<&'a ColValue>> {
+    col_names
+        .iter()
+        .map(|col_name| match cols.get(col_name) {
+            Some(Added(v) | Deleted(v) | Stay(v)) => Some(v),
+            Some(NoValue) | None => None,
+        })
+        .collect()
+}
+
+// どちらかのスナップショットにあるテーブルごとに、テーブル名の順で差分をとる
+// ハッシュの一致するテーブルは行を比べずに変更なしとして集計だけに含める
+// unchanged_table_names には、ハッシュが一致したため読み込まなかったテーブルを渡す
+// 名前が変わったとみなしたテーブルは、変更後の名前でひとつのテーブルとして差分をとる
+pub fn create_snapshot_diff(
+    snapshot_id1: &SnapshotId,
+    snapshot_id2: &SnapshotId,
+    table_snapshots1: &[TableSnapshot],
+    table_snapshots2: &[TableSnapshot],
+    unchanged_table_names: &[TableName],
+) -> SnapshotDiff {
+    let renames = find_renames(table_snapshots1, table_snapshots2);
+    let renamed_from = |table_name: &TableName| renames.iter().find(|(_, new_name)| *new_name == table_name).map(|&(old_name, _)| old_name);
+    let find_pair = |table_name: &TableName| {
+        (find_table_snapshot(table_snapshots1, renamed_from(table_name).unwrap_or(table_name)), find_table_snapshot(table_snapshots2, table_name))
+    };
+
+    let table_names = table_snapshots1
+        .iter()
+        .chain(table_snapshots2)
+        .map(|table_snapshot| &table_snapshot.table_name)
+        .filter(|&table_name| renames.iter().all(|(old_name, _)| *old_name != table_name))
+        .unique()
+        .sorted()
+        .collect_vec();
+
+    let (unchanged_table_names, changed_table_names): (Vec<&TableName>, Vec<&TableName>) =
+        table_names.into_iter().chain(unchanged_table_names).unique().partition(|&table_name| match find_pair(table_name) {
+            (Some(table_snapshot1), Some(table_snapshot2)) => table_snapshot1.hash == table_snapshot2.hash && renamed_from(table_name).is_none(),
+            (None, None) => true,
+            _ => false,
+        });
+
+    let table_diffs = changed_table_names
+        .iter()
+        .map(|&table_name| {
+            let (table_snapshot1, table_snapshot2) = find_pair(table_name);
+            let mut table_diff = create_table_diff(table_snapshot1, table_snapshot2);
+            if let Some(old_name) = renamed_from(table_name) {
+                table_diff.table_name = table_name.clone();
+                table_diff.renamed_from = Some(old_name.clone());
+            }
+            table_diff
+        })
+        .collect_vec();
+
+    let summary = DiffSummary::create(&table_diffs, &unchanged_table_names);
+
+    SnapshotDiff { diff_id: create_diff_id(), snapshot_id1: snapshot_id1.clone(), snapshot_id2: snapshot_id2.clone(), table_diffs, summary }
+}
+
+// 片方にしかないテーブルのうち、主キーと列の集合が一致し、多い方の行数の半分以上の行の内容が一致するものを名前の変更とみなす
+// 内容の一致する行がなければ、空のテーブルどうしでも名前の変更とはみなさない
+// 候補が複数ある場合は、内容の一致する行が最も多いものを選ぶ
+fn find_renames<'a>(table_snapshots1: &'a [TableSnapshot], table_snapshots2: &'a [TableSnapshot]) -> Vec<(&'a TableName, &'a TableName)> {
+    let added =
+        table_snapshots2.iter().filter(|table_snapshot| find_table_snapshot(table_snapshots1, &table_snapshot.table_name).is_none()).collect_vec();
+
+    let mut renames: Vec<(&TableName, &TableName)> = vec![];
+    for table_snapshot1 in
+        table_snapshots1.iter().filter(|table_snapshot| find_table_snapshot(table_snapshots2, &table_snapshot.table_name).is_none())
+    {
+        let row_hashes1 = table_snapshot1.row_snapshots.iter().map(|row_snapshot| &row_snapshot.hash).collect::<HashSet<_>>();
+
+        let candidate = added
+            .iter()
+            .filter(|table_snapshot2| renames.iter().all(|(_, new_name)| *new_name != &table_snapshot2.table_name))
+            .filter(|table_snapshot2| {
+                table_snapshot1.primary_col_name == table_snapshot2.primary_col_name
+                    && table_snapshot1.col_names.iter().sorted().eq(table_snapshot2.col_names.iter().sorted())
+            })
+            .map(|table_snapshot2| {
+                let overlap = table_snapshot2.row_snapshots.iter().filter(|row_snapshot| row_hashes1.contains(&row_snapshot.hash)).count();
+                (table_snapshot2, overlap)
+            })
+            .filter(|(table_snapshot2, overlap)| {
+                *overlap > 0 && overlap * 2 >= max(table_snapshot1.row_snapshots.len(), table_snapshot2.row_snapshots.len())
+            })
+            .max_by_key(|(_, overlap)| *overlap);
+
+        if let Some((table_snapshot2, _)) = candidate {
+            renames.push((&table_snapshot1.table_name, &table_snapshot2.table_name));
+        }
+    }
+
+    renames
+}
+
+// 両方のスナップショットにあって、ハッシュが一致するテーブルの名前を返す
+// これらのテーブルは行を読み込まなくても変更がないとわかる
+pub fn find_unchanged_table_names(table_hashes1: &[(TableName, Hash)], table_hashes2: &[(TableName, Hash)]) -> Vec<TableName> {
+    table_hashes1.iter().filter(|table_hash| table_hashes2.contains(table_hash)).map(|(table_name, _)| table_name.clone()).unique().sorted().collect()
+}
+
+fn find_table_snapshot<'a>(table_snapshots: &'a [TableSnapshot], table_name: &TableName) -> Option<&'a TableSnapshot> {
+    table_snapshots.iter().find(|table_snapshot| &table_snapshot.table_name == table_name)
+}
+
 pub fn create_table_diff(table_snapshot1: Option<&TableSnapshot>, table_snapshot2: Option<&TableSnapshot>) -> TableDiff {
     match (table_snapshot1, table_snapshot2) {
         (Some(table_snapshot1), Some(table_snapshot2)) => take_table_snapshot_diff(table_snapshot1, table_snapshot2),
@@ -154,6 +540,8 @@ fn create_missing_pair_diff(table_snapshot: &TableSnapshot, n: usize) -> TableDi
     let primary_col_values = table_snapshot.get_primary_col_values();
     let mut snapshot_diff =
         TableDiff::init(&table_snapshot.table_name, &primary_col_values, &table_snapshot.primary_col_name, table_snapshot.col_names.iter().collect());
+    snapshot_diff.created = n == 2;
+    snapshot_diff.dropped = n == 1;
 
     let rows = parse_rows(table_snapshot);
 
@@ -175,6 +563,352 @@ fn create_missing_pair_diff(table_snapshot: &TableSnapshot, n: usize) -> TableDi
     snapshot_diff
 }
 
+// 変更のあった行ひとつ分の変更で、NDJSON の 1 行や期待値ファイルの 1 件になる
+//   table: テーブル名 ( 名前が変わったとみなしたテーブルは変更後の名前 )
+//   key: 主キー値 ( rekeyed の場合は変更後の主キー値 )
+//   old_key: rekeyed の場合だけ、変更前の主キー値
+//   kind: added, removed, modified, rekeyed のいずれか
+//   before, after: 主キー以外の列名と値のオブジェクトで、行がない側は null
+// 値は ColValue::as_json_value の形式で、列名は辞書順に並ぶ
+#[derive(Serialize, Deserialize, Eq, PartialEq, Clone, Debug)]
+pub struct Change {
+    pub table: TableName,
+    pub key: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub old_key: Option<Value>,
+    pub kind: RowChange,
+    pub before: Option<RowObject>,
+    pub after: Option<RowObject>,
+}
+
+// テーブル名の順、主キー値の順に並べる
+pub fn create_changes(snapshot_diff: &SnapshotDiff) -> Vec<Change> {
+    snapshot_diff
+        .table_diffs
+        .iter()
+        .flat_map(|table_diff| {
+            table_diff.changed_rows().map(move |(primary_value, change)| {
+                let (before, after) = row_objects(table_diff, &primary_value);
+                Change {
+                    table: table_diff.table_name.clone(),
+                    key: primary_json_value(table_diff, &primary_value),
+                    old_key: table_diff
+                        .find_old_primary_value(&primary_value)
+                        .map(|old_primary_value| primary_json_value(table_diff, old_primary_value)),
+                    kind: change,
+                    before,
+                    after,
+                }
+            })
+        })
+        .collect()
+}
+
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub enum ChangeFormat {
+    Ndjson,
+    JsonPatch,
+}
+
+impl ChangeFormat {
+    pub fn emit(&self, snapshot_diff: &SnapshotDiff) -> anyhow::Result<String> {
+        match self {
+            ChangeFormat::Ndjson => emit_ndjson(snapshot_diff),
+            ChangeFormat::JsonPatch => emit_json_patch(snapshot_diff),
+        }
+    }
+}
+
+// 変更のあった行ごとに、Change を 1 行の JSON にして出力する ( NDJSON )
+pub fn emit_ndjson(snapshot_diff: &SnapshotDiff) -> anyhow::Result<String> {
+    create_changes(snapshot_diff).iter().map(|change| Ok(format!("{}\n", serde_json::to_string(change)?))).collect()
+}
+
+// 変更前のスナップショットを { テーブル名: { 主キー値: { 列名: 値 } } } の JSON とみなし、変更後にする RFC 6902 の JSON Patch を出力する
+// 主キー値は ColValue::as_json_value の値を文字列にしたもの
+//   名前が変わったとみなしたテーブル: テーブルの move
+//   作成されたテーブル: 空のテーブルの add と、行の add
+//   削除されたテーブル: テーブルの remove だけで、行の remove は出力しない
+//   added: 行の add
+//   removed: 行の remove
+//   modified: 変わった列ごとの add, remove, replace
+//   rekeyed: 行の move と、変わった列ごとの add, remove, replace
+// 1 行に 1 つの操作を書いた JSON の配列にする
+pub fn emit_json_patch(snapshot_diff: &SnapshotDiff) -> anyhow::Result<String> {
+    let mut operations = vec![];
+
+    for table_diff in &snapshot_diff.table_diffs {
+        let table_path = format!("/{}", escape_json_pointer(&table_diff.table_name));
+        if let Some(old_name) = &table_diff.renamed_from {
+            operations.push(PatchOperation::new("move", &table_path).from(format!("/{}", escape_json_pointer(old_name))));
+        }
+        if table_diff.dropped {
+            operations.push(PatchOperation::new("remove", &table_path));
+            continue;
+        }
+        if table_diff.created {
+            operations.push(PatchOperation::new("add", &table_path).value(json!({})));
+        }
+
+        for (primary_value, change) in table_diff.changed_rows() {
+            let row_path = format!("{table_path}/{}", escape_json_pointer(&primary_key_string(table_diff, &primary_value)));
+            let (before, after) = row_objects(table_diff, &primary_value);
+
+            match (change, before, after) {
+                (RowChange::Added, _, after) => operations.push(PatchOperation::new("add", &row_path).value(json!(after))),
+                (RowChange::Removed, _, _) => operations.push(PatchOperation::new("remove", &row_path)),
+                (_, Some(before), Some(after)) => {
+                    if let Some(old_primary_value) = table_diff.find_old_primary_value(&primary_value) {
+                        let from = format!("{table_path}/{}", escape_json_pointer(&primary_key_string(table_diff, old_primary_value)));
+                        operations.push(PatchOperation::new("move", &row_path).from(from));
+                    }
+                    for col_name in before.keys().chain(after.keys()).unique().sorted() {
+                        let path = format!("{row_path}/{}", escape_json_pointer(col_name));
+                        match (before.get(col_name), after.get(col_name)) {
+                            (Some(value1), Some(value2)) if value1 == value2 => {}
+                            (Some(_), Some(value2)) => operations.push(PatchOperation::new("replace", &path).value(value2.clone())),
+                            (None, Some(value2)) => operations.push(PatchOperation::new("add", &path).value(value2.clone())),
+                            (Some(_), None) => operations.push(PatchOperation::new("remove", &path)),
+                            (None, None) => unreachable!(),
+                        }
+                    }
+                }
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    if operations.is_empty() {
+        return Ok("[]\n".to_string());
+    }
+    let lines = operations.iter().map(|operation| Ok(format!("  {}", serde_json::to_string(operation)?))).collect::<anyhow::Result<Vec<_>>>()?;
+    Ok(format!("[\n{}\n]\n", lines.join(",\n")))
+}
+
+#[derive(Serialize)]
+struct PatchOperation {
+    op: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    from: Option<String>,
+    path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<Value>,
+}
+
+impl PatchOperation {
+    fn new(op: &'static str, path: &str) -> Self {
+        Self { op, from: None, path: path.to_string(), value: None }
+    }
+
+    fn from(self, from: String) -> Self {
+        Self { from: Some(from), ..self }
+    }
+
+    fn value(self, value: Value) -> Self {
+        Self { value: Some(value), ..self }
+    }
+}
+
+pub type RowObject = BTreeMap<ColName, Value>;
+
+// 変更前と変更後の行を、主キー以外の列名と値のオブジェクトにする
+fn row_objects(table_diff: &TableDiff, primary_value: &PrimaryValue) -> (Option<RowObject>, Option<RowObject>) {
+    let old_primary_value = table_diff.find_old_primary_value(primary_value).unwrap_or(primary_value);
+
+    let before = table_diff.row_diffs1.get(old_primary_value).map(|row_diff| {
+        row_diff
+            .iter()
+            .filter_map(|(col_name, col_diff)| match col_diff {
+                Stay(v) | Deleted(v) => Some((col_name.clone(), v.as_json_value())),
+                Added(_) | NoValue => None,
+            })
+            .collect()
+    });
+    let after = table_diff.row_diffs2.get(primary_value).map(|row_diff| {
+        row_diff
+            .iter()
+            .filter_map(|(col_name, col_diff)| match col_diff {
+                Stay(v) | Added(v) => Some((col_name.clone(), v.as_json_value())),
+                Deleted(_) | NoValue => None,
+            })
+            .collect()
+    });
+
+    (before, after)
+}
+
+fn primary_json_value(table_diff: &TableDiff, primary_value: &PrimaryValue) -> Value {
+    table_diff
+        .primary_col_values
+        .iter()
+        .find(|primary_col_value| &primary_col_value.as_primary_value() == primary_value)
+        .map(|primary_col_value| primary_col_value.as_json_value())
+        .unwrap_or_else(|| json!(primary_value))
+}
+
+fn primary_key_string(table_diff: &TableDiff, primary_value: &PrimaryValue) -> String {
+    match primary_json_value(table_diff, primary_value) {
+        Value::String(v) => v,
+        v => v.to_string(),
+    }
+}
+
+fn escape_json_pointer(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+// 基準のスナップショットと、そこから分かれた 2 つのスナップショットを比べる
+#[derive(Eq, PartialEq, Debug)]
+pub struct ThreeWayDiff {
+    pub snapshot_id_base: SnapshotId,
+    pub snapshot_id1: SnapshotId,
+    pub snapshot_id2: SnapshotId,
+    pub table_diffs: Vec<ThreeWayTableDiff>,
+}
+
+// 基準から変わった行だけを、主キー値の順に並べる
+#[derive(Eq, PartialEq, Debug)]
+pub struct ThreeWayTableDiff {
+    pub table_name: TableName,
+    pub primary_col_name: PrimaryColName,
+    pub col_names: Vec<ColName>,
+    pub rows: Vec<ThreeWayRowDiff>,
+}
+
+// 基準から変わった列だけを、列の順に並べる
+#[derive(Eq, PartialEq, Debug)]
+pub struct ThreeWayRowDiff {
+    pub primary_value: PrimaryValue,
+    pub change: ThreeWayChange,
+    pub cols: Vec<ThreeWayColDiff>,
+}
+
+// 行や列がない場合は None になる
+#[derive(Eq, PartialEq, Debug)]
+pub struct ThreeWayColDiff {
+    pub col_name: ColName,
+    pub change: ThreeWayChange,
+    pub base_value: Option<ColValue>,
+    pub value1: Option<ColValue>,
+    pub value2: Option<ColValue>,
+}
+
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub enum ThreeWayChange {
+    // 1 つ目のスナップショットだけで変わった
+    Only1,
+    // 2 つ目のスナップショットだけで変わった
+    Only2,
+    // 両方で同じように変わった
+    // 行では、両方で変わったが衝突する列がないことを表す
+    Both,
+    // 両方で異なるように変わった
+    Conflict,
+}
+
+impl ThreeWayChange {
+    fn of<T: PartialEq>(base: Option<T>, value1: Option<T>, value2: Option<T>) -> Option<Self> {
+        match (base != value1, base != value2) {
+            (false, false) => None,
+            (true, false) => Some(Self::Only1),
+            (false, true) => Some(Self::Only2),
+            (true, true) if value1 == value2 => Some(Self::Both),
+            (true, true) => Some(Self::Conflict),
+        }
+    }
+
+    // 列ごとの結果から行の結果を決める
+    // 片方が x を、もう片方が y を変えた行は衝突ではない
+    fn merge(changes: impl IntoIterator<Item = Self>) -> Option<Self> {
+        changes.into_iter().reduce(|acc, change| match (acc, change) {
+            (Self::Conflict, _) | (_, Self::Conflict) => Self::Conflict,
+            (acc, change) if acc == change => acc,
+            _ => Self::Both,
+        })
+    }
+}
+
+// 3 つのスナップショットのハッシュが一致するテーブルは行を比べない
+pub fn create_three_way_diff(
+    snapshot_id_base: &SnapshotId,
+    snapshot_id1: &SnapshotId,
+    snapshot_id2: &SnapshotId,
+    table_snapshots_base: &[TableSnapshot],
+    table_snapshots1: &[TableSnapshot],
+    table_snapshots2: &[TableSnapshot],
+) -> ThreeWayDiff {
+    let table_names = table_snapshots_base
+        .iter()
+        .chain(table_snapshots1)
+        .chain(table_snapshots2)
+        .map(|table_snapshot| &table_snapshot.table_name)
+        .unique()
+        .sorted()
+        .collect_vec();
+
+    let table_diffs = table_names
+        .into_iter()
+        .filter_map(|table_name| {
+            create_three_way_table_diff(
+                find_table_snapshot(table_snapshots_base, table_name),
+                find_table_snapshot(table_snapshots1, table_name),
+                find_table_snapshot(table_snapshots2, table_name),
+            )
+        })
+        .filter(|table_diff| !table_diff.rows.is_empty())
+        .collect();
+
+    ThreeWayDiff { snapshot_id_base: snapshot_id_base.clone(), snapshot_id1: snapshot_id1.clone(), snapshot_id2: snapshot_id2.clone(), table_diffs }
+}
+
+fn create_three_way_table_diff(
+    table_snapshot_base: Option<&TableSnapshot>,
+    table_snapshot1: Option<&TableSnapshot>,
+    table_snapshot2: Option<&TableSnapshot>,
+) -> Option<ThreeWayTableDiff> {
+    let table_snapshots = [table_snapshot_base, table_snapshot1, table_snapshot2].into_iter().flatten().collect_vec();
+    if table_snapshots.len() == 3 && table_snapshots.iter().map(|table_snapshot| &table_snapshot.hash).all_equal() {
+        return None;
+    }
+    let first = table_snapshots.first()?;
+
+    let col_names = table_snapshots.iter().flat_map(|table_snapshot| &table_snapshot.col_names).unique().collect_vec();
+    let primary_col_values = table_snapshots.iter().flat_map(|table_snapshot| table_snapshot.get_primary_col_values()).collect::<BTreeSet<_>>();
+
+    let rows_base = table_snapshot_base.map(parse_rows).unwrap_or_default();
+    let rows1 = table_snapshot1.map(parse_rows).unwrap_or_default();
+    let rows2 = table_snapshot2.map(parse_rows).unwrap_or_default();
+
+    let rows = primary_col_values
+        .into_iter()
+        .filter_map(|primary_col_value| {
+            let (row_base, row1, row2) = (rows_base.get(primary_col_value), rows1.get(primary_col_value), rows2.get(primary_col_value));
+            let row_change = ThreeWayChange::of(row_base.map(|(hash, _)| hash), row1.map(|(hash, _)| hash), row2.map(|(hash, _)| hash))?;
+
+            let value_of = |row: Option<&(&Hash, Cols)>, col_name| row.and_then(|(_, cols)| cols.get(col_name)).map(|&col_value| col_value.clone());
+            let cols = col_names
+                .iter()
+                .filter_map(|&col_name| {
+                    let (base_value, value1, value2) = (value_of(row_base, col_name), value_of(row1, col_name), value_of(row2, col_name));
+                    let change = ThreeWayChange::of(base_value.as_ref(), value1.as_ref(), value2.as_ref())?;
+                    Some(ThreeWayColDiff { col_name: col_name.clone(), change, base_value, value1, value2 })
+                })
+                .collect_vec();
+
+            // 主キーだけのテーブルでは列がないので、行のハッシュで決める
+            let change = ThreeWayChange::merge(cols.iter().map(|col| col.change)).unwrap_or(row_change);
+            Some(ThreeWayRowDiff { primary_value: primary_col_value.as_primary_value(), change, cols })
+        })
+        .collect();
+
+    Some(ThreeWayTableDiff {
+        table_name: first.table_name.clone(),
+        primary_col_name: first.primary_col_name.clone(),
+        col_names: col_names.into_iter().cloned().collect(),
+        rows,
+    })
+}
+
 #[cfg(test)]
 mod tests_create_snapshot_diff {
     use crate::domain::diff::ColDiff::*;
@@ -301,3 +1035,451 @@ mod tests_create_snapshot_diff {
         assert_eq!(&Added(n("39")), mk_act(&act.row_diffs2, n("1"), "age"));
     }
 }
+
+#[cfg(test)]
+mod tests_create_snapshot_diff_of_tables {
+    use crate::domain::diff::{
+        create_snapshot_diff, create_table_diff, find_unchanged_table_names, ColModification, DiffTotal, Rekey, TableDiffSummary,
+    };
+    use crate::domain::fixture::{self, n, s};
+    use crate::domain::snapshot::{ColValue, RowSnapshot, TableSnapshot};
+
+    fn mk_table_snapshot(table_name: &str, name: &str) -> TableSnapshot {
+        fixture::mk_table_snapshot(table_name, vec!["name"], vec![vec![n("1"), s(name)]])
+    }
+
+    #[test]
+    fn test_tables() {
+        let table_snapshots1 = vec![mk_table_snapshot("user", "John"), mk_table_snapshot("item", "pc")];
+        let table_snapshots2 = vec![mk_table_snapshot("user", "Jane"), mk_table_snapshot("category", "book")];
+
+        let act = create_snapshot_diff(&"snapshot1".to_string(), &"snapshot2".to_string(), &table_snapshots1, &table_snapshots2, &[]);
+
+        assert_eq!("snapshot1", act.snapshot_id1);
+        assert_eq!("snapshot2", act.snapshot_id2);
+
+        // 両方にあるテーブルも 1 つにまとめる
+        assert_eq!(vec!["category", "item", "user"], act.table_diffs.iter().map(|table_diff| table_diff.table_name.as_str()).collect::<Vec<_>>());
+        assert_eq!(1, act.table_diffs[0].row_diffs2.len());
+        assert_eq!(1, act.table_diffs[1].row_diffs1.len());
+        assert_eq!((1, 1), (act.table_diffs[2].row_diffs1.len(), act.table_diffs[2].row_diffs2.len()));
+    }
+
+    fn mk_users(col_names: Vec<&str>, rows: Vec<Vec<ColValue>>) -> TableSnapshot {
+        fixture::mk_table_snapshot("user", col_names, rows)
+    }
+
+    #[test]
+    fn test_summary() {
+        let table_snapshots1 = vec![
+            fixture::mk_table_snapshot(
+                "user",
+                vec!["name", "age"],
+                vec![
+                    vec![n("1"), s("John"), n("29")],
+                    vec![n("2"), s("Jack"), n("31")],
+                    vec![n("3"), s("Jill"), n("20")],
+                    vec![n("4"), s("Joe"), n("15")],
+                ],
+            ),
+            mk_table_snapshot("item", "pc"),
+        ];
+        let table_snapshots2 = vec![
+            fixture::mk_table_snapshot(
+                "user",
+                vec!["name", "age"],
+                vec![
+                    vec![n("1"), s("Jane"), n("29")],
+                    vec![n("3"), s("Jill"), n("21")],
+                    vec![n("4"), s("Joe"), n("15")],
+                    vec![n("5"), s("Jim"), n("40")],
+                ],
+            ),
+            mk_table_snapshot("item", "pc"),
+        ];
+
+        let act = create_snapshot_diff(&"snapshot1".to_string(), &"snapshot2".to_string(), &table_snapshots1, &table_snapshots2, &[]).summary;
+
+        assert_eq!(
+            vec![
+                TableDiffSummary {
+                    table_name: "item".to_string(),
+                    unchanged: true,
+                    renamed_from: None,
+                    inserted: 0,
+                    deleted: 0,
+                    updated: 0,
+                    rekeyed: 0,
+                    col_modifications: vec![]
+                },
+                TableDiffSummary {
+                    table_name: "user".to_string(),
+                    unchanged: false,
+                    renamed_from: None,
+                    inserted: 1,
+                    deleted: 1,
+                    updated: 2,
+                    rekeyed: 0,
+                    col_modifications: vec![
+                        ColModification { col_name: "name".to_string(), count: 1 },
+                        ColModification { col_name: "age".to_string(), count: 1 },
+                    ],
+                },
+            ],
+            act.tables
+        );
+        assert_eq!(DiffTotal { changed_tables: 1, unchanged_tables: 1, inserted: 1, deleted: 1, updated: 2, rekeyed: 0 }, act.total);
+    }
+
+    #[test]
+    fn test_unchanged_tables() {
+        let table_snapshots1 = vec![mk_table_snapshot("user", "John"), mk_table_snapshot("item", "pc")];
+        let table_snapshots2 = vec![mk_table_snapshot("user", "Jane"), mk_table_snapshot("item", "pc")];
+
+        // ハッシュの一致するテーブルは差分に含めず、集計では変更なしとする
+        let act = create_snapshot_diff(&"snapshot1".to_string(), &"snapshot2".to_string(), &table_snapshots1, &table_snapshots2, &[]);
+        assert_eq!(vec!["user"], act.table_diffs.iter().map(|table_diff| table_diff.table_name.as_str()).collect::<Vec<_>>());
+        assert_eq!(
+            vec![("item", true), ("user", false)],
+            act.summary.tables.iter().map(|table| (table.table_name.as_str(), table.unchanged)).collect::<Vec<_>>()
+        );
+
+        // 読み込まなかったテーブルも、集計では変更なしとする
+        let act = create_snapshot_diff(
+            &"snapshot1".to_string(),
+            &"snapshot2".to_string(),
+            &table_snapshots1[..1],
+            &table_snapshots2[..1],
+            &["category".to_string(), "item".to_string()],
+        );
+        assert_eq!(vec!["user"], act.table_diffs.iter().map(|table_diff| table_diff.table_name.as_str()).collect::<Vec<_>>());
+        assert_eq!(
+            vec![("category", true), ("item", true), ("user", false)],
+            act.summary.tables.iter().map(|table| (table.table_name.as_str(), table.unchanged)).collect::<Vec<_>>()
+        );
+        assert_eq!(DiffTotal { changed_tables: 1, unchanged_tables: 2, inserted: 0, deleted: 0, updated: 1, rekeyed: 0 }, act.summary.total);
+    }
+
+    #[test]
+    fn test_find_unchanged_table_names() {
+        let table_hashes1 = vec![("user".to_string(), "a".to_string()), ("item".to_string(), "b".to_string()), ("tag".to_string(), "c".to_string())];
+        let table_hashes2 =
+            vec![("user".to_string(), "x".to_string()), ("item".to_string(), "b".to_string()), ("category".to_string(), "c".to_string())];
+
+        assert_eq!(vec!["item".to_string()], find_unchanged_table_names(&table_hashes1, &table_hashes2));
+    }
+
+    fn rekey(primary_value1: &str, primary_value2: &str) -> Rekey {
+        Rekey { primary_value1: primary_value1.to_string(), primary_value2: primary_value2.to_string() }
+    }
+
+    #[test]
+    fn test_rekeys() {
+        let table_snapshot1 = fixture::mk_table_snapshot(
+            "user",
+            vec!["name", "age"],
+            vec![
+                vec![n("1"), s("John"), n("29")],
+                vec![n("2"), s("Jack"), n("31")],
+                vec![n("3"), s("Jill"), n("20")],
+                vec![n("4"), s("Joe"), n("15")],
+            ],
+        );
+        let table_snapshot2 = fixture::mk_table_snapshot(
+            "user",
+            vec!["name", "age"],
+            vec![
+                vec![n("1"), s("John"), n("29")],
+                vec![n("5"), s("Jill"), n("21")],
+                vec![n("6"), s("Jack"), n("31")],
+                vec![n("7"), s("Jim"), n("40")],
+            ],
+        );
+
+        // 内容の一致する行だけを組にする
+        let mut table_diff = create_table_diff(Some(&table_snapshot1), Some(&table_snapshot2));
+        table_diff.detect_rekeys(0);
+        assert_eq!(vec![rekey("2", "6")], table_diff.rekeys);
+
+        // 異なる列が 1 つまでの行も組にする
+        let mut table_diff = create_table_diff(Some(&table_snapshot1), Some(&table_snapshot2));
+        table_diff.detect_rekeys(1);
+        assert_eq!(vec![rekey("2", "6"), rekey("3", "5")], table_diff.rekeys);
+
+        // すべての列が異なる行は組にしない
+        let mut table_diff = create_table_diff(Some(&table_snapshot1), Some(&table_snapshot2));
+        table_diff.detect_rekeys(2);
+        assert_eq!(vec![rekey("2", "6"), rekey("3", "5")], table_diff.rekeys);
+
+        // 主キーの変わった行で異なる列も、列ごとの変更に数える
+        let mut act = create_snapshot_diff(&"snapshot1".to_string(), &"snapshot2".to_string(), &[table_snapshot1], &[table_snapshot2], &[]);
+        act.detect_rekeys(1);
+        assert_eq!(DiffTotal { changed_tables: 1, unchanged_tables: 0, inserted: 1, deleted: 1, updated: 0, rekeyed: 2 }, act.summary.total);
+        assert_eq!(vec![ColModification { col_name: "age".to_string(), count: 1 }], act.summary.tables[0].col_modifications);
+
+        // 主キー以外の列がないテーブルは組にしない
+        let tags1 = mk_users(vec![], vec![vec![n("1")], vec![n("2")]]);
+        let tags2 = mk_users(vec![], vec![vec![n("3")], vec![n("4")]]);
+        let mut table_diff = create_table_diff(Some(&tags1), Some(&tags2));
+        table_diff.detect_rekeys(1);
+        assert!(table_diff.rekeys.is_empty());
+    }
+
+    #[test]
+    fn test_renames() {
+        let table_snapshots1 = vec![
+            fixture::mk_table_snapshot(
+                "user",
+                vec!["name", "age"],
+                vec![vec![n("1"), s("John"), n("29")], vec![n("2"), s("Jack"), n("31")], vec![n("3"), s("Jill"), n("20")]],
+            ),
+            mk_table_snapshot("item", "pc"),
+        ];
+        let members = TableSnapshot::new(
+            &"member".to_string(),
+            "id".to_string(),
+            vec!["name".to_string(), "age".to_string()],
+            vec![
+                RowSnapshot::new(vec![n("1"), s("John"), n("29")]),
+                RowSnapshot::new(vec![n("2"), s("Jack"), n("31")]),
+                RowSnapshot::new(vec![n("3"), s("Jill"), n("21")]),
+            ],
+        );
+        let categories =
+            TableSnapshot::new(&"category".to_string(), "id".to_string(), vec!["title".to_string()], vec![RowSnapshot::new(vec![n("1"), s("pc")])]);
+        let table_snapshots2 = vec![members, categories];
+
+        let act = create_snapshot_diff(&"snapshot1".to_string(), &"snapshot2".to_string(), &table_snapshots1, &table_snapshots2, &[]);
+
+        // 列の異なるテーブルは名前の変更とみなさない
+        assert_eq!(
+            vec![("category", None), ("item", None), ("member", Some("user"))],
+            act.table_diffs.iter().map(|table_diff| (table_diff.table_name.as_str(), table_diff.renamed_from.as_deref())).collect::<Vec<_>>()
+        );
+        assert_eq!((1, 1), (act.table_diffs[2].row_diffs1.len(), act.table_diffs[2].row_diffs2.len()));
+        assert_eq!(Some("user"), act.summary.tables[2].renamed_from.as_deref());
+        assert_eq!(DiffTotal { changed_tables: 3, unchanged_tables: 0, inserted: 1, deleted: 1, updated: 1, rekeyed: 0 }, act.summary.total);
+
+        // 空のテーブルどうしは名前の変更とみなさない
+        let empty = |table_name: &str| TableSnapshot::new(&table_name.to_string(), "id".to_string(), vec!["name".to_string()], vec![]);
+        let act = create_snapshot_diff(&"snapshot1".to_string(), &"snapshot2".to_string(), &[empty("logs")], &[empty("events")], &[]);
+        assert_eq!(
+            vec![("events", None), ("logs", None)],
+            act.table_diffs.iter().map(|table_diff| (table_diff.table_name.as_str(), table_diff.renamed_from.as_deref())).collect::<Vec<_>>()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests_create_three_way_diff {
+    use crate::domain::diff::ThreeWayChange::{Both, Conflict, Only1, Only2};
+    use crate::domain::diff::{create_three_way_diff, ThreeWayChange};
+    use crate::domain::fixture::{mk_table_snapshot, n, s};
+    use crate::domain::snapshot::{ColValue, TableSnapshot};
+
+    fn mk_users(rows: Vec<Vec<ColValue>>) -> TableSnapshot {
+        mk_table_snapshot("users", vec!["name", "age"], rows)
+    }
+
+    type Col<'a> = (&'a str, ThreeWayChange, Option<ColValue>, Option<ColValue>, Option<ColValue>);
+
+    #[test]
+    fn test_three_way() {
+        let base = vec![mk_users(vec![
+            vec![n("1"), s("John"), n("29")],
+            vec![n("2"), s("Jack"), n("31")],
+            vec![n("3"), s("Jill"), n("20")],
+            vec![n("4"), s("Joe"), n("15")],
+            vec![n("5"), s("Jim"), n("40")],
+        ])];
+        let snapshot1 = vec![mk_users(vec![
+            vec![n("1"), s("Jane"), n("29")],
+            vec![n("2"), s("Jack"), n("31")],
+            vec![n("3"), s("Jill"), n("21")],
+            vec![n("4"), s("Joe"), n("16")],
+            vec![n("6"), s("Jeff"), n("50")],
+        ])];
+        let snapshot2 = vec![mk_users(vec![
+            vec![n("1"), s("John"), n("29")],
+            vec![n("2"), s("Jake"), n("31")],
+            vec![n("3"), s("Jill"), n("21")],
+            vec![n("4"), s("Joe"), n("17")],
+            vec![n("5"), s("Jim"), n("40")],
+        ])];
+
+        let act = create_three_way_diff(&"base".to_string(), &"s1".to_string(), &"s2".to_string(), &base, &snapshot1, &snapshot2);
+
+        assert_eq!(1, act.table_diffs.len());
+        let exp: Vec<(&str, ThreeWayChange, Vec<Col>)> = vec![
+            ("1", Only1, vec![("name", Only1, Some(s("John")), Some(s("Jane")), Some(s("John")))]),
+            ("2", Only2, vec![("name", Only2, Some(s("Jack")), Some(s("Jack")), Some(s("Jake")))]),
+            ("3", Both, vec![("age", Both, Some(n("20")), Some(n("21")), Some(n("21")))]),
+            ("4", Conflict, vec![("age", Conflict, Some(n("15")), Some(n("16")), Some(n("17")))]),
+            ("5", Only1, vec![("name", Only1, Some(s("Jim")), None, Some(s("Jim"))), ("age", Only1, Some(n("40")), None, Some(n("40")))]),
+            ("6", Only1, vec![("name", Only1, None, Some(s("Jeff")), None), ("age", Only1, None, Some(n("50")), None)]),
+        ];
+        assert_eq!(
+            exp,
+            act.table_diffs[0]
+                .rows
+                .iter()
+                .map(|row| (
+                    row.primary_value.as_str(),
+                    row.change,
+                    row.cols
+                        .iter()
+                        .map(|col| (col.col_name.as_str(), col.change, col.base_value.clone(), col.value1.clone(), col.value2.clone()))
+                        .collect::<Vec<_>>()
+                ))
+                .collect::<Vec<_>>()
+        );
+
+        // 3 つとも同じテーブルは含めない
+        let act = create_three_way_diff(&"base".to_string(), &"s1".to_string(), &"s2".to_string(), &base, &base, &base);
+        assert!(act.table_diffs.is_empty());
+    }
+
+    #[test]
+    fn test_three_way_row_change() {
+        let base = vec![mk_users(vec![vec![n("1"), s("John"), n("29")], vec![n("2"), s("Jack"), n("31")]])];
+        let snapshot1 = vec![mk_users(vec![vec![n("1"), s("Jane"), n("29")], vec![n("2"), s("Jake"), n("31")]])];
+        let snapshot2 = vec![mk_users(vec![vec![n("1"), s("John"), n("30")], vec![n("2"), s("Jill"), n("32")]])];
+
+        let act = create_three_way_diff(&"base".to_string(), &"s1".to_string(), &"s2".to_string(), &base, &snapshot1, &snapshot2);
+
+        // 別々の列を変えた行は衝突せず、同じ列を異なる値に変えた行だけが衝突する
+        assert_eq!(
+            vec![("1", Both, vec![Only1, Only2]), ("2", Conflict, vec![Conflict, Only2])],
+            act.table_diffs[0]
+                .rows
+                .iter()
+                .map(|row| (row.primary_value.as_str(), row.change, row.cols.iter().map(|col| col.change).collect::<Vec<_>>()))
+                .collect::<Vec<_>>()
+        );
+
+        // 主キーだけのテーブルは行のハッシュで決める
+        let tags = |rows: Vec<&str>| vec![mk_table_snapshot("tags", vec![], rows.into_iter().map(|id| vec![n(id)]).collect())];
+        let act = create_three_way_diff(
+            &"base".to_string(),
+            &"s1".to_string(),
+            &"s2".to_string(),
+            &tags(vec!["1"]),
+            &tags(vec!["1", "2"]),
+            &tags(vec!["1"]),
+        );
+        assert_eq!(vec![("2", Only1)], act.table_diffs[0].rows.iter().map(|row| (row.primary_value.as_str(), row.change)).collect::<Vec<_>>());
+    }
+}
+
+#[cfg(test)]
+mod tests_emit {
+    use crate::domain::diff::{create_snapshot_diff, emit_json_patch, emit_ndjson, escape_json_pointer, ChangeFormat, SnapshotDiff};
+    use crate::domain::fixture::{mk_table_snapshot, n, s};
+    use crate::domain::snapshot::ColValue::Null;
+
+    fn snapshot_diff() -> SnapshotDiff {
+        let table_snapshots1 = vec![
+            mk_table_snapshot("accounts", vec!["name"], vec![vec![n("1"), s("x")], vec![n("2"), s("y")]]),
+            mk_table_snapshot(
+                "users",
+                vec!["name", "age"],
+                vec![
+                    vec![n("1"), s("John"), n("29")],
+                    vec![n("2"), s("Jack"), n("31")],
+                    vec![n("3"), s("Jane"), Null],
+                    vec![n("5"), s("Ann"), n("40")],
+                ],
+            ),
+        ];
+        let table_snapshots2 = vec![
+            mk_table_snapshot("members", vec!["name"], vec![vec![n("1"), s("x")], vec![n("2"), s("z")]]),
+            mk_table_snapshot(
+                "users",
+                vec!["name", "age"],
+                vec![
+                    vec![n("1"), s("John"), n("29")],
+                    vec![n("2"), s("Jill"), n("31")],
+                    vec![n("4"), s("a/b~c"), n("20")],
+                    vec![n("6"), s("Ann"), n("40")],
+                ],
+            ),
+        ];
+
+        let mut snapshot_diff = create_snapshot_diff(&"s1".to_string(), &"s2".to_string(), &table_snapshots1, &table_snapshots2, &[]);
+        snapshot_diff.detect_rekeys(0);
+        snapshot_diff
+    }
+
+    #[test]
+    fn test_ndjson() {
+        assert_eq!(
+            r#"{"table":"members","key":2,"kind":"modified","before":{"name":"y"},"after":{"name":"z"}}
+{"table":"users","key":2,"kind":"modified","before":{"age":31,"name":"Jack"},"after":{"age":31,"name":"Jill"}}
+{"table":"users","key":3,"kind":"removed","before":{"age":null,"name":"Jane"},"after":null}
+{"table":"users","key":4,"kind":"added","before":null,"after":{"age":20,"name":"a/b~c"}}
+{"table":"users","key":6,"old_key":5,"kind":"rekeyed","before":{"age":40,"name":"Ann"},"after":{"age":40,"name":"Ann"}}
+"#,
+            emit_ndjson(&snapshot_diff()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_json_patch() {
+        assert_eq!(
+            r#"[
+  {"op":"move","from":"/accounts","path":"/members"},
+  {"op":"replace","path":"/members/2/name","value":"z"},
+  {"op":"replace","path":"/users/2/name","value":"Jill"},
+  {"op":"remove","path":"/users/3"},
+  {"op":"add","path":"/users/4","value":{"age":20,"name":"a/b~c"}},
+  {"op":"move","from":"/users/5","path":"/users/6"}
+]
+"#,
+            emit_json_patch(&snapshot_diff()).unwrap()
+        );
+
+        assert_eq!("a~1b~0c", escape_json_pointer("a/b~c"));
+    }
+
+    #[test]
+    fn test_created_and_dropped_tables() {
+        let table_snapshots1 = vec![mk_table_snapshot("logs", vec!["message"], vec![vec![n("1"), s("login")], vec![n("2"), s("logout")]])];
+        let table_snapshots2 = vec![
+            mk_table_snapshot("tags", vec![], vec![]),
+            mk_table_snapshot("users", vec!["name"], vec![vec![n("1"), s("John")], vec![n("2"), s("Jack")]]),
+        ];
+        let snapshot_diff = create_snapshot_diff(&"s1".to_string(), &"s2".to_string(), &table_snapshots1, &table_snapshots2, &[]);
+
+        // 作成されたテーブルは空のテーブルを追加してから行を追加し、削除されたテーブルはテーブルだけを削除する
+        assert_eq!(
+            r#"[
+  {"op":"remove","path":"/logs"},
+  {"op":"add","path":"/tags","value":{}},
+  {"op":"add","path":"/users","value":{}},
+  {"op":"add","path":"/users/1","value":{"name":"John"}},
+  {"op":"add","path":"/users/2","value":{"name":"Jack"}}
+]
+"#,
+            emit_json_patch(&snapshot_diff).unwrap()
+        );
+
+        // NDJSON は行ごとの変更だけを出力する
+        assert_eq!(
+            r#"{"table":"logs","key":1,"kind":"removed","before":{"message":"login"},"after":null}
+{"table":"logs","key":2,"kind":"removed","before":{"message":"logout"},"after":null}
+{"table":"users","key":1,"kind":"added","before":null,"after":{"name":"John"}}
+{"table":"users","key":2,"kind":"added","before":null,"after":{"name":"Jack"}}
+"#,
+            ChangeFormat::Ndjson.emit(&snapshot_diff).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_no_changes() {
+        let table_snapshots = vec![mk_table_snapshot("users", vec!["name"], vec![vec![n("1"), s("John")]])];
+        let snapshot_diff = create_snapshot_diff(&"s1".to_string(), &"s2".to_string(), &table_snapshots, &table_snapshots, &[]);
+
+        assert_eq!("", emit_ndjson(&snapshot_diff).unwrap());
+        assert_eq!("[]\n", ChangeFormat::JsonPatch.emit(&snapshot_diff).unwrap());
+    }
+}
@@ -0,0 +1,448 @@
+use std::fmt::{Display, Formatter};
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::domain::diff::{create_changes, Change, RowChange, RowObject, SnapshotDiff};
+use crate::domain::report::IgnoreRule;
+
+// 差分として期待する変更の一覧で、テストの期待値ファイルとして JSON で保存する
+// changes の値や key には、任意の値に一致する "*" や、"2023-07-*" のように任意の文字列を含む形を書ける
+// "*" そのものを書く場合は "\*"、"\" そのものを書く場合は "\\" とし、create と update で書く差分の値もそのようにエスケープする
+// ignore には orders, orders.updated_at, *.updated_at の形式で、比べないテーブルや列を書く
+#[derive(Serialize, Deserialize, Eq, PartialEq, Default, Debug)]
+pub struct Expectation {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ignore: Vec<String>,
+    pub changes: Vec<Change>,
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub enum Mismatch {
+    // 期待した変更が差分にない
+    Missing(Change),
+    // 期待していない変更が差分にある
+    Unexpected(Change),
+    // 同じ行の変更だが、内容が異なる
+    Different { table: String, key: Value, differences: Vec<Difference> },
+}
+
+// path は kind, old_key, before, after, before.<列名>, after.<列名> のいずれかで、値がない側は None
+#[derive(Eq, PartialEq, Debug)]
+pub struct Difference {
+    pub path: String,
+    pub expected: Option<Value>,
+    pub actual: Option<Value>,
+}
+
+impl Display for Mismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Mismatch::Missing(change) => write!(f, "missing: {} {} {}", change.table, display_key(&change.key), change.kind.as_str()),
+            Mismatch::Unexpected(change) => write!(f, "unexpected: {} {} {}", change.table, display_key(&change.key), change.kind.as_str()),
+            Mismatch::Different { table, key, differences } => {
+                write!(f, "different: {table} {}", display_key(key))?;
+                for difference in differences {
+                    let display = |value: &Option<Value>| value.as_ref().map(Value::to_string).unwrap_or_else(|| "-".to_string());
+                    write!(f, "\n    {}: expected {} but was {}", difference.path, display(&difference.expected), display(&difference.actual))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn display_key(key: &Value) -> String {
+    match key {
+        Value::String(key) => key.clone(),
+        key => key.to_string(),
+    }
+}
+
+impl Expectation {
+    // 差分の変更をそのまま期待値にする
+    pub fn create(snapshot_diff: &SnapshotDiff, ignore: Vec<String>) -> Self {
+        let changes = create_actual_changes(snapshot_diff, &ignore).iter().map(escape_change).collect();
+        Self { ignore, changes }
+    }
+
+    // 期待値を差分に合わせて書き直す
+    // 差分に一致する期待値はワイルドカードを残すためそのまま使い、それ以外は差分の変更に置き換える
+    pub fn update(&self, snapshot_diff: &SnapshotDiff) -> Self {
+        let actual_changes = create_actual_changes(snapshot_diff, &self.ignore);
+        let pairs = pair_changes(&self.changes, &actual_changes);
+
+        let changes = actual_changes
+            .iter()
+            .enumerate()
+            .map(|(j, actual)| match pairs.iter().find(|(_, paired_j)| *paired_j == Some(j)) {
+                Some(&(i, _)) if compare_changes(&self.changes[i], actual).is_empty() => self.changes[i].clone(),
+                _ => escape_change(actual),
+            })
+            .collect();
+
+        Self { ignore: self.ignore.clone(), changes }
+    }
+
+    // 期待値の並び順は問わない
+    // 主キー値にワイルドカードを含まない期待値は同じ行の変更と、含む期待値は残りの変更のうち一致するものと組にする
+    pub fn verify(&self, snapshot_diff: &SnapshotDiff) -> Vec<Mismatch> {
+        let actual_changes = create_actual_changes(snapshot_diff, &self.ignore);
+        let pairs = pair_changes(&self.changes, &actual_changes);
+
+        let mut mismatches = vec![];
+        for (i, j) in pairs.iter().sorted_by_key(|(i, _)| *i) {
+            let expected = &self.changes[*i];
+            match j {
+                Some(j) => {
+                    let differences = compare_changes(expected, &actual_changes[*j]);
+                    if !differences.is_empty() {
+                        mismatches.push(Mismatch::Different { table: expected.table.clone(), key: actual_changes[*j].key.clone(), differences });
+                    }
+                }
+                None => mismatches.push(Mismatch::Missing(expected.clone())),
+            }
+        }
+
+        for (j, actual) in actual_changes.iter().enumerate() {
+            if pairs.iter().all(|(_, paired_j)| *paired_j != Some(j)) {
+                mismatches.push(Mismatch::Unexpected(actual.clone()));
+            }
+        }
+
+        mismatches
+    }
+}
+
+// 無視するテーブルの変更と列を除き、無視する列だけが変わった行は変更とみなさない
+fn create_actual_changes(snapshot_diff: &SnapshotDiff, ignore: &[String]) -> Vec<Change> {
+    let ignore_rules = ignore.iter().map(|rule| IgnoreRule::parse(rule)).collect_vec();
+
+    create_changes(snapshot_diff)
+        .into_iter()
+        .filter(|change| ignore_rules.iter().all(|rule| !rule.ignores_table(&change.table)))
+        .map(|change| {
+            let retain = |row: Option<RowObject>| {
+                row.map(|row| {
+                    row.into_iter().filter(|(col_name, _)| ignore_rules.iter().all(|rule| !rule.ignores_col(&change.table, col_name))).collect()
+                })
+            };
+            Change { before: retain(change.before.clone()), after: retain(change.after.clone()), ..change }
+        })
+        .filter(|change| change.kind != RowChange::Modified || change.before != change.after)
+        .collect()
+}
+
+// 期待値ごとに、組にした変更の位置を返す
+fn pair_changes(expected_changes: &[Change], actual_changes: &[Change]) -> Vec<(usize, Option<usize>)> {
+    let mut paired = vec![false; actual_changes.len()];
+    let mut pairs = vec![];
+
+    // 主キー値が決まっている期待値は、同じテーブルの同じ主キー値の変更と組にする
+    let (exact, wildcard): (Vec<usize>, Vec<usize>) = (0..expected_changes.len()).partition(|&i| !has_wildcard(&expected_changes[i].key));
+    for i in exact {
+        let expected = &expected_changes[i];
+        let j = (0..actual_changes.len())
+            .find(|&j| !paired[j] && actual_changes[j].table == expected.table && matches_value(&expected.key, &actual_changes[j].key));
+        if let Some(j) = j {
+            paired[j] = true;
+        }
+        pairs.push((i, j));
+    }
+
+    // ワイルドカードを含む期待値は、まず内容まで一致する変更と組にする
+    let mut unpaired = vec![];
+    for i in wildcard {
+        let expected = &expected_changes[i];
+        match (0..actual_changes.len()).find(|&j| !paired[j] && compare_changes(expected, &actual_changes[j]).is_empty()) {
+            Some(j) => {
+                paired[j] = true;
+                pairs.push((i, Some(j)));
+            }
+            None => unpaired.push(i),
+        }
+    }
+
+    // 残りは、同じテーブルで主キー値と変更の種類の一致する変更と組にして、違いを報告する
+    for i in unpaired {
+        let expected = &expected_changes[i];
+        let j = (0..actual_changes.len()).find(|&j| {
+            !paired[j]
+                && actual_changes[j].table == expected.table
+                && actual_changes[j].kind == expected.kind
+                && matches_value(&expected.key, &actual_changes[j].key)
+        });
+        if let Some(j) = j {
+            paired[j] = true;
+        }
+        pairs.push((i, j));
+    }
+
+    pairs
+}
+
+fn compare_changes(expected: &Change, actual: &Change) -> Vec<Difference> {
+    let mut differences = vec![];
+
+    if expected.table != actual.table {
+        differences.push(Difference {
+            path: "table".to_string(),
+            expected: Some(Value::from(expected.table.clone())),
+            actual: Some(Value::from(actual.table.clone())),
+        });
+    }
+    if !matches_value(&expected.key, &actual.key) {
+        differences.push(Difference { path: "key".to_string(), expected: Some(expected.key.clone()), actual: Some(actual.key.clone()) });
+    }
+    if expected.kind != actual.kind {
+        differences.push(Difference {
+            path: "kind".to_string(),
+            expected: Some(Value::from(expected.kind.as_str())),
+            actual: Some(Value::from(actual.kind.as_str())),
+        });
+    }
+    match (&expected.old_key, &actual.old_key) {
+        (Some(expected_old_key), Some(actual_old_key)) if matches_value(expected_old_key, actual_old_key) => {}
+        (None, None) => {}
+        (expected_old_key, actual_old_key) => {
+            differences.push(Difference { path: "old_key".to_string(), expected: expected_old_key.clone(), actual: actual_old_key.clone() })
+        }
+    }
+
+    for (name, expected_row, actual_row) in [("before", &expected.before, &actual.before), ("after", &expected.after, &actual.after)] {
+        match (expected_row, actual_row) {
+            (Some(expected_row), Some(actual_row)) => {
+                for col_name in expected_row.keys().chain(actual_row.keys()).unique().sorted() {
+                    let (expected_value, actual_value) = (expected_row.get(col_name), actual_row.get(col_name));
+                    if !matches!((expected_value, actual_value), (Some(e), Some(a)) if matches_value(e, a)) {
+                        differences.push(Difference {
+                            path: format!("{name}.{col_name}"),
+                            expected: expected_value.cloned(),
+                            actual: actual_value.cloned(),
+                        });
+                    }
+                }
+            }
+            (None, None) => {}
+            (expected_row, actual_row) => differences.push(Difference {
+                path: name.to_string(),
+                expected: expected_row.as_ref().map(|row| json!(row)),
+                actual: actual_row.as_ref().map(|row| json!(row)),
+            }),
+        }
+    }
+
+    differences
+}
+
+// 差分の値をそのまま期待値に書くときに、\ と * をエスケープしてパターンとして読まれないようにする
+fn escape_change(change: &Change) -> Change {
+    let escape_row =
+        |row: &Option<RowObject>| row.as_ref().map(|row| row.iter().map(|(col_name, value)| (col_name.clone(), escape_value(value))).collect());
+    Change {
+        table: change.table.clone(),
+        key: escape_value(&change.key),
+        old_key: change.old_key.as_ref().map(escape_value),
+        kind: change.kind,
+        before: escape_row(&change.before),
+        after: escape_row(&change.after),
+    }
+}
+
+fn escape_value(value: &Value) -> Value {
+    match value {
+        Value::String(value) => Value::String(value.replace('\\', "\\\\").replace('*', "\\*")),
+        value => value.clone(),
+    }
+}
+
+fn has_wildcard(pattern: &Value) -> bool {
+    match pattern {
+        Value::String(pattern) => parse_pattern(pattern).len() > 1,
+        _ => false,
+    }
+}
+
+// "*" は null やオブジェクトも含めて任意の値に一致し、それ以外の * を含む文字列は任意の文字列に一致する
+// 数値には、数値を書いた文字列のパターンも一致する
+fn matches_value(pattern: &Value, actual: &Value) -> bool {
+    match (pattern, actual) {
+        (Value::String(pattern), _) if pattern == "*" => true,
+        (Value::String(pattern), Value::String(actual)) => matches_pattern(&parse_pattern(pattern), actual),
+        (Value::String(pattern), Value::Number(actual)) => matches_pattern(&parse_pattern(pattern), &actual.to_string()),
+        (pattern, actual) => pattern == actual,
+    }
+}
+
+// * で区切った文字列の並びにする
+fn parse_pattern(pattern: &str) -> Vec<String> {
+    let mut segments = vec![String::new()];
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(next) => segments.last_mut().unwrap().push(next),
+                None => segments.last_mut().unwrap().push(c),
+            },
+            '*' => segments.push(String::new()),
+            _ => segments.last_mut().unwrap().push(c),
+        }
+    }
+    segments
+}
+
+fn matches_pattern(segments: &[String], actual: &str) -> bool {
+    match segments {
+        [] => actual.is_empty(),
+        [segment] => segment == actual,
+        [first, middle @ .., last] => {
+            if actual.len() < first.len() + last.len() || !actual.starts_with(first.as_str()) || !actual.ends_with(last.as_str()) {
+                return false;
+            }
+            let mut rest = &actual[first.len()..actual.len() - last.len()];
+            for segment in middle {
+                match rest.find(segment.as_str()) {
+                    Some(i) => rest = &rest[i + segment.len()..],
+                    None => return false,
+                }
+            }
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::domain::diff::{create_snapshot_diff, SnapshotDiff};
+    use crate::domain::expectation::{matches_value, Expectation, Mismatch};
+    use crate::domain::fixture::{d, mk_table_snapshot, n, s};
+    use crate::domain::snapshot::{ColValue, TableSnapshot};
+
+    fn mk_users(rows: Vec<Vec<ColValue>>) -> TableSnapshot {
+        mk_table_snapshot("users", vec!["name", "updated_at"], rows)
+    }
+
+    fn snapshot_diff() -> SnapshotDiff {
+        let table_snapshots1 =
+            vec![mk_users(vec![vec![n("1"), s("John"), d("2023-07-01 10:00:00")], vec![n("2"), s("Jack"), d("2023-07-01 10:00:00")]])];
+        let table_snapshots2 = vec![mk_users(vec![
+            vec![n("1"), s("John"), d("2023-07-02 10:00:00")],
+            vec![n("2"), s("Jill"), d("2023-07-02 10:00:00")],
+            vec![n("38"), s("Bob"), d("2023-07-02 10:00:00")],
+        ])];
+        create_snapshot_diff(&"s1".to_string(), &"s2".to_string(), &table_snapshots1, &table_snapshots2, &[])
+    }
+
+    fn expectation(value: serde_json::Value) -> Expectation {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn verify() {
+        // 主キー値や日時はワイルドカードで書け、数値の主キー値は文字列でも書ける
+        let act = expectation(json!({
+            "changes": [
+                { "table": "users", "key": "*", "kind": "added", "before": null, "after": { "name": "Bob", "updated_at": "2023-07-02 *" } },
+                { "table": "users", "key": 2, "kind": "modified", "before": { "name": "Jack", "updated_at": "*" }, "after": { "name": "Jill", "updated_at": "*" } },
+                { "table": "users", "key": "1", "kind": "modified", "before": { "name": "John", "updated_at": "*" }, "after": { "name": "John", "updated_at": "*" } },
+            ],
+        }))
+        .verify(&snapshot_diff());
+        assert_eq!(Vec::<Mismatch>::new(), act);
+
+        // 無視する列だけが変わった行は、変更とみなさない
+        let act = expectation(json!({
+            "ignore": ["*.updated_at"],
+            "changes": [
+                { "table": "users", "key": "2", "kind": "modified", "before": { "name": "Jack" }, "after": { "name": "Jim" } },
+                { "table": "users", "key": "3", "kind": "removed", "before": { "name": "Jane" }, "after": null },
+            ],
+        }))
+        .verify(&snapshot_diff());
+        assert_eq!(
+            vec!["different: users 2\n    after.name: expected \"Jim\" but was \"Jill\"", "missing: users 3 removed", "unexpected: users 38 added",],
+            act.iter().map(|mismatch| mismatch.to_string()).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn update() {
+        let snapshot_diff = snapshot_diff();
+
+        let expected = Expectation::create(&snapshot_diff, vec!["*.updated_at".to_string()]);
+        assert_eq!(
+            json!({
+                "ignore": ["*.updated_at"],
+                "changes": [
+                    { "table": "users", "key": 2, "kind": "modified", "before": { "name": "Jack" }, "after": { "name": "Jill" } },
+                    { "table": "users", "key": 38, "kind": "added", "before": null, "after": { "name": "Bob" } },
+                ],
+            }),
+            serde_json::to_value(&expected).unwrap()
+        );
+        assert!(expected.verify(&snapshot_diff).is_empty());
+
+        // 一致する期待値はワイルドカードを残し、一致しない期待値は差分の変更に置き換える
+        let act = expectation(json!({
+            "ignore": ["*.updated_at"],
+            "changes": [
+                { "table": "users", "key": "*", "kind": "added", "before": null, "after": { "name": "Bob" } },
+                { "table": "users", "key": "2", "kind": "modified", "before": { "name": "Jack" }, "after": { "name": "J*m" } },
+            ],
+        }))
+        .update(&snapshot_diff);
+        assert_eq!(
+            json!({
+                "ignore": ["*.updated_at"],
+                "changes": [
+                    { "table": "users", "key": 2, "kind": "modified", "before": { "name": "Jack" }, "after": { "name": "Jill" } },
+                    { "table": "users", "key": "*", "kind": "added", "before": null, "after": { "name": "Bob" } },
+                ],
+            }),
+            serde_json::to_value(&act).unwrap()
+        );
+    }
+
+    #[test]
+    fn escape() {
+        let table_snapshots1 = vec![mk_users(vec![vec![n("1"), s("C:\\tmp"), d("2023-07-01 10:00:00")]])];
+        let table_snapshots2 =
+            vec![mk_users(vec![vec![n("1"), s("a*b"), d("2023-07-01 10:00:00")], vec![s("x*y"), s("*"), d("2023-07-02 10:00:00")]])];
+        let snapshot_diff = create_snapshot_diff(&"s1".to_string(), &"s2".to_string(), &table_snapshots1, &table_snapshots2, &[]);
+
+        // 差分の \ と * はエスケープして書くので、作成した期待値はそのまま一致する
+        let expected = Expectation::create(&snapshot_diff, vec![]);
+        assert_eq!(
+            json!([
+                { "table": "users", "key": 1, "kind": "modified", "before": { "name": "C:\\\\tmp", "updated_at": "2023-07-01 10:00:00" }, "after": { "name": "a\\*b", "updated_at": "2023-07-01 10:00:00" } },
+                { "table": "users", "key": "x\\*y", "kind": "added", "before": null, "after": { "name": "\\*", "updated_at": "2023-07-02 10:00:00" } },
+            ]),
+            serde_json::to_value(&expected.changes).unwrap()
+        );
+        assert!(expected.verify(&snapshot_diff).is_empty());
+
+        // 差分に置き換えた期待値も同じようにエスケープする
+        let act = Expectation { ignore: vec![], changes: vec![] }.update(&snapshot_diff);
+        assert_eq!(expected, act);
+        assert!(act.verify(&snapshot_diff).is_empty());
+    }
+
+    #[test]
+    fn pattern() {
+        assert!(matches_value(&json!("*"), &json!(null)));
+        assert!(matches_value(&json!("2023-07-*"), &json!("2023-07-02 10:00:00")));
+        assert!(matches_value(&json!("*-07-*:00"), &json!("2023-07-02 10:00:00")));
+        assert!(!matches_value(&json!("2023-08-*"), &json!("2023-07-02 10:00:00")));
+        assert!(!matches_value(&json!("ab*ba"), &json!("aba")));
+        assert!(matches_value(&json!(r"\*"), &json!("*")));
+        assert!(!matches_value(&json!(r"\*"), &json!("x")));
+        assert!(!matches_value(&json!("2023-07-*"), &json!(null)));
+        assert!(matches_value(&json!("12"), &json!(12)));
+        assert!(matches_value(&json!("1*"), &json!(12)));
+        assert!(!matches_value(&json!(12), &json!("12")));
+        assert!(!matches_value(&json!("*binary*"), &json!({ "type": "binary", "base64": "YWJj" })));
+    }
+}
@@ -0,0 +1,25 @@
+// テストで使うスナップショットの組み立て
+use crate::domain::snapshot::ColValue::{DateString, SimpleNumber, SimpleString};
+use crate::domain::snapshot::{ColValue, RowSnapshot, TableSnapshot};
+
+pub fn n(s: &str) -> ColValue {
+    SimpleNumber(s.to_string())
+}
+
+pub fn s(s: &str) -> ColValue {
+    SimpleString(s.to_string())
+}
+
+pub fn d(s: &str) -> ColValue {
+    DateString(s.to_string())
+}
+
+// 主キーは id で、各行の先頭を主キー値とする
+pub fn mk_table_snapshot(table_name: &str, col_names: Vec<&str>, rows: Vec<Vec<ColValue>>) -> TableSnapshot {
+    TableSnapshot::new(
+        &table_name.to_string(),
+        "id".to_string(),
+        col_names.into_iter().map(|col_name| col_name.to_string()).collect(),
+        rows.into_iter().map(RowSnapshot::new).collect(),
+    )
+}
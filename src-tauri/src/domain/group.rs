@@ -0,0 +1,369 @@
+use std::cmp::{max, min, Reverse};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+use itertools::Itertools;
+
+use crate::domain::diff::ColDiff::{Added, Deleted, Stay};
+use crate::domain::diff::{ColDiff, RowChange, TableDiff};
+use crate::domain::schema::{ColName, ForeignKey, PrimaryValue, TableName};
+use crate::domain::snapshot::ColValue::Null;
+
+// 外部キーでつながった変更のあった行のまとまりを、ひとつの行を根にした木で表す
+#[derive(Eq, PartialEq, Debug)]
+pub struct GroupedRow {
+    pub table_name: TableName,
+    pub primary_value: PrimaryValue,
+    pub kind: RowChange,
+    pub children: Vec<GroupedRow>,
+}
+
+impl GroupedRow {
+    // 子孫の行の数を、テーブル名の順に数える
+    pub fn count_descendants(&self) -> Vec<(&TableName, usize)> {
+        let mut counts = HashMap::new();
+        let mut rows = self.children.iter().collect_vec();
+        while let Some(row) = rows.pop() {
+            *counts.entry(&row.table_name).or_insert(0) += 1;
+            rows.extend(row.children.iter());
+        }
+        counts.into_iter().sorted().collect()
+    }
+
+    // orders 17 added with 3 order_items, 1 payments
+    pub fn describe(&self) -> String {
+        let head = format!("{} {} {}", self.table_name, self.primary_value, self.kind.as_str());
+        match self.count_descendants() {
+            counts if counts.is_empty() => head,
+            counts => format!("{head} with {}", counts.iter().map(|(table_name, count)| format!("{count} {table_name}")).join(", ")),
+        }
+    }
+}
+
+struct Node<'a> {
+    table_diff: &'a TableDiff,
+    primary_value: PrimaryValue,
+    kind: RowChange,
+}
+
+impl Node<'_> {
+    // 変更前と変更後の両方の値を返すので、参照先を付け替えた行は両方の参照先につながる
+    fn values(&self, col_name: &ColName) -> Vec<String> {
+        // 主キーの変わった行は、変更前の主キー値で参照する行ともつながる
+        if col_name == &self.table_diff.primary_col_name {
+            return [Some(&self.primary_value), self.table_diff.find_old_primary_value(&self.primary_value)].into_iter().flatten().cloned().collect();
+        }
+
+        // 主キーの変わった行の変更前の値は、変更前の主キー値で持っている
+        let old_primary_value = self.table_diff.find_old_primary_value(&self.primary_value).unwrap_or(&self.primary_value);
+        [(&self.table_diff.row_diffs1, old_primary_value), (&self.table_diff.row_diffs2, &self.primary_value)]
+            .into_iter()
+            .filter_map(|(row_diffs, primary_value)| row_diffs.get(primary_value).and_then(|row_diff| row_diff.get(col_name)))
+            .filter_map(|col_diff: &ColDiff| match col_diff {
+                Stay(v) | Added(v) | Deleted(v) if v != &Null => Some(v.as_display_value()),
+                _ => None,
+            })
+            .unique()
+            .collect()
+    }
+}
+
+// 外部キーでつながった変更のあった行をひとつのまとまりにして、まとまりごとに木を作る
+// 根には、変更のあった行を参照していない行のうち、参照元をたどって届く行が最も多いものを選ぶ
+// 根から近い順に行をたどり、参照先の行も参照元の行と同じく子として置く
+// まとまりは根のテーブル名と主キー値の順に並べる
+pub fn group_changed_rows(table_diffs: &[TableDiff], foreign_keys: &[(TableName, ForeignKey)]) -> Vec<GroupedRow> {
+    let nodes = table_diffs
+        .iter()
+        .sorted_by(|table_diff1, table_diff2| table_diff1.table_name.cmp(&table_diff2.table_name))
+        .flat_map(|table_diff| table_diff.changed_rows().map(move |(primary_value, kind)| Node { table_diff, primary_value, kind }))
+        .collect_vec();
+
+    // referrers は参照元の行、neighbors は参照の向きを問わずにつながった行
+    let mut referrers: Vec<Vec<usize>> = vec![vec![]; nodes.len()];
+    let mut neighbors: Vec<Vec<usize>> = vec![vec![]; nodes.len()];
+    let mut references = vec![false; nodes.len()];
+
+    for (table_name, foreign_key) in foreign_keys {
+        let mut referenced: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, node) in nodes.iter().enumerate().filter(|(_, node)| node.table_diff.table_name == foreign_key.referenced_table_name) {
+            for value in node.values(&foreign_key.referenced_col_name) {
+                referenced.entry(value).or_default().push(i);
+            }
+        }
+
+        for (i, node) in nodes.iter().enumerate().filter(|(_, node)| &node.table_diff.table_name == table_name) {
+            for value in node.values(&foreign_key.col_name) {
+                for &j in referenced.get(&value).into_iter().flatten().filter(|&&j| j != i) {
+                    referrers[j].push(i);
+                    references[i] = true;
+                    neighbors[i].push(j);
+                    neighbors[j].push(i);
+                }
+            }
+        }
+    }
+    let neighbors = neighbors.into_iter().map(|js| js.into_iter().sorted().dedup().collect_vec()).collect_vec();
+
+    // つながった行のまとまりを、辺を一度ずつ見て union-find で求める
+    let mut parents = (0..nodes.len()).collect_vec();
+    for (i, js) in neighbors.iter().enumerate() {
+        for &j in js {
+            let (root_i, root_j) = (find_root(&mut parents, i), find_root(&mut parents, j));
+            parents[max(root_i, root_j)] = min(root_i, root_j);
+        }
+    }
+    let mut components: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for i in 0..nodes.len() {
+        components.entry(find_root(&mut parents, i)).or_default().push(i);
+    }
+
+    let groups = components.into_values().map(|component| {
+        let reachable = count_reachable(&referrers, &component);
+        // 参照が循環していて参照していない行がなければ、最初の行を根にする
+        component
+            .iter()
+            .enumerate()
+            .filter(|(_, &j)| !references[j])
+            .max_by_key(|&(k, &j)| (reachable[k], Reverse(j)))
+            .map_or(component[0], |(_, &j)| j)
+    });
+
+    groups.sorted().map(|root| build_tree(&nodes, &neighbors, root)).collect()
+}
+
+// 根は常にまとまりの中で最も小さい番号の行になる
+fn find_root(parents: &mut [usize], i: usize) -> usize {
+    let mut root = i;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    let mut j = i;
+    while parents[j] != root {
+        (parents[j], j) = (root, parents[j]);
+    }
+    root
+}
+
+// まとまりの各行からたどり着ける行の数を、まとまりの並びで返す
+// 循環した参照はひとつにまとめ、たどった先の側から順にたどり着ける行の集合を足し合わせるので、行ごとにたどり直さない
+fn count_reachable(edges: &[Vec<usize>], component: &[usize]) -> Vec<usize> {
+    let positions: HashMap<usize, usize> = component.iter().enumerate().map(|(k, &i)| (i, k)).collect();
+    let edges = component.iter().map(|&i| edges[i].iter().map(|j| positions[j]).collect_vec()).collect_vec();
+    let len = component.len();
+
+    // 帰りがけ順に並べる
+    let mut order = vec![];
+    let mut visited = vec![false; len];
+    for start in 0..len {
+        if visited[start] {
+            continue;
+        }
+        visited[start] = true;
+        let mut stack = vec![(start, 0)];
+        while let Some((i, next)) = stack.pop() {
+            match edges[i].get(next) {
+                Some(&j) => {
+                    stack.push((i, next + 1));
+                    if !visited[j] {
+                        visited[j] = true;
+                        stack.push((j, 0));
+                    }
+                }
+                None => order.push(i),
+            }
+        }
+    }
+
+    // 逆向きの辺を帰りがけ順の後ろからたどり、互いにたどり着ける行をまとめる
+    // まとめたものは、たどる元の側から順に番号が振られる
+    let mut reversed = vec![vec![]; len];
+    for (i, js) in edges.iter().enumerate() {
+        for &j in js {
+            reversed[j].push(i);
+        }
+    }
+    let mut cycles = vec![usize::MAX; len];
+    let mut cycle_count = 0;
+    for &start in order.iter().rev() {
+        if cycles[start] != usize::MAX {
+            continue;
+        }
+        cycles[start] = cycle_count;
+        let mut stack = vec![start];
+        while let Some(i) = stack.pop() {
+            for &j in &reversed[i] {
+                if cycles[j] == usize::MAX {
+                    cycles[j] = cycle_count;
+                    stack.push(j);
+                }
+            }
+        }
+        cycle_count += 1;
+    }
+
+    // たどり着ける行の集合をビット列で持ち、たどった先の側から足し合わせる
+    let mut members = vec![vec![]; cycle_count];
+    for i in 0..len {
+        members[cycles[i]].push(i);
+    }
+    let mut reachable = vec![vec![0u64; len.div_ceil(64)]; cycle_count];
+    for c in (0..cycle_count).rev() {
+        let (current, rest) = reachable[c..].split_first_mut().unwrap();
+        for &i in &members[c] {
+            current[i / 64] |= 1 << (i % 64);
+            for &j in edges[i].iter().filter(|&&j| cycles[j] != c) {
+                for (word, other) in current.iter_mut().zip(&rest[cycles[j] - c - 1]) {
+                    *word |= other;
+                }
+            }
+        }
+    }
+
+    (0..len).map(|i| reachable[cycles[i]].iter().map(|word| word.count_ones() as usize).sum()).collect()
+}
+
+fn build_tree(nodes: &[Node], neighbors: &[Vec<usize>], root: usize) -> GroupedRow {
+    // 根から近い順にたどり、最初にたどり着いた行の子にする
+    let mut children: HashMap<usize, Vec<usize>> = HashMap::new();
+    let mut visited = HashSet::from([root]);
+    let mut queue = VecDeque::from([root]);
+    while let Some(i) = queue.pop_front() {
+        for &j in &neighbors[i] {
+            if visited.insert(j) {
+                children.entry(i).or_default().push(j);
+                queue.push_back(j);
+            }
+        }
+    }
+
+    fn build(nodes: &[Node], children: &HashMap<usize, Vec<usize>>, i: usize) -> GroupedRow {
+        GroupedRow {
+            table_name: nodes[i].table_diff.table_name.clone(),
+            primary_value: nodes[i].primary_value.clone(),
+            kind: nodes[i].kind,
+            children: children.get(&i).into_iter().flatten().map(|&j| build(nodes, children, j)).collect(),
+        }
+    }
+
+    build(nodes, &children, root)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::diff::{create_table_diff, RowChange};
+    use crate::domain::fixture::{mk_table_snapshot, n, s};
+    use crate::domain::group::{count_reachable, group_changed_rows, GroupedRow};
+    use crate::domain::schema::{ForeignKey, TableName};
+    use crate::domain::snapshot::ColValue::Null;
+
+    fn mk_foreign_key(table_name: &str, col_name: &str, referenced_table_name: &str) -> (TableName, ForeignKey) {
+        (
+            table_name.to_string(),
+            ForeignKey {
+                col_name: col_name.to_string(),
+                referenced_table_name: referenced_table_name.to_string(),
+                referenced_col_name: "id".to_string(),
+            },
+        )
+    }
+
+    fn tree(row: &GroupedRow) -> String {
+        match row.children.is_empty() {
+            true => format!("{}:{}", row.table_name, row.primary_value),
+            false => format!("{}:{}({})", row.table_name, row.primary_value, row.children.iter().map(tree).collect::<Vec<_>>().join(" ")),
+        }
+    }
+
+    #[test]
+    fn group() {
+        let orders1 = mk_table_snapshot("orders", vec!["status"], vec![vec![n("16"), s("paid")]]);
+        let orders2 = mk_table_snapshot("orders", vec!["status"], vec![vec![n("16"), s("shipped")], vec![n("17"), s("new")]]);
+        let order_items1 = mk_table_snapshot("order_items", vec!["order_id", "item_id"], vec![]);
+        let order_items2 = mk_table_snapshot(
+            "order_items",
+            vec!["order_id", "item_id"],
+            vec![vec![n("1"), n("17"), n("100")], vec![n("2"), n("17"), n("101")], vec![n("3"), n("17"), n("100")]],
+        );
+        let payments1 = mk_table_snapshot("payments", vec!["order_id"], vec![vec![n("5"), n("16")]]);
+        let payments2 = mk_table_snapshot("payments", vec!["order_id"], vec![vec![n("5"), n("16")], vec![n("6"), n("17")], vec![n("7"), Null]]);
+        let items1 = mk_table_snapshot("items", vec!["stock"], vec![vec![n("100"), n("10")], vec![n("101"), n("5")]]);
+        let items2 = mk_table_snapshot("items", vec!["stock"], vec![vec![n("100"), n("8")], vec![n("101"), n("4")]]);
+
+        let table_diffs = vec![
+            create_table_diff(Some(&orders1), Some(&orders2)),
+            create_table_diff(Some(&order_items1), Some(&order_items2)),
+            create_table_diff(Some(&payments1), Some(&payments2)),
+            create_table_diff(Some(&items1), Some(&items2)),
+        ];
+        let foreign_keys = vec![
+            mk_foreign_key("order_items", "order_id", "orders"),
+            mk_foreign_key("order_items", "item_id", "items"),
+            mk_foreign_key("payments", "order_id", "orders"),
+        ];
+
+        let act = group_changed_rows(&table_diffs, &foreign_keys);
+
+        // 変わっていない行や null の参照は、つながりに含めない
+        // 参照先の items も、参照元の order_items の子としてまとまりに含める
+        assert_eq!(
+            vec!["orders:16", "orders:17(order_items:1(items:100) order_items:2(items:101) order_items:3 payments:6)", "payments:7"],
+            act.iter().map(tree).collect::<Vec<_>>()
+        );
+
+        assert_eq!(RowChange::Added, act[1].kind);
+        assert_eq!("orders 17 added with 2 items, 3 order_items, 1 payments", act[1].describe());
+        assert_eq!("orders 16 modified", act[0].describe());
+    }
+
+    #[test]
+    fn self_reference() {
+        let categories = mk_table_snapshot("categories", vec!["parent_id"], vec![vec![n("1"), n("2")], vec![n("2"), n("1")], vec![n("3"), n("3")]]);
+
+        let table_diffs = vec![create_table_diff(None, Some(&categories))];
+        let foreign_keys = vec![mk_foreign_key("categories", "parent_id", "categories")];
+
+        // 参照が循環している場合は最初の行を根にし、自身への参照は無視する
+        let act = group_changed_rows(&table_diffs, &foreign_keys);
+        assert_eq!(vec!["categories:1(categories:2)", "categories:3"], act.iter().map(tree).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn rekeyed() {
+        let users1 = mk_table_snapshot("users", vec!["name", "email"], vec![vec![n("1"), s("John"), s("john@example.com")]]);
+        let users2 = mk_table_snapshot("users", vec!["name", "email"], vec![vec![n("10"), s("John"), s("john@example.com")]]);
+        let posts1 = mk_table_snapshot("posts", vec!["user_id", "title"], vec![vec![n("1"), n("1"), s("hello")]]);
+        let posts2 = mk_table_snapshot("posts", vec!["user_id", "title"], vec![vec![n("1"), n("1"), s("hi")]]);
+
+        let mut users_diff = create_table_diff(Some(&users1), Some(&users2));
+        users_diff.detect_rekeys(0);
+        let table_diffs = vec![users_diff, create_table_diff(Some(&posts1), Some(&posts2))];
+        let foreign_keys = vec![mk_foreign_key("posts", "user_id", "users")];
+
+        // 主キーの変わった行は変更後の主キー値で表し、変更前の主キー値で参照する行ともつながる
+        let act = group_changed_rows(&table_diffs, &foreign_keys);
+        assert_eq!(vec!["users:10(posts:1)"], act.iter().map(tree).collect::<Vec<_>>());
+        assert_eq!(RowChange::Rekeyed, act[0].kind);
+
+        // 主キーの変わった行の変更前の参照先は、変更前の主キー値の行から読む
+        let users1 = mk_table_snapshot("users", vec!["name"], vec![vec![n("5"), s("John")], vec![n("6"), s("Jane")]]);
+        let users2 = mk_table_snapshot("users", vec!["name"], vec![vec![n("5"), s("Johnny")], vec![n("6"), s("Janet")]]);
+        let posts1 = mk_table_snapshot("posts", vec!["user_id", "title"], vec![vec![n("1"), n("5"), s("hello")]]);
+        let posts2 = mk_table_snapshot("posts", vec!["user_id", "title"], vec![vec![n("10"), n("6"), s("hello")]]);
+
+        let mut posts_diff = create_table_diff(Some(&posts1), Some(&posts2));
+        posts_diff.detect_rekeys(1);
+        let table_diffs = vec![create_table_diff(Some(&users1), Some(&users2)), posts_diff];
+
+        let act = group_changed_rows(&table_diffs, &foreign_keys);
+        assert_eq!(vec!["users:5(posts:10(users:6))"], act.iter().map(tree).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn reachable() {
+        // 1 と 2 は循環していて、0 からは 3 を経由しても 2 にたどり着く
+        let edges = vec![vec![1, 3], vec![2], vec![1], vec![2], vec![]];
+
+        assert_eq!(vec![4, 2, 2, 3], count_reachable(&edges, &[0, 1, 2, 3]));
+        assert_eq!(vec![1], count_reachable(&edges, &[4]));
+    }
+}
@@ -0,0 +1,310 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use itertools::Itertools;
+
+use crate::domain::schema::{ColName, PrimaryValue, TableName};
+use crate::domain::snapshot::ColValue::{BinaryString, Null, SimpleNumber};
+use crate::domain::snapshot::{ColValue, SnapshotSummary, TableSnapshot};
+
+// スナップショットを作成順に追加していき、ひとつの行が変わったスナップショットだけを記録する
+#[derive(Eq, PartialEq, Debug)]
+pub struct RowHistory {
+    pub table_name: TableName,
+    pub primary_value: PrimaryValue,
+    pub entries: Vec<RowHistoryEntry>,
+    last_cols: Option<Vec<(ColName, ColValue)>>,
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub struct RowHistoryEntry {
+    pub snapshot_summary: SnapshotSummary,
+    pub kind: RowHistoryKind,
+    // appeared では全列、changed では変わった列だけ、disappeared では空になる
+    pub cols: Vec<ColChange>,
+}
+
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub enum RowHistoryKind {
+    Appeared,
+    Changed,
+    Disappeared,
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub struct ColChange {
+    pub col_name: ColName,
+    pub old_value: Option<ColValue>,
+    pub new_value: Option<ColValue>,
+}
+
+impl RowHistoryKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RowHistoryKind::Appeared => "appeared",
+            RowHistoryKind::Changed => "changed",
+            RowHistoryKind::Disappeared => "disappeared",
+        }
+    }
+}
+
+impl RowHistory {
+    pub fn new(table_name: &TableName, primary_value: &PrimaryValue) -> Self {
+        Self { table_name: table_name.clone(), primary_value: primary_value.clone(), entries: vec![], last_cols: None }
+    }
+
+    // テーブルがないスナップショットでは、行もないものとして扱う
+    pub fn push(&mut self, snapshot_summary: SnapshotSummary, table_snapshot: Option<&TableSnapshot>) {
+        let cols = table_snapshot.and_then(|table_snapshot| find_row_cols(table_snapshot, &self.primary_value));
+
+        let entry = match (&self.last_cols, &cols) {
+            (None, None) => None,
+            (None, Some(cols)) => Some((
+                RowHistoryKind::Appeared,
+                cols.iter()
+                    .map(|(col_name, col_value)| ColChange { col_name: col_name.clone(), old_value: None, new_value: Some(col_value.clone()) })
+                    .collect_vec(),
+            )),
+            (Some(_), None) => Some((RowHistoryKind::Disappeared, vec![])),
+            (Some(last_cols), Some(cols)) => {
+                let col_changes = diff_cols(last_cols, cols);
+                (!col_changes.is_empty()).then_some((RowHistoryKind::Changed, col_changes))
+            }
+        };
+
+        if let Some((kind, cols)) = entry {
+            self.entries.push(RowHistoryEntry { snapshot_summary, kind, cols });
+        }
+        self.last_cols = cols;
+    }
+}
+
+// 主キー値は表示用の値でも、引用符を外した文字列でも指定できる
+// 指定された値を行の主キーと同じ種類の値に戻して比べるので、バイナリや NULL の主キーの行は一致しない
+fn find_row_cols(table_snapshot: &TableSnapshot, primary_value: &PrimaryValue) -> Option<Vec<(ColName, ColValue)>> {
+    let quoted = format!(r#""{primary_value}""#);
+
+    table_snapshot
+        .row_snapshots
+        .iter()
+        .find(|row_snapshot| {
+            let primary_col_value = &row_snapshot.primary_col_value;
+            [primary_value, &quoted].into_iter().any(|value| primary_col_value.parse_primary_value(value).as_ref() == Some(primary_col_value))
+        })
+        .map(|row_snapshot| table_snapshot.col_names.iter().cloned().zip(row_snapshot.col_values.iter().cloned()).collect())
+}
+
+// 列の追加や削除も、片方の値がない変更として記録する
+fn diff_cols(cols1: &[(ColName, ColValue)], cols2: &[(ColName, ColValue)]) -> Vec<ColChange> {
+    let find = |cols: &[(ColName, ColValue)], col_name: &ColName| cols.iter().find(|(name, _)| name == col_name).map(|(_, value)| value.clone());
+
+    cols1
+        .iter()
+        .chain(cols2)
+        .map(|(col_name, _)| col_name)
+        .unique()
+        .filter_map(|col_name| {
+            let old_value = find(cols1, col_name);
+            let new_value = find(cols2, col_name);
+            (old_value != new_value).then(|| ColChange { col_name: col_name.clone(), old_value, new_value })
+        })
+        .collect()
+}
+
+// スナップショットごとの列の統計を、グラフにできるよう作成順に並べる
+#[derive(Eq, PartialEq, Debug)]
+pub struct ColStatsSeries {
+    pub table_name: TableName,
+    pub col_name: ColName,
+    pub points: Vec<ColStatsPoint>,
+}
+
+// テーブルか列がないスナップショットでは stats が None になる
+#[derive(Eq, PartialEq, Debug)]
+pub struct ColStatsPoint {
+    pub snapshot_summary: SnapshotSummary,
+    pub stats: Option<ColStats>,
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub struct ColStats {
+    pub row_count: usize,
+    pub null_count: usize,
+    pub distinct_count: usize,
+    // null とバイナリ値は比べない
+    pub min: Option<ColValue>,
+    pub max: Option<ColValue>,
+    // 行数の多い順に top 件まで
+    pub value_counts: Vec<(ColValue, usize)>,
+}
+
+impl ColStatsSeries {
+    pub fn new(table_name: &TableName, col_name: &ColName) -> Self {
+        Self { table_name: table_name.clone(), col_name: col_name.clone(), points: vec![] }
+    }
+
+    pub fn push(&mut self, snapshot_summary: SnapshotSummary, table_snapshot: Option<&TableSnapshot>, top: usize) {
+        let stats = table_snapshot.and_then(|table_snapshot| create_col_stats(table_snapshot, &self.col_name, top));
+        self.points.push(ColStatsPoint { snapshot_summary, stats });
+    }
+}
+
+// 主キーの列も対象にできる
+fn create_col_stats(table_snapshot: &TableSnapshot, col_name: &ColName, top: usize) -> Option<ColStats> {
+    let col_values = if &table_snapshot.primary_col_name == col_name {
+        table_snapshot.row_snapshots.iter().map(|row_snapshot| &row_snapshot.primary_col_value).collect_vec()
+    } else {
+        let i = table_snapshot.col_names.iter().position(|name| name == col_name)?;
+        table_snapshot.row_snapshots.iter().map(|row_snapshot| &row_snapshot.col_values[i]).collect_vec()
+    };
+
+    let mut counts: HashMap<&ColValue, usize> = HashMap::new();
+    for &col_value in &col_values {
+        *counts.entry(col_value).or_default() += 1;
+    }
+
+    let comparables = col_values.iter().filter(|col_value| !matches!(col_value, Null | BinaryString(_))).collect_vec();
+
+    Some(ColStats {
+        row_count: col_values.len(),
+        null_count: counts.get(&Null).copied().unwrap_or_default(),
+        distinct_count: counts.len(),
+        min: comparables.iter().min_by(|a, b| compare_col_values(a, b)).map(|&&col_value| col_value.clone()),
+        max: comparables.iter().max_by(|a, b| compare_col_values(a, b)).map(|&&col_value| col_value.clone()),
+        value_counts: counts
+            .into_iter()
+            .sorted_by(|(value1, count1), (value2, count2)| count2.cmp(count1).then_with(|| compare_col_values(value1, value2)))
+            .take(top)
+            .map(|(col_value, count)| (col_value.clone(), count))
+            .collect(),
+    })
+}
+
+// 数値は文字列ではなく数値として比べる
+fn compare_col_values(col_value1: &ColValue, col_value2: &ColValue) -> Ordering {
+    match (col_value1, col_value2) {
+        (SimpleNumber(v1), SimpleNumber(v2)) => match (v1.parse::<f64>(), v2.parse::<f64>()) {
+            (Ok(n1), Ok(n2)) => n1.partial_cmp(&n2).unwrap_or(Ordering::Equal).then_with(|| v1.cmp(v2)),
+            _ => v1.cmp(v2),
+        },
+        _ => col_value1.cmp(col_value2),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::fixture::{mk_table_snapshot, n, s};
+    use crate::domain::history::{ColChange, ColStats, ColStatsSeries, RowHistory, RowHistoryKind};
+    use crate::domain::snapshot::ColValue::{BinaryString, Null};
+    use crate::domain::snapshot::{ColValue, SnapshotSummary, TableSnapshot};
+
+    fn mk_summary(snapshot_id: &str) -> SnapshotSummary {
+        SnapshotSummary::new(&snapshot_id.to_string(), snapshot_id, "2023-07-03 08:17:52")
+    }
+
+    fn mk_users(col_names: Vec<&str>, rows: Vec<Vec<ColValue>>) -> TableSnapshot {
+        mk_table_snapshot("users", col_names, rows)
+    }
+
+    #[test]
+    fn history() {
+        let mut act = RowHistory::new(&"users".to_string(), &"42".to_string());
+
+        act.push(mk_summary("s1"), Some(&mk_users(vec!["name", "age"], vec![vec![n("1"), s("John"), n("29")]])));
+        act.push(mk_summary("s2"), Some(&mk_users(vec!["name", "age"], vec![vec![n("42"), s("Jane"), n("29")]])));
+        // 変わっていないスナップショットは記録しない
+        act.push(mk_summary("s3"), Some(&mk_users(vec!["name", "age"], vec![vec![n("1"), s("John"), n("29")], vec![n("42"), s("Jane"), n("29")]])));
+        act.push(mk_summary("s4"), Some(&mk_users(vec!["name", "age"], vec![vec![n("42"), s("Jane"), n("30")]])));
+        act.push(mk_summary("s5"), Some(&mk_users(vec!["name", "age", "email"], vec![vec![n("42"), s("Jane"), n("30"), s("jane@example.com")]])));
+        act.push(mk_summary("s6"), None);
+        act.push(mk_summary("s7"), Some(&mk_users(vec!["name"], vec![vec![n("42"), s("Jane")]])));
+
+        assert_eq!(
+            vec![
+                ("s2", RowHistoryKind::Appeared, vec![("name", None, Some(s("Jane"))), ("age", None, Some(n("29")))]),
+                ("s4", RowHistoryKind::Changed, vec![("age", Some(n("29")), Some(n("30")))]),
+                ("s5", RowHistoryKind::Changed, vec![("email", None, Some(s("jane@example.com")))]),
+                ("s6", RowHistoryKind::Disappeared, vec![]),
+                ("s7", RowHistoryKind::Appeared, vec![("name", None, Some(s("Jane")))]),
+            ],
+            act.entries
+                .iter()
+                .map(|entry| (
+                    entry.snapshot_summary.snapshot_id.as_str(),
+                    entry.kind,
+                    entry
+                        .cols
+                        .iter()
+                        .map(|ColChange { col_name, old_value, new_value }| (col_name.as_str(), old_value.clone(), new_value.clone()))
+                        .collect::<Vec<_>>()
+                ))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn string_key() {
+        let mut act = RowHistory::new(&"users".to_string(), &"abc".to_string());
+
+        act.push(mk_summary("s1"), Some(&mk_users(vec!["name"], vec![vec![s("abc"), s("John")]])));
+
+        assert_eq!(1, act.entries.len());
+        assert_eq!(RowHistoryKind::Appeared, act.entries[0].kind);
+    }
+
+    #[test]
+    fn unsupported_key() {
+        let mut act = RowHistory::new(&"users".to_string(), &"binary".to_string());
+
+        // 主キー値にできない値の行があっても、一致しないものとして扱う
+        act.push(mk_summary("s1"), Some(&mk_users(vec!["name"], vec![vec![BinaryString("abc".to_string()), s("John")], vec![Null, s("Jane")]])));
+
+        assert!(act.entries.is_empty());
+    }
+
+    #[test]
+    fn col_stats() {
+        let mut act = ColStatsSeries::new(&"users".to_string(), &"status".to_string());
+
+        let users = mk_users(
+            vec!["status", "balance"],
+            vec![
+                vec![n("1"), s("pending"), n("9")],
+                vec![n("2"), s("done"), n("10")],
+                vec![n("3"), s("pending"), n("-1.5")],
+                vec![n("4"), Null, Null],
+            ],
+        );
+        act.push(mk_summary("s1"), Some(&users), 2);
+        act.push(mk_summary("s2"), Some(&mk_users(vec!["balance"], vec![vec![n("1"), n("0")]])), 2);
+        act.push(mk_summary("s3"), None, 2);
+
+        assert_eq!(
+            vec![
+                Some(ColStats {
+                    row_count: 4,
+                    null_count: 1,
+                    distinct_count: 3,
+                    min: Some(s("done")),
+                    max: Some(s("pending")),
+                    value_counts: vec![(s("pending"), 2), (s("done"), 1)],
+                }),
+                None,
+                None,
+            ],
+            act.points.into_iter().map(|point| point.stats).collect::<Vec<_>>()
+        );
+
+        // 数値は数値として比べる
+        let mut act = ColStatsSeries::new(&"users".to_string(), &"balance".to_string());
+        act.push(mk_summary("s1"), Some(&users), 10);
+        let stats = act.points[0].stats.as_ref().unwrap();
+        assert_eq!((Some(&n("-1.5")), Some(&n("10"))), (stats.min.as_ref(), stats.max.as_ref()));
+
+        // 主キーの列
+        let mut act = ColStatsSeries::new(&"users".to_string(), &"id".to_string());
+        act.push(mk_summary("s1"), Some(&users), 0);
+        let stats = act.points[0].stats.as_ref().unwrap();
+        assert_eq!((4, 4, Some(&n("1")), Some(&n("4"))), (stats.row_count, stats.distinct_count, stats.min.as_ref(), stats.max.as_ref()));
+    }
+}
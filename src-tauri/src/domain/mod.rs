@@ -1,4 +1,13 @@
+pub mod archive;
+pub mod diagnosis;
 pub mod diff;
+pub mod expectation;
+#[cfg(test)]
+pub mod fixture;
+pub mod group;
+pub mod history;
 pub mod project;
+pub mod report;
+pub mod schedule;
 pub mod schema;
 pub mod snapshot;
@@ -0,0 +1,475 @@
+use itertools::Itertools;
+
+use crate::domain::diff::ColDiff::{Added, Deleted, Stay};
+use crate::domain::diff::{RowChange, SnapshotDiff, TableDiff};
+use crate::domain::schema::{ColName, TableName};
+use crate::domain::snapshot::SnapshotName;
+
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub enum ReportFormat {
+    Markdown,
+    Html,
+}
+
+impl ReportFormat {
+    pub fn render(
+        &self,
+        snapshot_diff: &SnapshotDiff,
+        snapshot_name1: &SnapshotName,
+        snapshot_name2: &SnapshotName,
+        options: &ReportOptions,
+    ) -> String {
+        match self {
+            ReportFormat::Markdown => render_markdown(snapshot_diff, snapshot_name1, snapshot_name2, options),
+            ReportFormat::Html => render_html(snapshot_diff, snapshot_name1, snapshot_name2, options),
+        }
+    }
+
+    // 出力先にディレクトリを指定したときのファイル名に使う
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ReportFormat::Markdown => "md",
+            ReportFormat::Html => "html",
+        }
+    }
+}
+
+#[derive(Eq, PartialEq, Clone, Default, Debug)]
+pub struct ReportOptions {
+    pub ignore_rules: Vec<IgnoreRule>,
+    // テーブルごとに出力する行数の上限で、超えた行は件数だけを出力する
+    pub max_rows_per_table: Option<usize>,
+}
+
+impl ReportOptions {
+    fn ignores_table(&self, table_name: &TableName) -> bool {
+        self.ignore_rules.iter().any(|rule| rule.ignores_table(table_name))
+    }
+
+    fn ignores_col(&self, table_name: &TableName, col_name: &ColName) -> bool {
+        self.ignore_rules.iter().any(|rule| rule.ignores_col(table_name, col_name))
+    }
+}
+
+// table_name が * の場合はすべてのテーブルに、col_name がない場合はテーブル全体に当てはまる
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct IgnoreRule {
+    pub table_name: TableName,
+    pub col_name: Option<ColName>,
+}
+
+impl IgnoreRule {
+    // orders, orders.updated_at, *.updated_at の形式で書く
+    pub fn parse(rule: &str) -> Self {
+        match rule.split_once('.') {
+            Some((table_name, col_name)) => Self { table_name: table_name.to_string(), col_name: Some(col_name.to_string()) },
+            None => Self { table_name: rule.to_string(), col_name: None },
+        }
+    }
+
+    pub fn ignores_table(&self, table_name: &TableName) -> bool {
+        self.col_name.is_none() && self.matches_table(table_name)
+    }
+
+    pub fn ignores_col(&self, table_name: &TableName, col_name: &ColName) -> bool {
+        self.col_name.as_ref() == Some(col_name) && self.matches_table(table_name)
+    }
+
+    fn matches_table(&self, table_name: &TableName) -> bool {
+        self.table_name == "*" || &self.table_name == table_name
+    }
+}
+
+struct ReportTable<'a> {
+    table_diff: &'a TableDiff,
+    col_names: Vec<&'a ColName>,
+    rows: Vec<ReportRow>,
+    // 出力する行の数で、残りの行は件数だけを出力する
+    shown: usize,
+}
+
+impl ReportTable<'_> {
+    fn label(&self) -> String {
+        match &self.table_diff.renamed_from {
+            Some(old_name) => format!("{old_name} → {}", self.table_diff.table_name),
+            None => self.table_diff.table_name.clone(),
+        }
+    }
+
+    fn count(&self) -> String {
+        [RowChange::Added, RowChange::Removed, RowChange::Modified, RowChange::Rekeyed]
+            .iter()
+            .map(|change| (change, self.rows.iter().filter(|row| &row.change == change).count()))
+            .filter(|(_, count)| *count > 0)
+            .map(|(change, count)| format!("{count} {}", change.as_str()))
+            .join(", ")
+    }
+
+    fn shown_rows(&self) -> impl Iterator<Item = &ReportRow> {
+        self.rows.iter().take(self.shown)
+    }
+
+    fn omitted(&self) -> usize {
+        self.rows.len() - self.shown
+    }
+}
+
+struct ReportRow {
+    primary_value: String,
+    change: RowChange,
+    cells: Vec<ReportCell>,
+}
+
+struct ReportCell {
+    old_value: Option<String>,
+    new_value: Option<String>,
+}
+
+impl ReportCell {
+    fn is_changed(&self) -> bool {
+        self.old_value != self.new_value
+    }
+}
+
+// 無視する列だけが変わった行や、変更のある行がなくなったテーブルは含めない
+fn create_report_tables<'a>(snapshot_diff: &'a SnapshotDiff, options: &ReportOptions) -> Vec<ReportTable<'a>> {
+    snapshot_diff
+        .table_diffs
+        .iter()
+        .filter(|table_diff| !options.ignores_table(&table_diff.table_name))
+        .map(|table_diff| {
+            let col_names = table_diff.col_names.iter().filter(|&col_name| !options.ignores_col(&table_diff.table_name, col_name)).collect_vec();
+
+            let rows = table_diff
+                .changed_rows()
+                .map(|(primary_value, change)| create_report_row(table_diff, &col_names, primary_value, change))
+                .filter(|row| row.change != RowChange::Modified || row.cells.iter().any(ReportCell::is_changed))
+                .collect_vec();
+            let shown = options.max_rows_per_table.unwrap_or(rows.len()).min(rows.len());
+
+            ReportTable { table_diff, col_names, rows, shown }
+        })
+        .filter(|report_table| !report_table.rows.is_empty())
+        .collect()
+}
+
+fn create_report_row(table_diff: &TableDiff, col_names: &[&ColName], primary_value: String, change: RowChange) -> ReportRow {
+    let old_primary_value = table_diff.find_old_primary_value(&primary_value);
+
+    let row_diff1 = table_diff.row_diffs1.get(old_primary_value.unwrap_or(&primary_value));
+    let row_diff2 = table_diff.row_diffs2.get(&primary_value);
+
+    let cells = col_names
+        .iter()
+        .map(|&col_name| ReportCell {
+            old_value: match row_diff1.and_then(|row_diff| row_diff.get(col_name)) {
+                Some(Stay(v) | Deleted(v)) => Some(v.as_display_value()),
+                _ => None,
+            },
+            new_value: match row_diff2.and_then(|row_diff| row_diff.get(col_name)) {
+                Some(Stay(v) | Added(v)) => Some(v.as_display_value()),
+                _ => None,
+            },
+        })
+        .collect();
+
+    let primary_value = match old_primary_value {
+        Some(old_primary_value) => format!("{old_primary_value} → {primary_value}"),
+        None => primary_value,
+    };
+
+    ReportRow { primary_value, change, cells }
+}
+
+// GitHub Flavored Markdown の表で、変更されたセルは変更前を打ち消し線、変更後を太字にする
+pub fn render_markdown(
+    snapshot_diff: &SnapshotDiff,
+    snapshot_name1: &SnapshotName,
+    snapshot_name2: &SnapshotName,
+    options: &ReportOptions,
+) -> String {
+    let mut lines = vec![
+        "# table-snapshot diff".to_string(),
+        "".to_string(),
+        format!("{} → {}", escape_markdown(snapshot_name1), escape_markdown(snapshot_name2)),
+    ];
+
+    let (report_tables, omitted_tables) = split_shown_tables(create_report_tables(snapshot_diff, options));
+    if report_tables.is_empty() && omitted_tables == 0 {
+        lines.extend(["".to_string(), "No changes.".to_string()]);
+    }
+
+    for report_table in report_tables {
+        lines.extend([
+            "".to_string(),
+            format!("## {}", escape_markdown(&report_table.label())),
+            "".to_string(),
+            report_table.count(),
+            "".to_string(),
+        ]);
+
+        let header =
+            ["", &report_table.table_diff.primary_col_name].into_iter().chain(report_table.col_names.iter().map(|col_name| col_name.as_str()));
+        lines.push(format!("| {} |", header.clone().map(escape_markdown).join(" | ")));
+        lines.push(format!("| {} |", header.map(|_| "---").join(" | ")));
+
+        for row in report_table.shown_rows() {
+            let cells = row.cells.iter().map(|cell| match (&cell.old_value, &cell.new_value) {
+                (old_value, new_value) if !cell.is_changed() || matches!(row.change, RowChange::Added | RowChange::Removed) => {
+                    escape_markdown(new_value.as_ref().or(old_value.as_ref()).map_or("", String::as_str))
+                }
+                (old_value, new_value) => [
+                    old_value.as_ref().map(|v| format!("~~{}~~", escape_markdown(v))),
+                    new_value.as_ref().map(|v| format!("**{}**", escape_markdown(v))),
+                ]
+                .into_iter()
+                .flatten()
+                .join(" → "),
+            });
+            let row_markdown = [row.change.as_str().to_string(), escape_markdown(&row.primary_value)].into_iter().chain(cells).join(" | ");
+            lines.push(format!("| {row_markdown} |"));
+        }
+
+        if report_table.omitted() > 0 {
+            lines.extend(["".to_string(), format!("_{} more rows omitted_", report_table.omitted())]);
+        }
+    }
+
+    if omitted_tables > 0 {
+        lines.extend(["".to_string(), format!("_{omitted_tables} more tables omitted_")]);
+    }
+
+    lines.push("".to_string());
+    lines.join("\n")
+}
+
+// 値がそのまま表示されるように、インラインの記法に使う文字をすべてエスケープする
+fn escape_markdown(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            '\\' | '`' | '*' | '_' | '~' | '|' | '[' | ']' => format!("\\{c}"),
+            '<' => "&lt;".to_string(),
+            '>' => "&gt;".to_string(),
+            '&' => "&amp;".to_string(),
+            '\n' => "<br>".to_string(),
+            _ => c.to_string(),
+        })
+        .collect()
+}
+
+// 単体で開ける HTML で、色は画面の差分表示と揃える
+pub fn render_html(snapshot_diff: &SnapshotDiff, snapshot_name1: &SnapshotName, snapshot_name2: &SnapshotName, options: &ReportOptions) -> String {
+    let mut lines = vec![
+        "<!DOCTYPE html>".to_string(),
+        "<html>".to_string(),
+        "<head>".to_string(),
+        r#"<meta charset="utf-8">"#.to_string(),
+        "<title>table-snapshot diff</title>".to_string(),
+        "<style>".to_string(),
+        "body { font-family: sans-serif; }".to_string(),
+        "table { border-collapse: collapse; }".to_string(),
+        "th, td { border: solid 1px gray; padding: 0.25em 1em; }".to_string(),
+        "tr.added td { background-color: #cfc; }".to_string(),
+        "tr.removed td { background-color: #fcc; }".to_string(),
+        "del { background-color: #fcc; }".to_string(),
+        "ins { background-color: #cfc; text-decoration: none; }".to_string(),
+        "</style>".to_string(),
+        "</head>".to_string(),
+        "<body>".to_string(),
+        "<h1>table-snapshot diff</h1>".to_string(),
+        format!("<p>{} → {}</p>", escape_html(snapshot_name1), escape_html(snapshot_name2)),
+    ];
+
+    let (report_tables, omitted_tables) = split_shown_tables(create_report_tables(snapshot_diff, options));
+    if report_tables.is_empty() && omitted_tables == 0 {
+        lines.push("<p>No changes.</p>".to_string());
+    }
+
+    for report_table in report_tables {
+        lines.push(format!("<h2>{}</h2>", escape_html(&report_table.label())));
+        lines.push(format!("<p>{}</p>", report_table.count()));
+        lines.push("<table>".to_string());
+
+        let header =
+            ["", &report_table.table_diff.primary_col_name].into_iter().chain(report_table.col_names.iter().map(|col_name| col_name.as_str()));
+        lines.push(format!("<thead><tr>{}</tr></thead>", header.map(|col_name| format!("<th>{}</th>", escape_html(col_name))).join("")));
+
+        lines.push("<tbody>".to_string());
+        for row in report_table.shown_rows() {
+            let cells = row.cells.iter().map(|cell| match (&cell.old_value, &cell.new_value) {
+                (old_value, new_value) if !cell.is_changed() || matches!(row.change, RowChange::Added | RowChange::Removed) => {
+                    escape_html(new_value.as_ref().or(old_value.as_ref()).map_or("", String::as_str))
+                }
+                (old_value, new_value) => [
+                    old_value.as_ref().map(|v| format!("<del>{}</del>", escape_html(v))),
+                    new_value.as_ref().map(|v| format!("<ins>{}</ins>", escape_html(v))),
+                ]
+                .into_iter()
+                .flatten()
+                .join(" "),
+            });
+            let row_html = [escape_html(row.change.as_str()), escape_html(&row.primary_value)]
+                .into_iter()
+                .chain(cells)
+                .map(|cell| format!("<td>{cell}</td>"))
+                .join("");
+            lines.push(format!(r#"<tr class="{}">{row_html}</tr>"#, row.change.as_str()));
+        }
+        lines.push("</tbody>".to_string());
+        lines.push("</table>".to_string());
+
+        if report_table.omitted() > 0 {
+            lines.push(format!("<p>{} more rows omitted</p>", report_table.omitted()));
+        }
+    }
+
+    if omitted_tables > 0 {
+        lines.push(format!("<p>{omitted_tables} more tables omitted</p>"));
+    }
+
+    lines.extend(["</body>".to_string(), "</html>".to_string(), "".to_string()]);
+    lines.join("\n")
+}
+
+// 1 行も出力しないテーブルは見出しごと省き、省いたテーブルの数だけを返す
+fn split_shown_tables(report_tables: Vec<ReportTable>) -> (Vec<ReportTable>, usize) {
+    let (shown_tables, omitted_tables): (Vec<_>, Vec<_>) = report_tables.into_iter().partition(|report_table| report_table.shown > 0);
+    (shown_tables, omitted_tables.len())
+}
+
+fn escape_html(value: &str) -> String {
+    value.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::diff::create_snapshot_diff;
+    use crate::domain::fixture::{mk_table_snapshot, n, s};
+    use crate::domain::report::{escape_markdown, render_html, render_markdown, IgnoreRule, ReportOptions};
+    use crate::domain::snapshot::TableSnapshot;
+
+    fn table_snapshots1() -> Vec<TableSnapshot> {
+        vec![
+            mk_table_snapshot("logs", vec!["message"], vec![]),
+            mk_table_snapshot(
+                "users",
+                vec!["name", "updated_at"],
+                vec![
+                    vec![n("1"), s("John"), s("2023-07-01")],
+                    vec![n("2"), s("Jack"), s("2023-07-01")],
+                    vec![n("3"), s("Jane|Doe"), s("2023-07-01")],
+                ],
+            ),
+        ]
+    }
+
+    fn table_snapshots2() -> Vec<TableSnapshot> {
+        vec![
+            mk_table_snapshot("logs", vec!["message"], vec![vec![n("1"), s("login")]]),
+            mk_table_snapshot(
+                "users",
+                vec!["name", "updated_at"],
+                vec![vec![n("1"), s("John"), s("2023-07-02")], vec![n("2"), s("Jill"), s("2023-07-02")], vec![n("4"), s("<Bob>"), s("2023-07-02")]],
+            ),
+        ]
+    }
+
+    #[test]
+    fn markdown() {
+        let snapshot_diff = create_snapshot_diff(&"s1".to_string(), &"s2".to_string(), &table_snapshots1(), &table_snapshots2(), &[]);
+
+        let (name1, name2) = ("before_import".to_string(), "after import".to_string());
+
+        // 無視する列だけが変わった行は出力しない
+        let options = ReportOptions { ignore_rules: vec![IgnoreRule::parse("logs"), IgnoreRule::parse("*.updated_at")], max_rows_per_table: None };
+        assert_eq!(
+            r#"# table-snapshot diff
+
+before\_import → after import
+
+## users
+
+1 added, 1 removed, 1 modified
+
+|  | id | name |
+| --- | --- | --- |
+| modified | 2 | ~~"Jack"~~ → **"Jill"** |
+| removed | 3 | "Jane\|Doe" |
+| added | 4 | "&lt;Bob&gt;" |
+"#,
+            render_markdown(&snapshot_diff, &name1, &name2, &options)
+        );
+
+        // 上限を超えた行は件数だけを出力する
+        let options = ReportOptions { ignore_rules: vec![IgnoreRule::parse("logs")], max_rows_per_table: Some(1) };
+        assert_eq!(
+            r#"# table-snapshot diff
+
+before\_import → after import
+
+## users
+
+1 added, 1 removed, 2 modified
+
+|  | id | name | updated\_at |
+| --- | --- | --- | --- |
+| modified | 1 | "John" | ~~"2023-07-01"~~ → **"2023-07-02"** |
+
+_3 more rows omitted_
+"#,
+            render_markdown(&snapshot_diff, &name1, &name2, &options)
+        );
+
+        // 1 行も出力しないテーブルは、見出しごと省いて数だけを出力する
+        let options = ReportOptions { ignore_rules: vec![], max_rows_per_table: Some(0) };
+        assert_eq!(
+            "# table-snapshot diff\n\nbefore\\_import → after import\n\n_2 more tables omitted_\n",
+            render_markdown(&snapshot_diff, &name1, &name2, &options)
+        );
+
+        // 変更がすべて無視される場合
+        let options = ReportOptions { ignore_rules: vec![IgnoreRule::parse("*")], max_rows_per_table: None };
+        assert_eq!(
+            "# table-snapshot diff\n\nbefore\\_import → after import\n\nNo changes.\n",
+            render_markdown(&snapshot_diff, &name1, &name2, &options)
+        );
+    }
+
+    #[test]
+    fn escape() {
+        // インラインの記法に使う文字は、そのまま表示されるようにエスケープする
+        assert_eq!(r"\*a\* \_b\_ \~\~c\~\~ \`d\` \[e\](f) C:\\tmp a\|b", escape_markdown(r"*a* _b_ ~~c~~ `d` [e](f) C:\tmp a|b"));
+        assert_eq!("&lt;b&gt; &amp;amp;<br>x", escape_markdown("<b> &amp;\nx"));
+    }
+
+    #[test]
+    fn html() {
+        let snapshot_diff = create_snapshot_diff(&"s1".to_string(), &"s2".to_string(), &table_snapshots1(), &table_snapshots2(), &[]);
+
+        let options =
+            ReportOptions { ignore_rules: vec![IgnoreRule::parse("logs"), IgnoreRule::parse("users.updated_at")], max_rows_per_table: Some(2) };
+        let act = render_html(&snapshot_diff, &"before".to_string(), &"<after>".to_string(), &options);
+
+        assert!(act.starts_with("<!DOCTYPE html>\n<html>\n<head>\n"));
+        assert!(act.contains("<h1>table-snapshot diff</h1>\n<p>before → &lt;after&gt;</p>\n"));
+        assert!(act
+            .contains("<h2>users</h2>\n<p>1 added, 1 removed, 1 modified</p>\n<table>\n<thead><tr><th></th><th>id</th><th>name</th></tr></thead>\n"));
+        assert!(
+            act.contains(r#"<tr class="modified"><td>modified</td><td>2</td><td><del>&quot;Jack&quot;</del> <ins>&quot;Jill&quot;</ins></td></tr>"#)
+        );
+        assert!(act.contains(r#"<tr class="removed"><td>removed</td><td>3</td><td>&quot;Jane|Doe&quot;</td></tr>"#));
+        assert!(act.contains("</table>\n<p>1 more rows omitted</p>\n"));
+        assert!(!act.contains("<h2>logs</h2>"));
+        assert!(act.ends_with("</body>\n</html>\n"));
+
+        // 1 行も出力しないテーブルは、見出しごと省く
+        let options = ReportOptions { ignore_rules: vec![], max_rows_per_table: Some(0) };
+        let act = render_html(&snapshot_diff, &"before".to_string(), &"after".to_string(), &options);
+
+        assert!(!act.contains("<h2>"));
+        assert!(!act.contains("<table>"));
+        assert!(act.contains("<p>2 more tables omitted</p>\n"));
+        assert!(!act.contains("No changes."));
+    }
+}
@@ -0,0 +1,198 @@
+use anyhow::anyhow;
+use chrono::{Datelike, NaiveDateTime, Timelike};
+use serde::{Deserialize, Serialize};
+
+use crate::domain::project::ProjectId;
+use crate::domain::snapshot::SnapshotName;
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Clone, Debug)]
+pub struct SnapshotSchedule {
+    pub project_id: ProjectId,
+    pub trigger: Trigger,
+    pub name_prefix: String,
+    // 自動スナップショットを残す最大数 (スケジュールで作ったものだけを数える)
+    pub retention: usize,
+    pub enabled: bool,
+}
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Clone, Debug)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Trigger {
+    Interval { seconds: u64 },
+    Cron { expression: String },
+}
+
+impl SnapshotSchedule {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.name_prefix.is_empty() {
+            return Err(anyhow!("name prefix is required"));
+        }
+        if self.retention == 0 {
+            return Err(anyhow!("retention must be greater than 0"));
+        }
+        match &self.trigger {
+            Trigger::Interval { seconds } if *seconds == 0 => Err(anyhow!("interval must be greater than 0")),
+            Trigger::Interval { .. } => Ok(()),
+            Trigger::Cron { expression } => CronExpression::parse(expression).map(|_| ()),
+        }
+    }
+
+    pub fn is_due(&self, last_run: Option<NaiveDateTime>, now: NaiveDateTime) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        match &self.trigger {
+            Trigger::Interval { seconds } => last_run.is_none_or(|last_run| (now - last_run).num_seconds() >= *seconds as i64),
+            Trigger::Cron { expression } => {
+                // 同じ分の中では一度だけ実行する
+                let same_minute = last_run
+                    .is_some_and(|last_run| last_run.date() == now.date() && last_run.hour() == now.hour() && last_run.minute() == now.minute());
+                !same_minute && CronExpression::parse(expression).is_ok_and(|cron| cron.matches(now))
+            }
+        }
+    }
+
+    pub fn snapshot_name(&self, now: NaiveDateTime) -> SnapshotName {
+        format!("{}{}", self.name_prefix, now.format("%Y-%m-%d %H:%M:%S"))
+    }
+}
+
+// 分 時 日 月 曜日 の 5 フィールドで、各フィールドは * , - / を使える
+// 日と曜日の両方を * 以外で指定した場合は、標準の cron と同じくどちらかに一致すればよい
+#[derive(Eq, PartialEq, Debug)]
+pub struct CronExpression {
+    minutes: Vec<u32>,
+    hours: Vec<u32>,
+    days: Vec<u32>,
+    months: Vec<u32>,
+    weekdays: Vec<u32>,
+    either_day: bool,
+}
+
+impl CronExpression {
+    pub fn parse(expression: &str) -> anyhow::Result<Self> {
+        let fields = expression.split_whitespace().collect::<Vec<_>>();
+        if fields.len() != 5 {
+            return Err(anyhow!("cron expression must have 5 fields: {expression}"));
+        }
+        Ok(Self {
+            minutes: parse_cron_field(fields[0], 0, 59)?,
+            hours: parse_cron_field(fields[1], 0, 23)?,
+            days: parse_cron_field(fields[2], 1, 31)?,
+            months: parse_cron_field(fields[3], 1, 12)?,
+            // 日曜日は 0 と 7 のどちらでも指定できる
+            weekdays: parse_cron_field(fields[4], 0, 7)?.into_iter().map(|weekday| weekday % 7).collect(),
+            either_day: !fields[2].starts_with('*') && !fields[4].starts_with('*'),
+        })
+    }
+
+    pub fn matches(&self, at: NaiveDateTime) -> bool {
+        let day = self.days.contains(&at.day());
+        let weekday = self.weekdays.contains(&at.weekday().num_days_from_sunday());
+        let day_matches = match self.either_day {
+            true => day || weekday,
+            false => day && weekday,
+        };
+        self.minutes.contains(&at.minute()) && self.hours.contains(&at.hour()) && self.months.contains(&at.month()) && day_matches
+    }
+}
+
+fn parse_cron_field(field: &str, min: u32, max: u32) -> anyhow::Result<Vec<u32>> {
+    let parse =
+        |value: &str| value.parse::<u32>().ok().filter(|value| (min..=max).contains(value)).ok_or_else(|| anyhow!("invalid cron field: {field}"));
+
+    let mut values = vec![];
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                (range, step.parse::<usize>().ok().filter(|step| *step > 0).ok_or_else(|| anyhow!("invalid cron field: {field}"))?)
+            }
+            None => (part, 1),
+        };
+        let (from, to) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((from, to)) => (parse(from)?, parse(to)?),
+                None => (parse(range)?, parse(range)?),
+            },
+        };
+        if to < from {
+            return Err(anyhow!("invalid cron field: {field}"));
+        }
+        values.extend((from..=to).step_by(step));
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+
+    use crate::domain::schedule::{CronExpression, SnapshotSchedule, Trigger};
+
+    fn at(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    fn schedule(trigger: Trigger) -> SnapshotSchedule {
+        SnapshotSchedule { project_id: "project".to_string(), trigger, name_prefix: "auto-".to_string(), retention: 3, enabled: true }
+    }
+
+    #[test]
+    fn parse_cron() -> anyhow::Result<()> {
+        let cron = CronExpression::parse("*/15 9-18 * * 1-5")?;
+        assert!(cron.matches(at("2023-07-03 09:00:00"))); // monday
+        assert!(cron.matches(at("2023-07-03 18:45:00")));
+        assert!(!cron.matches(at("2023-07-03 09:10:00")));
+        assert!(!cron.matches(at("2023-07-03 19:00:00")));
+        assert!(!cron.matches(at("2023-07-02 09:00:00"))); // sunday
+
+        // 日と曜日の両方を指定した場合は、どちらかに一致すればよい
+        let cron = CronExpression::parse("0 0 1,15 * 0")?;
+        assert!(cron.matches(at("2023-01-15 00:00:00"))); // sunday
+        assert!(cron.matches(at("2023-01-01 00:00:00"))); // sunday
+        assert!(cron.matches(at("2023-07-01 00:00:00"))); // saturday
+        assert!(cron.matches(at("2023-07-02 00:00:00"))); // sunday
+        assert!(!cron.matches(at("2023-07-03 00:00:00"))); // monday
+
+        // 片方が * の場合は、両方に一致する必要がある
+        let cron = CronExpression::parse("0 0 */2 * 1")?;
+        assert!(cron.matches(at("2023-07-03 00:00:00"))); // monday
+        assert!(!cron.matches(at("2023-07-10 00:00:00"))); // monday
+        assert!(!cron.matches(at("2023-07-05 00:00:00"))); // wednesday
+
+        assert!(CronExpression::parse("* * * *").is_err());
+        assert!(CronExpression::parse("60 * * * *").is_err());
+        assert!(CronExpression::parse("*/0 * * * *").is_err());
+        assert!(CronExpression::parse("10-5 * * * *").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn is_due() {
+        let interval = schedule(Trigger::Interval { seconds: 30 });
+        assert!(interval.is_due(None, at("2023-07-03 09:00:00")));
+        assert!(!interval.is_due(Some(at("2023-07-03 09:00:00")), at("2023-07-03 09:00:29")));
+        assert!(interval.is_due(Some(at("2023-07-03 09:00:00")), at("2023-07-03 09:00:30")));
+
+        let cron = schedule(Trigger::Cron { expression: "*/5 * * * *".to_string() });
+        assert!(cron.is_due(None, at("2023-07-03 09:05:00")));
+        assert!(cron.is_due(Some(at("2023-07-03 09:00:00")), at("2023-07-03 09:05:01")));
+        assert!(!cron.is_due(Some(at("2023-07-03 09:05:00")), at("2023-07-03 09:05:01")));
+        assert!(!cron.is_due(None, at("2023-07-03 09:06:00")));
+
+        let disabled = SnapshotSchedule { enabled: false, ..interval };
+        assert!(!disabled.is_due(None, at("2023-07-03 09:00:00")));
+    }
+
+    #[test]
+    fn validate() {
+        assert!(schedule(Trigger::Interval { seconds: 30 }).validate().is_ok());
+        assert!(schedule(Trigger::Interval { seconds: 0 }).validate().is_err());
+        assert!(schedule(Trigger::Cron { expression: "0 * * * *".to_string() }).validate().is_ok());
+        assert!(schedule(Trigger::Cron { expression: "every minute".to_string() }).validate().is_err());
+        assert!(SnapshotSchedule { retention: 0, ..schedule(Trigger::Interval { seconds: 30 }) }.validate().is_err());
+        assert!(SnapshotSchedule { name_prefix: "".to_string(), ..schedule(Trigger::Interval { seconds: 30 }) }.validate().is_err());
+    }
+}
@@ -1,4 +1,5 @@
 use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 
 pub type TableName = String;
 
@@ -33,10 +34,6 @@ impl ColumnSchemata {
         cols.insert(0, &self.primary_col);
         cols
     }
-
-    pub fn count(&self) -> usize {
-        self.cols.len() + 1
-    }
 }
 
 #[derive(Clone)]
@@ -45,3 +42,12 @@ pub struct ColumnSchema {
     pub data_type: String,
     pub column_type: String,
 }
+
+// 単一列の外部キー
+// 複数列からなる外部キーは扱わない
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Debug)]
+pub struct ForeignKey {
+    pub col_name: ColName,
+    pub referenced_table_name: TableName,
+    pub referenced_col_name: ColName,
+}
@@ -1,12 +1,14 @@
-use std::cmp::max;
+use std::cmp::{max, Ordering};
 use std::collections::BTreeSet;
 
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
 use chrono::Local;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::domain::schema::{ColName, Hash, PrimaryColName, PrimaryValue, TableName};
+use crate::domain::schema::{ColName, ForeignKey, Hash, PrimaryColName, PrimaryValue, TableName};
 use crate::domain::snapshot::ColValue::*;
 
 pub type SnapshotId = String;
@@ -42,13 +44,20 @@ pub struct TableSnapshot {
     pub col_names: Vec<ColName>,
     pub hash: Hash,
     pub row_snapshots: Vec<RowSnapshot>,
+    // 外部キーは行の内容ではないので、ハッシュには含めない
+    #[serde(default)]
+    pub foreign_keys: Vec<ForeignKey>,
 }
 
 impl TableSnapshot {
     pub fn new(table_name: &TableName, primary_col_name: PrimaryColName, col_names: Vec<ColName>, row_snapshots: Vec<RowSnapshot>) -> Self {
         let row_hashes = row_snapshots.iter().map(|row_snapshot| &row_snapshot.hash).join("");
         let hash = format!("{:?}", md5::compute(format!("{}{}{}", primary_col_name, col_names.join(""), row_hashes)));
-        Self { table_name: table_name.clone(), primary_col_name, col_names, hash, row_snapshots }
+        Self { table_name: table_name.clone(), primary_col_name, col_names, hash, row_snapshots, foreign_keys: vec![] }
+    }
+
+    pub fn with_foreign_keys(self, foreign_keys: Vec<ForeignKey>) -> Self {
+        Self { foreign_keys, ..self }
     }
 
     pub fn get_primary_col_values(&self) -> Vec<&PrimaryColValue> {
@@ -125,6 +134,19 @@ impl ColValue {
         }
     }
 
+    // as_primary_value で文字列にした主キー値を、self と同じ種類の値に戻す
+    pub fn parse_primary_value(&self, primary_value: &str) -> Option<ColValue> {
+        let unquote = |v: &str| v.strip_prefix('"').and_then(|v| v.strip_suffix('"')).map(|v| v.to_string());
+        match self {
+            SimpleNumber(_) => Some(SimpleNumber(primary_value.to_string())),
+            BitNumber(_) => primary_value.strip_prefix("bit(").and_then(|v| v.strip_suffix(')')).map(|v| BitNumber(v.to_string())),
+            SimpleString(_) => unquote(primary_value).map(SimpleString),
+            DateString(_) => unquote(primary_value).map(DateString),
+            JsonString(_) => Some(JsonString(primary_value.to_string())),
+            Null | BinaryString(_) | ParseError => None,
+        }
+    }
+
     pub fn as_display_value(&self) -> String {
         match self {
             SimpleNumber(v) => v.to_string(),
@@ -138,6 +160,30 @@ impl ColValue {
         }
     }
 
+    // 差分の出力に使う JSON の値
+    // 数値は JSON の数値にし、桁を落とさずに表せない値だけを文字列にする
+    // バイナリと解釈できなかった値は、文字列と区別できるよう { "type": ... } のオブジェクトにする
+    // JSON 型の列は解釈できればそのまま埋め込む
+    pub fn as_json_value(&self) -> serde_json::Value {
+        match self {
+            SimpleNumber(v) => number_json_value(v),
+            BitNumber(v) => u64::from_str_radix(v, 2).map(serde_json::Value::from).unwrap_or_else(|_| serde_json::Value::String(v.clone())),
+            SimpleString(v) | DateString(v) => serde_json::Value::String(v.clone()),
+            BinaryString(v) => serde_json::json!({ "type": "binary", "base64": STANDARD.encode(v) }),
+            JsonString(v) => serde_json::from_str(v).unwrap_or_else(|_| serde_json::Value::String(v.clone())),
+            Null => serde_json::Value::Null,
+            ParseError => serde_json::json!({ "type": "parse-error" }),
+        }
+    }
+
+    // 数値どうしは文字列ではなく数値として比べ、それ以外は値の種類と文字列で比べる
+    pub fn cmp_value(&self, other: &ColValue) -> Ordering {
+        match (self, other) {
+            (SimpleNumber(a), SimpleNumber(b)) => cmp_number(a, b),
+            _ => self.cmp(other),
+        }
+    }
+
     fn as_raw_value(&self) -> String {
         match self {
             SimpleNumber(v) => v.to_string(),
@@ -151,3 +197,98 @@ impl ColValue {
         }
     }
 }
+
+// 整数は i64 か u64 に収まれば、小数は f64 にしても値が変わらなければ数値にする
+fn number_json_value(v: &str) -> serde_json::Value {
+    if let Ok(n) = v.parse::<i64>() {
+        return serde_json::Value::from(n);
+    }
+    if let Ok(n) = v.parse::<u64>() {
+        return serde_json::Value::from(n);
+    }
+    match v.parse::<f64>().ok().filter(|n| n.is_finite() && cmp_number(v, &n.to_string()) == Ordering::Equal) {
+        Some(n) => serde_json::Value::from(n),
+        None => serde_json::Value::String(v.to_string()),
+    }
+}
+
+// bigint unsigned や decimal の桁を落とさないよう、f64 にせず桁を比べる
+// float や double の指数表記など、桁を比べられない値は f64 として比べる
+fn cmp_number(a: &str, b: &str) -> Ordering {
+    match (split_number(a), split_number(b)) {
+        (Some((a_negative, a_int, a_frac)), Some((b_negative, b_int, b_frac))) => {
+            let abs = a_int.len().cmp(&b_int.len()).then_with(|| a_int.cmp(b_int)).then_with(|| a_frac.cmp(b_frac));
+            match (a_negative, b_negative) {
+                (false, false) => abs,
+                (true, true) => abs.reverse(),
+                (false, true) => Ordering::Greater,
+                (true, false) => Ordering::Less,
+            }
+        }
+        _ => a.parse::<f64>().unwrap_or(f64::MIN).total_cmp(&b.parse::<f64>().unwrap_or(f64::MIN)),
+    }
+}
+
+// 符号、先頭の 0 を除いた整数部、末尾の 0 を除いた小数部に分ける
+// 0 は符号を持たないものとする
+fn split_number(v: &str) -> Option<(bool, &str, &str)> {
+    let (negative, v) = match v.strip_prefix('-') {
+        Some(v) => (true, v),
+        None => (false, v.strip_prefix('+').unwrap_or(v)),
+    };
+    let (int, frac) = v.split_once('.').unwrap_or((v, ""));
+    if int.is_empty() && frac.is_empty() || !int.chars().chain(frac.chars()).all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let int = int.trim_start_matches('0');
+    let frac = frac.trim_end_matches('0');
+    Some((negative && !(int.is_empty() && frac.is_empty()), int, frac))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering::{Equal, Greater, Less};
+
+    use serde_json::json;
+
+    use crate::domain::fixture::{n, s};
+    use crate::domain::snapshot::ColValue::{BinaryString, BitNumber, DateString, JsonString, Null, ParseError};
+
+    #[test]
+    fn cmp_value() {
+        assert_eq!(Less, n("9").cmp_value(&n("10")));
+        assert_eq!(Greater, n("18446744073709551615").cmp_value(&n("18446744073709551614")));
+        assert_eq!(Less, n("-10").cmp_value(&n("-9")));
+        assert_eq!(Less, n("-0.5").cmp_value(&n("0")));
+        assert_eq!(Equal, n("-0.00").cmp_value(&n("0")));
+        assert_eq!(Equal, n("1.50").cmp_value(&n("01.5")));
+        assert_eq!(Greater, n("999.99").cmp_value(&n("999.9")));
+        assert_eq!(Greater, n("1e+20").cmp_value(&n("99999")));
+
+        // 数値以外は文字列として比べる
+        assert_eq!(Greater, s("9").cmp_value(&s("10")));
+    }
+
+    #[test]
+    fn as_json_value() {
+        assert_eq!(json!(-12), n("-12").as_json_value());
+        assert_eq!(json!(18446744073709551615u64), n("18446744073709551615").as_json_value());
+        assert_eq!(json!(1.5), n("1.50").as_json_value());
+        assert_eq!(json!(1e20), n("1e+20").as_json_value());
+
+        // f64 で桁が落ちる値は文字列のままにする
+        assert_eq!(json!("18446744073709551616"), n("18446744073709551616").as_json_value());
+        assert_eq!(json!("12345678901234567.89"), n("12345678901234567.89").as_json_value());
+
+        assert_eq!(json!(5), BitNumber("101".to_string()).as_json_value());
+        assert_eq!(json!("2023-07-01"), DateString("2023-07-01".to_string()).as_json_value());
+        assert_eq!(json!({ "type": "binary", "base64": "YWJj" }), BinaryString("abc".to_string()).as_json_value());
+        assert_eq!(json!({ "a": [1] }), JsonString(r#"{"a": [1]}"#.to_string()).as_json_value());
+        assert_eq!(json!(null), Null.as_json_value());
+        assert_eq!(json!({ "type": "parse-error" }), ParseError.as_json_value());
+
+        // 文字列の "binary" や "parse error" とは区別する
+        assert_ne!(s("binary").as_json_value(), BinaryString("binary".to_string()).as_json_value());
+    }
+}
@@ -1,5 +1,6 @@
-use crate::domain::schema::{ColumnSchemata, TableSchema};
-use crate::domain::snapshot::RowSnapshot;
+use crate::domain::diagnosis::ConnectionReport;
+use crate::domain::schema::{ColName, ColumnSchemata, ForeignKey, TableSchema};
+use crate::domain::snapshot::{PrimaryColValue, RowSnapshot};
 
 pub trait TargetDbAdapter {
     fn get_table_schemata(&mut self) -> anyhow::Result<Vec<TableSchema>>;
@@ -7,4 +8,19 @@ pub trait TargetDbAdapter {
     fn get_col_schemata(&mut self, table_schema: &TableSchema) -> anyhow::Result<ColumnSchemata>;
 
     fn get_row_snapshots(&mut self, table_schema: &TableSchema, column_schemata: &ColumnSchemata) -> anyhow::Result<Vec<RowSnapshot>>;
+
+    // ウォーターマーク列の値が watermark 以上、または NULL の行だけを取得する
+    fn get_row_snapshots_since(
+        &mut self,
+        table_schema: &TableSchema,
+        column_schemata: &ColumnSchemata,
+        watermark_col_name: &ColName,
+        watermark: &str,
+    ) -> anyhow::Result<Vec<RowSnapshot>>;
+
+    fn get_primary_col_values(&mut self, table_schema: &TableSchema, column_schemata: &ColumnSchemata) -> anyhow::Result<Vec<PrimaryColValue>>;
+
+    fn get_foreign_keys(&mut self, table_schema: &TableSchema) -> anyhow::Result<Vec<ForeignKey>>;
+
+    fn diagnose(&mut self) -> anyhow::Result<ConnectionReport>;
 }
@@ -0,0 +1,354 @@
+use std::io::ErrorKind;
+use std::process::Command;
+
+use anyhow::anyhow;
+use mysql::Opts;
+use serde::{Deserialize, Serialize};
+
+use crate::domain::diff::{DiffSummary, TableDiff};
+use crate::domain::project::{ConnectionOptions, SslMode};
+use crate::domain::schema::{ColumnSchema, TableName};
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Clone, Debug)]
+pub struct BinlogPosition {
+    pub file: String,
+    pub position: u64,
+}
+
+impl BinlogPosition {
+    pub fn new<S: Into<String>>(file: S, position: u64) -> Self {
+        Self { file: file.into(), position }
+    }
+}
+
+impl std::fmt::Display for BinlogPosition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.file, self.position)
+    }
+}
+
+// スナップショットに紐づかないので、スナップショット ID の代わりに binlog の位置を持つ
+pub struct BinlogDiff {
+    pub from: BinlogPosition,
+    pub to: BinlogPosition,
+    pub table_diffs: Vec<TableDiff>,
+    pub summary: DiffSummary,
+}
+
+impl BinlogDiff {
+    pub fn new(from: &BinlogPosition, to: &BinlogPosition, table_diffs: Vec<TableDiff>) -> Self {
+        let summary = DiffSummary::create(&table_diffs, &[]);
+        Self { from: from.clone(), to: to.clone(), table_diffs, summary }
+    }
+}
+
+// NULL は None で、文字列はクォートを外してエスケープを戻した値になる
+pub type BinlogValue = Option<String>;
+
+#[derive(Eq, PartialEq, Debug)]
+pub struct RowEvent {
+    pub schema: String,
+    pub table_name: TableName,
+    pub before: Option<Vec<BinlogValue>>,
+    pub after: Option<Vec<BinlogValue>>,
+}
+
+enum Section {
+    Before,
+    After,
+}
+
+// mysqlbinlog コマンドが PATH にある必要がある
+// mysqlbinlog はクエリを実行しないので、charset と init_sql は渡さない
+pub fn run_mysqlbinlog(
+    opts: &Opts,
+    options: &ConnectionOptions,
+    files: &[String],
+    from: &BinlogPosition,
+    to: &BinlogPosition,
+) -> anyhow::Result<String> {
+    let mut command = Command::new("mysqlbinlog");
+    command
+        .arg("--read-from-remote-server")
+        .arg("--base64-output=DECODE-ROWS")
+        .arg("--verbose")
+        .arg("--verbose")
+        .arg(format!("--start-position={}", from.position))
+        .arg(format!("--stop-position={}", to.position));
+    match (opts.get_socket(), opts.get_ip_or_hostname()) {
+        (Some(socket), _) => command.arg(format!("--socket={socket}")),
+        (None, Some(host)) => command.arg("--protocol=TCP").arg(format!("--host={host}")).arg(format!("--port={}", opts.get_tcp_port())),
+        (None, None) => return Err(anyhow!("host or socket is required to read binlog")),
+    };
+    if let Some(user) = opts.get_user() {
+        command.arg(format!("--user={user}"));
+    }
+    // コマンドラインに残らないよう、パスワードは環境変数で渡す
+    if let Some(pass) = opts.get_pass() {
+        command.env("MYSQL_PWD", pass);
+    }
+    command.args(ssl_args(options));
+    command.args(files);
+
+    let output = command.output().map_err(|e| match e.kind() {
+        ErrorKind::NotFound => anyhow!("mysqlbinlog is not found, install the mysql client and add mysqlbinlog to PATH"),
+        _ => anyhow!("failed to run mysqlbinlog: {e}"),
+    })?;
+    if !output.status.success() {
+        return Err(anyhow!("mysqlbinlog failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// 接続と同じ SSL モードで読む
+fn ssl_args(options: &ConnectionOptions) -> Vec<String> {
+    let mut args = vec![match options.ssl_mode {
+        SslMode::Disabled => "--ssl-mode=DISABLED".to_string(),
+        SslMode::Required => "--ssl-mode=REQUIRED".to_string(),
+        SslMode::VerifyCa => "--ssl-mode=VERIFY_CA".to_string(),
+    }];
+    if options.ssl_mode != SslMode::Disabled {
+        if let Some(ssl_ca_path) = options.ssl_ca_path.as_ref().filter(|path| !path.is_empty()) {
+            args.push(format!("--ssl-ca={ssl_ca_path}"));
+        }
+    }
+    args
+}
+
+// mysqlbinlog -vv が出力する ### から始まる疑似 SQL を読む
+pub fn parse_row_events(output: &str) -> anyhow::Result<Vec<RowEvent>> {
+    let mut events: Vec<RowEvent> = vec![];
+    let mut section = None;
+
+    for line in output.lines() {
+        let Some(line) = line.strip_prefix("### ") else {
+            continue;
+        };
+
+        if let Some(name) = line.strip_prefix("INSERT INTO ") {
+            events.push(new_row_event(name, false)?);
+            section = None;
+        } else if let Some(name) = line.strip_prefix("UPDATE ") {
+            events.push(new_row_event(name, true)?);
+            section = None;
+        } else if let Some(name) = line.strip_prefix("DELETE FROM ") {
+            events.push(new_row_event(name, true)?);
+            section = None;
+        } else if line == "WHERE" {
+            section = Some(Section::Before);
+        } else if line == "SET" {
+            section = Some(Section::After);
+        } else if let Some(col) = line.trim_start().strip_prefix('@') {
+            let event = events.last_mut().ok_or_else(|| anyhow!("column value without row event: {line}"))?;
+            let values = match section {
+                Some(Section::Before) => event.before.get_or_insert_with(Vec::new),
+                Some(Section::After) => event.after.get_or_insert_with(Vec::new),
+                None => return Err(anyhow!("column value without WHERE or SET: {line}")),
+            };
+            let (_, value) = col.split_once('=').ok_or_else(|| anyhow!("invalid column value: {line}"))?;
+            values.push(parse_binlog_value(value));
+        }
+    }
+
+    Ok(events)
+}
+
+fn new_row_event(qualified_name: &str, has_before: bool) -> anyhow::Result<RowEvent> {
+    let (schema, table_name) = qualified_name.trim_matches('`').split_once("`.`").ok_or_else(|| anyhow!("invalid table name: {qualified_name}"))?;
+    Ok(RowEvent {
+        schema: schema.replace("``", "`"),
+        table_name: table_name.replace("``", "`"),
+        before: if has_before { Some(vec![]) } else { None },
+        after: None,
+    })
+}
+
+fn parse_binlog_value(value: &str) -> BinlogValue {
+    // -vv では値のあとに /* 型情報 */ が付く
+    let value = match value.rfind(" /* ") {
+        Some(i) => &value[..i],
+        None => value,
+    };
+
+    if value == "NULL" {
+        None
+    } else if value.len() >= 2 && value.starts_with('\'') && value.ends_with('\'') {
+        Some(unescape(&value[1..value.len() - 1]))
+    } else {
+        // 浮動小数点数は右側が空白で埋められる
+        Some(value.trim_end().to_string())
+    }
+}
+
+fn unescape(value: &str) -> String {
+    let mut bytes = vec![];
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('x') => {
+                    let hex = chars.by_ref().take(2).collect::<String>();
+                    match u8::from_str_radix(&hex, 16) {
+                        Ok(byte) => bytes.push(byte),
+                        Err(_) => bytes.extend(format!("\\x{hex}").as_bytes()),
+                    }
+                }
+                Some(c) => bytes.extend(c.to_string().as_bytes()),
+                None => bytes.push(b'\\'),
+            },
+            c => bytes.extend(c.to_string().as_bytes()),
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+// スナップショットの SELECT 結果と同じ表現にそろえる
+// timestamp は UNIX 時間で記録されているので、サーバのタイムゾーンで変換する必要があり、ここでは扱わない
+pub fn normalize_binlog_value(column_schema: &ColumnSchema, value: &str) -> anyhow::Result<String> {
+    match column_schema.data_type.as_str() {
+        "tinyint" | "smallint" | "mediumint" | "int" | "bigint" => {
+            // unsigned の列で符号付きとして負になる値は -1 (255) のように出力される
+            match (column_schema.column_type.contains("unsigned"), value.split_once(" (")) {
+                (true, Some((_, unsigned))) => Ok(unsigned.trim_end_matches(')').to_string()),
+                _ => Ok(value.to_string()),
+            }
+        }
+        // SELECT と同じく、元の値を表せる最短の表現にする
+        "float" => value.parse::<f32>().map(|value| value.to_string()).map_err(|_| anyhow!("invalid float value: {value}")),
+        "double" => value.parse::<f64>().map(|value| value.to_string()).map_err(|_| anyhow!("invalid double value: {value}")),
+        "bit" => {
+            let bits = value.trim_start_matches("b'").trim_end_matches('\'').trim_start_matches('0');
+            Ok(if bits.is_empty() { "0".to_string() } else { bits.to_string() })
+        }
+        "date" => Ok(value.replace(':', "-")),
+        "enum" => {
+            let labels = parse_labels(&column_schema.column_type)?;
+            let index = value.parse::<usize>().map_err(|_| anyhow!("invalid enum value: {value}"))?;
+            Ok(index.checked_sub(1).and_then(|i| labels.get(i)).cloned().unwrap_or_default())
+        }
+        "set" => {
+            let labels = parse_labels(&column_schema.column_type)?;
+            let bits = value.parse::<u64>().map_err(|_| anyhow!("invalid set value: {value}"))?;
+            Ok(labels.iter().enumerate().filter(|(i, _)| bits & (1 << i) != 0).map(|(_, label)| label.as_str()).collect::<Vec<_>>().join(","))
+        }
+        _ => Ok(value.to_string()),
+    }
+}
+
+// enum('a','b') や set('a','b') の定義から値の一覧を取り出す
+fn parse_labels(column_type: &str) -> anyhow::Result<Vec<String>> {
+    let body =
+        column_type.split_once('(').and_then(|(_, body)| body.strip_suffix(')')).ok_or_else(|| anyhow!("invalid column type: {column_type}"))?;
+
+    let mut labels = vec![];
+    let mut label = String::new();
+    let mut quoted = false;
+    let mut chars = body.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('\'', true) if chars.peek() == Some(&'\'') => {
+                label.push('\'');
+                chars.next();
+            }
+            ('\'', _) => quoted = !quoted,
+            (',', false) => labels.push(std::mem::take(&mut label)),
+            (c, true) => label.push(c),
+            _ => {}
+        }
+    }
+    labels.push(label);
+    Ok(labels)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::project::{ConnectionOptions, SslMode};
+    use crate::domain::schema::ColumnSchema;
+    use crate::dump::binlog::{normalize_binlog_value, parse_row_events, ssl_args, RowEvent};
+
+    fn s(s: &str) -> Option<String> {
+        Some(s.to_string())
+    }
+
+    fn col(data_type: &str, column_type: &str) -> ColumnSchema {
+        ColumnSchema { col_name: "col".to_string(), data_type: data_type.to_string(), column_type: column_type.to_string() }
+    }
+
+    #[test]
+    fn parse() -> anyhow::Result<()> {
+        let output = r#"
+# at 157
+#230703  9:00:00 server id 1  end_log_pos 236 CRC32 0x8c2b9d6f 	Anonymous_GTID	last_committed=0	sequence_number=1
+BEGIN
+/*!*/;
+### INSERT INTO `testdata`.`users`
+### SET
+###   @1=1 /* INT meta=0 nullable=0 is_null=0 */
+###   @2='it\'s /* not a comment */' /* VARSTRING(40) meta=40 nullable=1 is_null=0 */
+###   @3=NULL /* VARSTRING(40) meta=40 nullable=1 is_null=1 */
+### UPDATE `testdata`.`users`
+### WHERE
+###   @1=1 /* INT meta=0 nullable=0 is_null=0 */
+###   @2='a\x0ab' /* VARSTRING(40) meta=40 nullable=1 is_null=0 */
+###   @3=NULL /* VARSTRING(40) meta=40 nullable=1 is_null=1 */
+### SET
+###   @1=1 /* INT meta=0 nullable=0 is_null=0 */
+###   @2='NULL' /* VARSTRING(40) meta=40 nullable=1 is_null=0 */
+###   @3=-1 (255) /* TINYINT meta=0 nullable=1 is_null=0 */
+### DELETE FROM `testdata`.`items`
+### WHERE
+###   @1=2 /* INT meta=0 nullable=0 is_null=0 */
+COMMIT/*!*/;
+"#;
+
+        let events = parse_row_events(output)?;
+
+        assert_eq!(
+            vec![
+                RowEvent {
+                    schema: "testdata".to_string(),
+                    table_name: "users".to_string(),
+                    before: None,
+                    after: Some(vec![s("1"), s("it's /* not a comment */"), None]),
+                },
+                RowEvent {
+                    schema: "testdata".to_string(),
+                    table_name: "users".to_string(),
+                    before: Some(vec![s("1"), s("a\nb"), None]),
+                    after: Some(vec![s("1"), s("NULL"), s("-1 (255)")]),
+                },
+                RowEvent { schema: "testdata".to_string(), table_name: "items".to_string(), before: Some(vec![s("2")]), after: None },
+            ],
+            events
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn normalize() -> anyhow::Result<()> {
+        assert_eq!("255", normalize_binlog_value(&col("tinyint", "tinyint unsigned"), "-1 (255)")?);
+        assert_eq!("-1", normalize_binlog_value(&col("tinyint", "tinyint"), "-1")?);
+        assert_eq!("999.99", normalize_binlog_value(&col("float", "float(5,2)"), "999.99")?);
+        assert_eq!("-999.99", normalize_binlog_value(&col("double", "double(5,2)"), "-999.99000000000000909")?);
+        assert_eq!("1000000000", normalize_binlog_value(&col("bit", "bit(10)"), "b'1000000000'")?);
+        assert_eq!("0", normalize_binlog_value(&col("bit", "bit(10)"), "b'0000000000'")?);
+        assert_eq!("2020-01-01", normalize_binlog_value(&col("date", "date"), "2020:01:01")?);
+        assert_eq!("inactive", normalize_binlog_value(&col("enum", "enum('active','inactive')"), "2")?);
+        assert_eq!("", normalize_binlog_value(&col("enum", "enum('active','inactive')"), "0")?);
+        assert_eq!("pc,phone", normalize_binlog_value(&col("set", "set('pc','phone')"), "3")?);
+        assert_eq!("it's,a", normalize_binlog_value(&col("set", "set('it''s','a,b','a')"), "5")?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn ssl() {
+        let options =
+            |ssl_mode, ssl_ca_path: Option<&str>| ConnectionOptions { ssl_mode, ssl_ca_path: ssl_ca_path.map(str::to_string), ..Default::default() };
+
+        assert_eq!(vec!["--ssl-mode=DISABLED"], ssl_args(&options(SslMode::Disabled, Some("/etc/ca.pem"))));
+        assert_eq!(vec!["--ssl-mode=REQUIRED"], ssl_args(&options(SslMode::Required, None)));
+        assert_eq!(vec!["--ssl-mode=VERIFY_CA", "--ssl-ca=/etc/ca.pem"], ssl_args(&options(SslMode::VerifyCa, Some("/etc/ca.pem"))));
+    }
+}
@@ -1,36 +1,531 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::anyhow;
+use itertools::Itertools;
 use mysql::Conn;
 
-use crate::db::snapshot::{insert_snapshot_summary, insert_table_snapshot};
+use crate::credential::CredentialKey;
+use crate::db::snapshot::{all_snapshot_summaries, find_table_hashes, find_table_snapshot, insert_snapshot_summary, insert_table_snapshot};
+use crate::db::transaction;
+use crate::domain::diagnosis::ConnectionReport;
 use crate::domain::project::Project;
 use crate::domain::project::Rdbms::Mysql;
-use crate::domain::snapshot::{create_snapshot_id, SnapshotId, SnapshotName, SnapshotSummary, TableSnapshot};
+use crate::domain::schema::{ColName, ColumnSchemata, TableName, TableSchema};
+use crate::domain::snapshot::ColValue::{DateString, Null, SimpleNumber, SimpleString};
+use crate::domain::snapshot::{create_snapshot_id, PrimaryColValue, RowSnapshot, SnapshotId, SnapshotName, SnapshotSummary, TableSnapshot};
 use crate::dump::adapter::TargetDbAdapter;
 use crate::dump::mysql80::TargetDbMysql80;
+use crate::error::Error;
+
+pub use crate::dump::binlog::{BinlogDiff, BinlogPosition};
 
 mod adapter;
+mod binlog;
 mod mysql80;
+#[cfg(test)]
+mod testdata;
+mod tunnel;
+
+pub type JobId = String;
+
+pub fn create_job_id() -> JobId {
+    uuid::Uuid::new_v4().to_string()
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct DumpProgress {
+    pub snapshot_id: SnapshotId,
+    pub table_count: usize,
+    pub tables_done: usize,
+    pub rows_fetched: usize,
+    pub current_table: Option<TableName>,
+}
+
+// キャンセルはテーブル単位で確認するので、巨大なテーブルの取得中は取得が終わるまで止まらない
+pub fn dump(
+    conn: &mut Conn,
+    project: &Project,
+    key: &CredentialKey,
+    snapshot_name: SnapshotName,
+    cancelled: &AtomicBool,
+    on_progress: impl FnMut(&DumpProgress),
+) -> anyhow::Result<SnapshotId> {
+    dump_tables(conn, project, key, snapshot_name, cancelled, on_progress, None)?.ok_or_else(|| anyhow!("snapshot was not saved"))
+}
+
+// すべてのテーブルのハッシュが前回のスナップショットと同じなら保存せずに None を返す
+pub fn dump_if_changed(
+    conn: &mut Conn,
+    project: &Project,
+    key: &CredentialKey,
+    snapshot_name: SnapshotName,
+    previous_snapshot_id: &SnapshotId,
+) -> anyhow::Result<Option<SnapshotId>> {
+    dump_tables(conn, project, key, snapshot_name, &AtomicBool::new(false), |_| {}, Some(previous_snapshot_id))
+}
+
+fn dump_tables(
+    conn: &mut Conn,
+    project: &Project,
+    key: &CredentialKey,
+    snapshot_name: SnapshotName,
+    cancelled: &AtomicBool,
+    mut on_progress: impl FnMut(&DumpProgress),
+    previous_snapshot_id: Option<&SnapshotId>,
+) -> anyhow::Result<Option<SnapshotId>> {
+    let password = key.decrypt(&project.password)?;
 
-pub fn dump(conn: &mut Conn, project: &Project, snapshot_name: SnapshotName) -> anyhow::Result<SnapshotId> {
     let mut adapter = match &project.rdbms {
-        Mysql => TargetDbMysql80::new(project),
+        Mysql => TargetDbMysql80::new(project, &password),
     }?;
 
     let snapshot_id = create_snapshot_id();
 
+    // ウォーターマーク列を使う場合は、直前のスナップショットを元にする
+    let parent_snapshot_id = match previous_snapshot_id {
+        _ if project.options.watermark_cols.is_empty() => None,
+        Some(previous_snapshot_id) => Some(previous_snapshot_id.clone()),
+        None => all_snapshot_summaries(conn, &project.project_id)?.pop().map(|snapshot_summary| snapshot_summary.snapshot_id),
+    };
+
     let table_schemata = adapter.get_table_schemata()?;
 
-    for table_schema in table_schemata {
-        let col_schemata = adapter.get_col_schemata(&table_schema)?;
+    let mut progress =
+        DumpProgress { snapshot_id: snapshot_id.clone(), table_count: table_schemata.len(), tables_done: 0, rows_fetched: 0, current_table: None };
+
+    // 書き込み途中の table_snapshot が他のコマンドから見えないよう、ひとつのトランザクションで保存する
+    let result = transaction(conn, |conn| {
+        let snapshot_summary = SnapshotSummary::create(&snapshot_id, &snapshot_name);
+        insert_snapshot_summary(conn, &project.project_id, &snapshot_summary)?;
+
+        let mut table_hashes = vec![];
+
+        for table_schema in table_schemata {
+            if cancelled.load(Ordering::SeqCst) {
+                return Err(anyhow!(Error::Cancelled));
+            }
+
+            progress.current_table = Some(table_schema.table_name.clone());
+            on_progress(&progress);
+
+            let incremental = match (project.options.watermark_cols.get(&table_schema.table_name), &parent_snapshot_id) {
+                (Some(watermark_col_name), Some(parent_snapshot_id)) => Some((&mut *conn, watermark_col_name, parent_snapshot_id)),
+                _ => None,
+            };
+            let (table_snapshot, rows_fetched) = dump_table(&mut adapter, &table_schema, incremental)?;
+            progress.rows_fetched += rows_fetched;
+
+            insert_table_snapshot(conn, &snapshot_id, &table_snapshot)?;
+            table_hashes.push((table_snapshot.table_name, table_snapshot.hash));
+
+            progress.tables_done += 1;
+            on_progress(&progress);
+        }
+
+        if let Some(previous_snapshot_id) = previous_snapshot_id {
+            let previous_table_hashes = find_table_hashes(conn, previous_snapshot_id)?;
+            if table_hashes.into_iter().sorted().collect_vec() == previous_table_hashes.into_iter().sorted().collect_vec() {
+                return Err(anyhow!(Unchanged));
+            }
+        }
+        Ok(())
+    });
+
+    match result {
+        Ok(()) => Ok(Some(snapshot_id)),
+        Err(e) if e.is::<Unchanged>() => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+// 前回と変わらなかった場合は、このエラーでロールバックさせてから None にする
+#[derive(Debug)]
+struct Unchanged;
+
+impl Display for Unchanged {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unchanged")
+    }
+}
+
+impl std::error::Error for Unchanged {}
+
+// 保存せずにメモリ上にだけダンプする
+pub fn dump_in_memory(project: &Project, key: &CredentialKey) -> anyhow::Result<Vec<TableSnapshot>> {
+    let password = key.decrypt(&project.password)?;
+
+    let mut adapter = match &project.rdbms {
+        Mysql => TargetDbMysql80::new(project, &password),
+    }?;
+
+    adapter
+        .get_table_schemata()?
+        .into_iter()
+        .map(|table_schema| dump_table(&mut adapter, &table_schema, None).map(|(table_snapshot, _)| table_snapshot))
+        .collect()
+}
+
+// ひとつのテーブルを取得する
+// incremental にはスナップショットの保存先と、ウォーターマーク列と元にするスナップショットを渡す
+// 戻り値の 2 つ目は実際に取得した行数
+fn dump_table(
+    adapter: &mut impl TargetDbAdapter,
+    table_schema: &TableSchema,
+    incremental: Option<(&mut Conn, &ColName, &SnapshotId)>,
+) -> anyhow::Result<(TableSnapshot, usize)> {
+    let col_schemata = adapter.get_col_schemata(table_schema)?;
+
+    let (row_snapshots, rows_fetched) = match incremental {
+        Some((conn, watermark_col_name, parent_snapshot_id)) => {
+            get_row_snapshots_incrementally(conn, adapter, table_schema, &col_schemata, watermark_col_name, parent_snapshot_id)?
+        }
+        None => {
+            let row_snapshots = adapter.get_row_snapshots(table_schema, &col_schemata)?;
+            let rows_fetched = row_snapshots.len();
+            (row_snapshots, rows_fetched)
+        }
+    };
+
+    let foreign_keys = adapter.get_foreign_keys(table_schema)?;
+
+    let (primary_col_name, col_names) = col_schemata.get_all_col_names();
+    let table_snapshot = TableSnapshot::new(&table_schema.table_name, primary_col_name, col_names, row_snapshots).with_foreign_keys(foreign_keys);
+
+    Ok((table_snapshot, rows_fetched))
+}
+
+// 前回から変更された行と現在の主キーだけを取得し、前回のテーブルスナップショットの行と組み合わせる
+// 列が変わっている場合など、全件取得と同じ結果にできない場合は全件を取得する
+// 戻り値の 2 つ目は実際に取得した行数
+fn get_row_snapshots_incrementally(
+    conn: &mut Conn,
+    adapter: &mut impl TargetDbAdapter,
+    table_schema: &TableSchema,
+    col_schemata: &ColumnSchemata,
+    watermark_col_name: &ColName,
+    parent_snapshot_id: &SnapshotId,
+) -> anyhow::Result<(Vec<RowSnapshot>, usize)> {
+    let parent = find_table_snapshot(conn, parent_snapshot_id, &table_schema.table_name)?.filter(|parent| {
+        parent.primary_col_name == col_schemata.primary_col.col_name && parent.col_names.iter().eq(col_schemata.cols.iter().map(|col| &col.col_name))
+    });
+
+    if let Some(parent) = parent {
+        if let Some(watermark) = max_watermark(&parent, watermark_col_name) {
+            let primary_col_values = adapter.get_primary_col_values(table_schema, col_schemata)?;
+            let changed_row_snapshots = adapter.get_row_snapshots_since(table_schema, col_schemata, watermark_col_name, &watermark)?;
+            let rows_fetched = changed_row_snapshots.len();
+
+            if let Some(row_snapshots) = compose_row_snapshots(parent, primary_col_values, changed_row_snapshots) {
+                return Ok((row_snapshots, rows_fetched));
+            }
+        }
+    }
+
+    let row_snapshots = adapter.get_row_snapshots(table_schema, col_schemata)?;
+    let rows_fetched = row_snapshots.len();
+    Ok((row_snapshots, rows_fetched))
+}
+
+// 数値は文字列のままでは大小を比べられないので、数値として比べる
+fn max_watermark(table_snapshot: &TableSnapshot, watermark_col_name: &ColName) -> Option<String> {
+    let i = table_snapshot.col_names.iter().position(|col_name| col_name == watermark_col_name)?;
+
+    let max = table_snapshot
+        .row_snapshots
+        .iter()
+        .map(|row_snapshot| &row_snapshot.col_values[i])
+        .filter(|&col_value| col_value != &Null)
+        .max_by(|a, b| a.cmp_value(b))?;
+
+    match max {
+        SimpleNumber(value) | DateString(value) | SimpleString(value) => Some(value.clone()),
+        _ => None,
+    }
+}
+
+// 主キーの並びに従って、変更された行は新しい値を、それ以外は前回の行を使う
+// どちらにもない主キーがある場合は、ウォーターマーク列が更新されずに追加された行があるので None を返す
+fn compose_row_snapshots(
+    parent: TableSnapshot,
+    primary_col_values: Vec<PrimaryColValue>,
+    changed_row_snapshots: Vec<RowSnapshot>,
+) -> Option<Vec<RowSnapshot>> {
+    let mut parent_rows: HashMap<PrimaryColValue, RowSnapshot> =
+        parent.row_snapshots.into_iter().map(|row_snapshot| (row_snapshot.primary_col_value.clone(), row_snapshot)).collect();
+    let mut changed_rows: HashMap<PrimaryColValue, RowSnapshot> =
+        changed_row_snapshots.into_iter().map(|row_snapshot| (row_snapshot.primary_col_value.clone(), row_snapshot)).collect();
+
+    primary_col_values
+        .into_iter()
+        .map(|primary_col_value| changed_rows.remove(&primary_col_value).or_else(|| parent_rows.remove(&primary_col_value)))
+        .collect()
+}
+
+// 接続できなかった場合もエラーにはせず、レポートに含めて返す
+pub fn test_connection(project: &Project, key: &CredentialKey) -> ConnectionReport {
+    let password = match key.decrypt(&project.password) {
+        Ok(password) => password,
+        Err(e) => return ConnectionReport::failed(e.to_string()),
+    };
+
+    let adapter = match &project.rdbms {
+        Mysql => TargetDbMysql80::new(project, &password),
+    };
+
+    match adapter.and_then(|mut adapter| adapter.diagnose()) {
+        Ok(report) => report,
+        Err(e) => ConnectionReport::failed(e.to_string()),
+    }
+}
+
+pub fn current_binlog_position(project: &Project, key: &CredentialKey) -> anyhow::Result<BinlogPosition> {
+    let password = key.decrypt(&project.password)?;
+
+    let mut adapter = match &project.rdbms {
+        Mysql => TargetDbMysql80::new(project, &password),
+    }?;
+
+    adapter.get_binlog_position()
+}
+
+// テーブル全体を読み直さずに、二つの binlog 位置の間で変更された行だけの差分を作る
+// スナップショットは保存しないので、差分の snapshot_id には binlog 位置を入れる
+pub fn binlog_diff(project: &Project, key: &CredentialKey, from: &BinlogPosition, to: &BinlogPosition) -> anyhow::Result<BinlogDiff> {
+    let password = key.decrypt(&project.password)?;
+
+    let mut adapter = match &project.rdbms {
+        Mysql => TargetDbMysql80::new(project, &password),
+    }?;
+
+    let table_diffs = adapter.get_binlog_diffs(from, to)?;
+
+    Ok(BinlogDiff::new(from, to, table_diffs))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+
+    use crate::credential::CredentialKey;
+    use crate::db::create_connection;
+    use crate::db::project::insert_project;
+    use crate::db::snapshot::{all_snapshot_summaries, find_table_snapshots};
+    use crate::domain::diff::ColDiff::{Added, Deleted};
+    use crate::domain::fixture::{d, mk_table_snapshot, n, s};
+    use crate::domain::project::Rdbms::Mysql;
+    use crate::domain::project::{create_project_id, ConnectionOptions, Project};
+    use crate::domain::snapshot::ColValue::Null;
+    use crate::domain::snapshot::{ColValue, RowSnapshot, TableSnapshot};
+    use crate::dump::testdata::create_schema;
+    use crate::dump::{binlog_diff, compose_row_snapshots, current_binlog_position, dump, dump_in_memory, max_watermark};
+    use crate::error::Error;
+
+    #[test]
+    fn dump_and_cancel() -> anyhow::Result<()> {
+        // setup
+
+        let mut target = create_schema("testdata_dump")?;
+        target.query("create table users ( id int, name varchar(10), primary key (id) )")?;
+        target.query("insert into users values (1, 'john'), (2, 'jane')")?;
+        target.query("create table items ( id int, name varchar(10), primary key (id) )")?;
+        target.query("insert into items values (1, 'pc')")?;
+
+        let mut conn = create_connection()?;
+
+        let key = CredentialKey::load_or_create(&std::env::temp_dir().join(create_project_id()))?;
 
-        let row_snapshots = adapter.get_row_snapshots(&table_schema, &col_schemata)?;
+        let project =
+            Project::new(&create_project_id(), "test-project", Mysql, "user", &key.encrypt("password")?, "127.0.0.1", "19001", "testdata_dump");
+        insert_project(&mut conn, &project)?;
 
-        let (primary_col_name, col_names) = col_schemata.get_all_col_names();
-        let table_snapshot = TableSnapshot::new(&table_schema.table_name, primary_col_name, col_names, row_snapshots);
+        // cancel
+        let e = dump(&mut conn, &project, &key, "cancelled".to_string(), &AtomicBool::new(true), |_| {}).unwrap_err();
+        assert_eq!(Error::Cancelled, Error::from(e));
 
-        insert_table_snapshot(conn, &snapshot_id, &table_snapshot)?;
+        assert_eq!(0, all_snapshot_summaries(&mut conn, &project.project_id)?.len());
+
+        // dump
+        let mut progresses = vec![];
+        let snapshot_id =
+            dump(&mut conn, &project, &key, "completed".to_string(), &AtomicBool::new(false), |progress| progresses.push(progress.clone()))?;
+
+        let table_snapshots = find_table_snapshots(&mut conn, &snapshot_id)?;
+        let last = progresses.last().unwrap();
+        assert_eq!(table_snapshots.len(), last.table_count);
+        assert_eq!(table_snapshots.len(), last.tables_done);
+        assert_eq!(table_snapshots.iter().map(|table_snapshot| table_snapshot.row_snapshots.len()).sum::<usize>(), last.rows_fetched);
+
+        assert_eq!(1, all_snapshot_summaries(&mut conn, &project.project_id)?.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn in_memory() -> anyhow::Result<()> {
+        // setup
+
+        let mut target = create_schema("testdata_memory")?;
+        target.query("create table users ( id int, name varchar(10), primary key (id) )")?;
+        target.query("insert into users values (1, 'john'), (2, 'jane')")?;
+
+        let mut conn = create_connection()?;
+
+        let key = CredentialKey::load_or_create(&std::env::temp_dir().join(create_project_id()))?;
+
+        let project =
+            Project::new(&create_project_id(), "test-project", Mysql, "user", &key.encrypt("password")?, "127.0.0.1", "19001", "testdata_memory");
+        insert_project(&mut conn, &project)?;
+
+        // dump
+        let table_snapshots = dump_in_memory(&project, &key)?;
+
+        assert_eq!(0, all_snapshot_summaries(&mut conn, &project.project_id)?.len());
+
+        let snapshot_id = dump(&mut conn, &project, &key, "saved".to_string(), &AtomicBool::new(false), |_| {})?;
+        assert_eq!(find_table_snapshots(&mut conn, &snapshot_id)?, table_snapshots);
+
+        Ok(())
     }
-    let snapshot_summary = SnapshotSummary::create(&snapshot_id, &snapshot_name);
-    insert_snapshot_summary(conn, &project.project_id, &snapshot_summary)?;
 
-    Ok(snapshot_id)
+    // mysqlbinlog コマンドと、testdata-mysql80 で binlog が有効になっている必要がある
+    #[test]
+    fn read_binlog_diff() -> anyhow::Result<()> {
+        let key = CredentialKey::load_or_create(&std::env::temp_dir().join(create_project_id()))?;
+
+        let project =
+            Project::new(&create_project_id(), "test-project", Mysql, "user", &key.encrypt("password")?, "127.0.0.1", "19001", "testdata_binlog");
+
+        let mut conn = create_schema("testdata_binlog")?;
+        conn.query("create table binlog_users ( id int, name varchar(10), primary key (id) )")?;
+        conn.query("insert into binlog_users values (1, 'john'), (2, 'jane'), (3, 'jack')")?;
+
+        let from = current_binlog_position(&project, &key)?;
+
+        conn.query("update binlog_users set name = 'johnny' where id = 1")?;
+        conn.query("delete from binlog_users where id = 2")?;
+        conn.query("insert into binlog_users values (4, 'jill')")?;
+        // 途中で戻した変更は差分に現れない
+        conn.query("update binlog_users set name = 'x' where id = 3")?;
+        conn.query("update binlog_users set name = 'jack' where id = 3")?;
+
+        let to = current_binlog_position(&project, &key)?;
+
+        let binlog_diff = binlog_diff(&project, &key, &from, &to)?;
+        conn.query("drop table binlog_users")?;
+
+        assert_eq!(from, binlog_diff.from);
+        assert_eq!(to, binlog_diff.to);
+
+        let table_diff = binlog_diff.table_diffs.iter().find(|table_diff| table_diff.table_name == "binlog_users").unwrap();
+        assert_eq!(vec![n("1"), n("2"), n("4")], table_diff.primary_col_values);
+        assert_eq!(Some(&Deleted(s("john"))), table_diff.row_diffs1["1"].get("name"));
+        assert_eq!(Some(&Added(s("johnny"))), table_diff.row_diffs2["1"].get("name"));
+        assert_eq!(Some(&Deleted(s("jane"))), table_diff.row_diffs1["2"].get("name"));
+        assert_eq!(Some(&Added(s("jill"))), table_diff.row_diffs2["4"].get("name"));
+        assert!(!table_diff.row_diffs1.contains_key("3"));
+
+        Ok(())
+    }
+
+    fn table_snapshot(rows: Vec<Vec<ColValue>>) -> TableSnapshot {
+        mk_table_snapshot("users", vec!["name", "version", "updated_at"], rows)
+    }
+
+    #[test]
+    fn watermark() {
+        let parent = table_snapshot(vec![
+            vec![n("1"), s("john"), n("9"), d("2023-07-03 09:00:00")],
+            vec![n("2"), s("jane"), n("10"), Null],
+            vec![n("3"), s("jack"), n("2"), d("2023-07-01 09:00:00")],
+        ]);
+
+        assert_eq!(Some("10".to_string()), max_watermark(&parent, &"version".to_string()));
+        assert_eq!(Some("2023-07-03 09:00:00".to_string()), max_watermark(&parent, &"updated_at".to_string()));
+        assert_eq!(None, max_watermark(&parent, &"deleted_at".to_string()));
+        assert_eq!(None, max_watermark(&table_snapshot(vec![]), &"updated_at".to_string()));
+
+        // f64 では区別できない桁の違いも比べる
+        let unsigned =
+            table_snapshot(vec![vec![n("1"), s("john"), n("18446744073709551615"), Null], vec![n("2"), s("jane"), n("18446744073709551614"), Null]]);
+        assert_eq!(Some("18446744073709551615".to_string()), max_watermark(&unsigned, &"version".to_string()));
+    }
+
+    #[test]
+    fn compose() {
+        let parent = || {
+            table_snapshot(vec![
+                vec![n("1"), s("john"), n("1"), d("2023-07-01 09:00:00")],
+                vec![n("2"), s("jane"), n("1"), d("2023-07-01 09:00:00")],
+                vec![n("3"), s("jack"), n("1"), d("2023-07-01 09:00:00")],
+            ])
+        };
+        let changed = || {
+            vec![
+                RowSnapshot::new(vec![n("1"), s("johnny"), n("2"), d("2023-07-03 09:00:00")]),
+                RowSnapshot::new(vec![n("4"), s("jill"), n("1"), d("2023-07-03 09:00:00")]),
+            ]
+        };
+
+        let row_snapshots = compose_row_snapshots(parent(), vec![n("1"), n("3"), n("4")], changed()).unwrap();
+        assert_eq!(
+            table_snapshot(vec![
+                vec![n("1"), s("johnny"), n("2"), d("2023-07-03 09:00:00")],
+                vec![n("3"), s("jack"), n("1"), d("2023-07-01 09:00:00")],
+                vec![n("4"), s("jill"), n("1"), d("2023-07-03 09:00:00")],
+            ]),
+            TableSnapshot::new(&"users".to_string(), "id".to_string(), parent().col_names, row_snapshots)
+        );
+
+        // ウォーターマーク列を更新せずに追加された行があると組み立てられない
+        assert_eq!(None, compose_row_snapshots(parent(), vec![n("1"), n("5")], changed()));
+    }
+
+    #[test]
+    fn incremental_dump() -> anyhow::Result<()> {
+        // setup
+
+        let mut conn = create_connection()?;
+
+        let key = CredentialKey::load_or_create(&std::env::temp_dir().join(create_project_id()))?;
+
+        let full = Project::new(&create_project_id(), "full", Mysql, "user", &key.encrypt("password")?, "127.0.0.1", "19001", "testdata_incremental");
+        insert_project(&mut conn, &full)?;
+
+        let incremental =
+            Project::new(&create_project_id(), "incremental", Mysql, "user", &key.encrypt("password")?, "127.0.0.1", "19001", "testdata_incremental")
+                .with_options(ConnectionOptions {
+                    watermark_cols: vec![("watermark_users".to_string(), "updated_at".to_string())].into_iter().collect(),
+                    ..Default::default()
+                });
+        insert_project(&mut conn, &incremental)?;
+
+        let mut target = create_schema("testdata_incremental")?;
+        target.query("create table watermark_users ( id int, name varchar(10), updated_at datetime, primary key (id) )")?;
+        target.query(
+            "insert into watermark_users values (1, 'john', '2023-07-01 09:00:00'), (2, 'jane', '2023-07-01 09:00:00'), (3, 'jack', '2023-07-02 09:00:00')",
+        )?;
+
+        // 最初は元になるスナップショットがないので全件を取得する
+        dump(&mut conn, &incremental, &key, "parent".to_string(), &AtomicBool::new(false), |_| {})?;
+
+        target.query("update watermark_users set name = 'johnny', updated_at = '2023-07-03 09:00:00' where id = 1")?;
+        target.query("delete from watermark_users where id = 2")?;
+        target.query("insert into watermark_users values (4, 'jill', '2023-07-03 09:00:00'), (5, 'joe', null)")?;
+
+        let incremental_snapshot_id = dump(&mut conn, &incremental, &key, "incremental".to_string(), &AtomicBool::new(false), |_| {})?;
+        let full_snapshot_id = dump(&mut conn, &full, &key, "full".to_string(), &AtomicBool::new(false), |_| {})?;
+
+        target.query("drop table watermark_users")?;
+
+        let find = |conn: &mut mysql::Conn, snapshot_id| -> anyhow::Result<TableSnapshot> {
+            Ok(find_table_snapshots(conn, snapshot_id)?.into_iter().find(|table_snapshot| table_snapshot.table_name == "watermark_users").unwrap())
+        };
+        let incremental_snapshot = find(&mut conn, &incremental_snapshot_id)?;
+        assert_eq!(find(&mut conn, &full_snapshot_id)?, incremental_snapshot);
+        assert_eq!(vec![&n("1"), &n("3"), &n("4"), &n("5")], incremental_snapshot.get_primary_col_values());
+
+        Ok(())
+    }
 }
@@ -1,118 +1,443 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::time::Duration;
+
 use anyhow::anyhow;
 use itertools::Itertools;
 use mysql::Value::NULL;
-use mysql::{from_row, from_value, Conn, Opts, OptsBuilder, Value};
+use mysql::{from_row_opt, from_value_opt, Conn, Opts, OptsBuilder, Row, Value};
 use r2d2::ManageConnection;
 use r2d2_mysql::MysqlConnectionManager;
 
-use crate::domain::project::Project;
-use crate::domain::schema::{ColumnSchema, ColumnSchemata, TableSchema};
+use crate::domain::diagnosis::{ConnectionReport, TableReport, UnsupportedCol};
+use crate::domain::diff::{create_table_diff, TableDiff};
+use crate::domain::project::{ConnectionOptions, Project, SslMode};
+use crate::domain::schema::{ColName, ColumnSchema, ColumnSchemata, ForeignKey, TableName, TableSchema};
 use crate::domain::snapshot::ColValue::*;
-use crate::domain::snapshot::{ColValue, RowSnapshot};
+use crate::domain::snapshot::{ColValue, PrimaryColValue, RowSnapshot, TableSnapshot};
 use crate::dump::adapter::TargetDbAdapter;
+use crate::dump::binlog::{normalize_binlog_value, parse_row_events, run_mysqlbinlog, BinlogPosition, BinlogValue, RowEvent};
+use crate::dump::tunnel::Tunnel;
+use crate::error::Error;
 
 pub struct TargetDbMysql80 {
     conn: Conn,
     schema: String,
+    // mysqlbinlog にも同じ接続先を渡すため、トンネル適用後のものを持っておく
+    opts: Opts,
+    options: ConnectionOptions,
+    // 接続中はトンネルを開いたままにする
+    _tunnel: Option<Tunnel>,
 }
 
 impl TargetDbMysql80 {
-    pub fn new(project: &Project) -> anyhow::Result<Self> {
-        let conn = TargetDbMysql80::create_connection(project)?;
-        let schema = project.schema.clone();
+    pub fn new(project: &Project, password: &str) -> anyhow::Result<Self> {
+        let opts = TargetDbMysql80::create_opts(project, password)?;
+        let schema = opts.get_db_name().map(|db_name| db_name.to_string()).unwrap_or_else(|| project.schema.clone());
+
+        let (opts, tunnel) = match &project.options.ssh {
+            Some(ssh) => {
+                let remote_host = opts.get_ip_or_hostname().ok_or_else(|| anyhow!("host is required to connect through ssh"))?;
+                let tunnel = Tunnel::open(ssh, remote_host, opts.get_tcp_port())?;
+
+                let mut builder = OptsBuilder::from_opts(opts);
+                builder.ip_or_hostname(Some("127.0.0.1")).tcp_port(tunnel.local_port).socket(None::<String>).prefer_socket(false);
+                (builder.into(), Some(tunnel))
+            }
+            None => (opts, None),
+        };
+
+        let conn = TargetDbMysql80::create_connection(opts.clone())?;
+
+        Ok(Self { conn, schema, opts, options: project.options.clone(), _tunnel: tunnel })
+    }
+
+    pub fn get_binlog_position(&mut self) -> anyhow::Result<BinlogPosition> {
+        let row = self.conn.first::<_, Row>("show master status")?.ok_or_else(|| anyhow!("binary logging is disabled"))?;
+        let file = row.get(0).ok_or_else(|| anyhow!("binlog file is missing"))?;
+        let position = row.get(1).ok_or_else(|| anyhow!("binlog position is missing"))?;
+        Ok(BinlogPosition { file, position })
+    }
+
+    // ファイル名は連番なので、文字列の比較で範囲内のファイルを選べる
+    fn get_binlog_files(&mut self, from: &BinlogPosition, to: &BinlogPosition) -> anyhow::Result<Vec<String>> {
+        let files: Vec<String> =
+            self.conn.query("show binary logs")?.map(|row| row?.get(0).ok_or_else(|| anyhow!("binlog file is missing"))).try_collect()?;
+        Ok(files.into_iter().filter(|file| &from.file <= file && file <= &to.file).collect())
+    }
+
+    // 現在のテーブル定義で読むので、二つの位置の間で列を変更したテーブルは正しく読めない
+    pub fn get_binlog_diffs(&mut self, from: &BinlogPosition, to: &BinlogPosition) -> anyhow::Result<Vec<TableDiff>> {
+        let files = self.get_binlog_files(from, to)?;
+        if files.is_empty() {
+            return Err(anyhow!("binlog between {from} and {to} is not found"));
+        }
+
+        let output = run_mysqlbinlog(&self.opts, &self.options, &files, from, to)?;
+        let events = parse_row_events(&output)?.into_iter().filter(|event| event.schema == self.schema).collect_vec();
+
+        let table_names = events.iter().map(|event| event.table_name.clone()).unique().sorted().collect_vec();
+        table_names
+            .into_iter()
+            .map(|table_name| {
+                let table_events = events.iter().filter(|event| event.table_name == table_name).collect_vec();
+                self.create_binlog_table_diff(&table_name, &table_events)
+            })
+            .collect()
+    }
+
+    fn create_binlog_table_diff(&mut self, table_name: &TableName, events: &[&RowEvent]) -> anyhow::Result<TableDiff> {
+        let col_schemata = self.get_col_schemata(&TableSchema { table_name: table_name.clone() })?;
+
+        // binlog の値は定義順に並んでいるので、スナップショットの列の位置に対応させる
+        let ordinals: HashMap<String, usize> = self
+            .conn
+            .prep_exec(
+                "select column_name, ordinal_position from information_schema.columns where table_schema = ? and table_name = ?",
+                (&self.schema, table_name),
+            )?
+            .map(|row| row.and_then(|row| Ok(from_row_opt(row)?)))
+            .try_collect()?;
+        let cols = col_schemata.get_all_col_refs();
+        let indexes = cols
+            .iter()
+            .map(|col| {
+                ordinals.get(&col.col_name).map(|ordinal| ordinal - 1).ok_or_else(|| anyhow!("column {} is not found in {table_name}", col.col_name))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        // 同じ行への複数の変更は、最初の変更前と最後の変更後にまとめる
+        let mut rows: HashMap<PrimaryColValue, (Option<RowSnapshot>, Option<RowSnapshot>)> = HashMap::new();
+        for event in events {
+            if let Some(values) = &event.before {
+                let row_snapshot = self.read_row_snapshot(table_name, &cols, &indexes, values)?;
+                match rows.entry(row_snapshot.primary_col_value.clone()) {
+                    Entry::Occupied(mut entry) => entry.get_mut().1 = None,
+                    Entry::Vacant(entry) => {
+                        entry.insert((Some(row_snapshot), None));
+                    }
+                }
+            }
+            if let Some(values) = &event.after {
+                let row_snapshot = self.read_row_snapshot(table_name, &cols, &indexes, values)?;
+                let primary_col_value = row_snapshot.primary_col_value.clone();
+                rows.entry(primary_col_value).or_insert((None, None)).1 = Some(row_snapshot);
+            }
+        }
+
+        let (before, after): (Vec<_>, Vec<_>) = rows.into_values().filter(|(before, after)| before != after).unzip();
+
+        let (primary_col_name, col_names) = col_schemata.get_all_col_names();
+        let before = TableSnapshot::new(table_name, primary_col_name.clone(), col_names.clone(), before.into_iter().flatten().collect());
+        let after = TableSnapshot::new(table_name, primary_col_name, col_names, after.into_iter().flatten().collect());
+
+        Ok(create_table_diff(Some(&before), Some(&after)))
+    }
+
+    fn read_row_snapshot(
+        &mut self,
+        table_name: &TableName,
+        cols: &[&ColumnSchema],
+        indexes: &[usize],
+        values: &[BinlogValue],
+    ) -> anyhow::Result<RowSnapshot> {
+        let col_values = cols
+            .iter()
+            .zip(indexes)
+            .map(|(col, &i)| match values.get(i).ok_or_else(|| anyhow!("column {} is missing in binlog of {table_name}", col.col_name))? {
+                None => Ok(Null),
+                // timestamp は UNIX 時間で記録されているので、SELECT と同じセッションのタイムゾーンで変換する
+                Some(value) if col.data_type == "timestamp" => {
+                    let value = self
+                        .conn
+                        .first_exec::<_, _, String>("select cast(from_unixtime(?) as char)", (value,))?
+                        .ok_or_else(|| anyhow!("invalid timestamp: {value}"))?;
+                    Ok(parse_col_value(col, value))
+                }
+                Some(value) => Ok(parse_col_value(col, normalize_binlog_value(col, value)?)),
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(RowSnapshot::new(col_values))
+    }
+
+    // テーブルのハッシュは行の並びで変わるので、全件でも差分でも主キー順で取得する
+    fn select_row_snapshots(
+        &mut self,
+        table_schema: &TableSchema,
+        column_schemata: &ColumnSchemata,
+        condition: &str,
+    ) -> anyhow::Result<Vec<RowSnapshot>> {
+        let cols = column_schemata.get_all_col_refs();
+
+        self.conn
+            .query(format!(
+                "select {} from {} {condition} order by {}",
+                cols.iter().map(|col| as_select_col(col)).join(","),
+                quote(&table_schema.table_name),
+                quote(&column_schemata.primary_col.col_name)
+            ))?
+            .map(|row| {
+                let row = row?;
+                let col_values = cols
+                    .iter()
+                    .enumerate()
+                    .map(|(i, col)| {
+                        let value: Value = row.get(i).ok_or_else(|| anyhow!("column {} is missing in {}", col.col_name, table_schema.table_name))?;
+                        if value == NULL {
+                            Ok(Null)
+                        } else {
+                            let value = from_value_opt::<String>(value).map_err(|e| {
+                                Error::UnsupportedType(format!("{}.{} ({}): {e}", table_schema.table_name, col.col_name, col.column_type))
+                            })?;
+                            Ok(parse_col_value(col, value))
+                        }
+                    })
+                    .collect::<anyhow::Result<_>>()?;
+                Ok(RowSnapshot::new(col_values))
+            })
+            .collect()
+    }
 
-        Ok(Self { conn, schema })
+    fn create_opts(project: &Project, password: &str) -> anyhow::Result<Opts> {
+        let options = &project.options;
+        options.validate()?;
+
+        let mut builder = match &options.url {
+            Some(url) => OptsBuilder::from_opts(Opts::from_url(url).map_err(|e| anyhow!("invalid connection url: {e}"))?),
+            None => {
+                let port = project.port.parse::<u16>().map_err(|_| anyhow!("invalid port: {}", project.port))?;
+                let mut builder = OptsBuilder::new();
+                builder.ip_or_hostname(Some(&project.host)).tcp_port(port).user(Some(&project.user)).db_name(Some(&project.schema));
+                builder
+            }
+        };
+
+        if !password.is_empty() {
+            builder.pass(Some(password));
+        }
+        if let Some(socket) = &options.socket {
+            builder.socket(Some(socket));
+        }
+        builder
+            .tcp_connect_timeout(options.connect_timeout_sec.map(Duration::from_secs))
+            .read_timeout(options.read_timeout_sec.map(Duration::from_secs));
+
+        let mut init = vec![];
+        if let Some(charset) = &options.charset {
+            init.push(format!("set names {charset}"));
+        }
+        init.extend(options.init_sql.iter().cloned());
+        builder.init(init);
+
+        apply_ssl_opts(&mut builder, options)?;
+
+        Ok(builder.into())
     }
 
-    fn create_connection(project: &Project) -> anyhow::Result<Conn> {
-        let url = format!("mysql://{}:{}@{}:{}/{}", project.user, project.password, project.host, project.port, project.schema);
-        let opt = Opts::from_url(&url).unwrap();
-        let builder = OptsBuilder::from_opts(opt);
-        let manager = MysqlConnectionManager::new(builder);
-        manager.connect().map_err(|e| anyhow!(e))
+    fn create_connection(opts: Opts) -> anyhow::Result<Conn> {
+        let manager = MysqlConnectionManager::new(OptsBuilder::from_opts(opts));
+        manager.connect().map_err(|e| anyhow!(Error::Connection(e.to_string())))
     }
 }
 
 impl TargetDbAdapter for TargetDbMysql80 {
     fn get_table_schemata(&mut self) -> anyhow::Result<Vec<TableSchema>> {
         self.conn
-            .query(format!("select table_name from information_schema.tables where table_schema = '{}' order by table_name", self.schema))
-            .map(|result| {
-                result
-                    .map(|x| x.unwrap())
-                    .map(|row| {
-                        let table_name = from_row(row);
-                        TableSchema { table_name }
-                    })
-                    .collect()
+            .query(format!("select table_name from information_schema.tables where table_schema = '{}' order by table_name", self.schema))?
+            .map(|row| {
+                let table_name = from_row_opt(row?)?;
+                Ok(TableSchema { table_name })
             })
-            .map_err(|e| anyhow!(e))
+            .collect()
     }
 
     fn get_col_schemata(&mut self, table_schema: &TableSchema) -> anyhow::Result<ColumnSchemata> {
         let unique_cols: Vec<ColumnSchema> = self.conn.query(
-            format!("select column_name, data_type, column_type from information_schema.columns where table_schema = '{}' and table_name = '{}' and column_key = 'PRI'", self.schema, table_schema.table_name))
-            .map(|result| {
-                result
-                    .map(|x| x.unwrap())
-                    .map(|row| {
-                        let (column_name, data_type, column_type) = from_row(row);
-                        ColumnSchema { col_name: column_name, data_type, column_type }
-                    })
-                    .collect_vec()
-            }).map_err(|e| anyhow!(e))?;
+            format!("select column_name, data_type, column_type from information_schema.columns where table_schema = '{}' and table_name = '{}' and column_key = 'PRI'", self.schema, table_schema.table_name))?
+            .map(|row| {
+                let (column_name, data_type, column_type) = from_row_opt(row?)?;
+                Ok(ColumnSchema { col_name: column_name, data_type, column_type })
+            })
+            .collect::<anyhow::Result<_>>()?;
         // todo: case [ no primary col ]
-        let primary_col = unique_cols[0].clone();
+        let primary_col = unique_cols.first().cloned().ok_or_else(|| anyhow!("table {} has no primary key", table_schema.table_name))?;
 
         let cols: Vec<ColumnSchema> = self.conn.query(
-            format!("select column_name, data_type, column_type from information_schema.columns where table_schema = '{}' and table_name = '{}' and column_key = '' order by ordinal_position", self.schema, table_schema.table_name))
-            .map(|result| {
-                result
-                    .map(|x| x.unwrap())
-                    .map(|row| {
-                        let (column_name, data_type, column_type) = from_row(row);
-                        ColumnSchema { col_name: column_name, data_type, column_type }
-                    })
-                    .collect_vec()
-            }).map_err(|e| anyhow!(e))?;
+            format!("select column_name, data_type, column_type from information_schema.columns where table_schema = '{}' and table_name = '{}' and column_key = '' order by ordinal_position", self.schema, table_schema.table_name))?
+            .map(|row| {
+                let (column_name, data_type, column_type) = from_row_opt(row?)?;
+                Ok(ColumnSchema { col_name: column_name, data_type, column_type })
+            })
+            .collect::<anyhow::Result<_>>()?;
 
         Ok(ColumnSchemata::new(primary_col, cols))
     }
 
     fn get_row_snapshots(&mut self, table_schema: &TableSchema, column_schemata: &ColumnSchemata) -> anyhow::Result<Vec<RowSnapshot>> {
-        let cols = column_schemata.get_all_col_refs();
+        self.select_row_snapshots(table_schema, column_schemata, "")
+    }
+
+    fn get_row_snapshots_since(
+        &mut self,
+        table_schema: &TableSchema,
+        column_schemata: &ColumnSchemata,
+        watermark_col_name: &ColName,
+        watermark: &str,
+    ) -> anyhow::Result<Vec<RowSnapshot>> {
+        // 列名は設定から来るので、テーブルの列であることを確かめてから識別子として囲む
+        if column_schemata.get_all_col_refs().iter().all(|col| &col.col_name != watermark_col_name) {
+            return Err(anyhow!("watermark column {watermark_col_name} is not found in {}", table_schema.table_name));
+        }
+        let watermark_col_name = quote(watermark_col_name);
+
+        // 値の表現をダンプと揃えるためテキストプロトコルで取得するので、プレースホルダは使わずにエスケープする
+        let watermark = watermark.replace('\\', "\\\\").replace('\'', "''");
+        self.select_row_snapshots(
+            table_schema,
+            column_schemata,
+            &format!("where {watermark_col_name} >= '{watermark}' or {watermark_col_name} is null"),
+        )
+    }
+
+    // 差分だけを取得した行を全件と同じ並びで組み立てられるよう、主キー順で取得する
+    fn get_primary_col_values(&mut self, table_schema: &TableSchema, column_schemata: &ColumnSchemata) -> anyhow::Result<Vec<PrimaryColValue>> {
+        let primary_col = &column_schemata.primary_col;
 
         self.conn
-            .query(format!("select {} from {}", cols.iter().map(|col| as_select_col(col)).join(","), table_schema.table_name))
-            .map(|result| {
-                result
-                    .map(|x| x.unwrap())
-                    .map(|row| {
-                        (0..column_schemata.count())
-                            .map(|i| {
-                                let value: Value = row.get(i).unwrap();
-                                if value == NULL {
-                                    Null
-                                } else {
-                                    parse_col_value(cols[i], from_value(value))
-                                }
-                            })
-                            .collect_vec()
+            .query(format!(
+                "select {} from {} order by {}",
+                as_select_col(primary_col),
+                quote(&table_schema.table_name),
+                quote(&primary_col.col_name)
+            ))?
+            .map(|row| {
+                let value: Option<String> = from_row_opt(row?)?;
+                let value = value.ok_or_else(|| anyhow!("primary key of {} is null", table_schema.table_name))?;
+                Ok(parse_col_value(primary_col, value))
+            })
+            .collect()
+    }
+
+    fn get_foreign_keys(&mut self, table_schema: &TableSchema) -> anyhow::Result<Vec<ForeignKey>> {
+        let rows: Vec<(String, ColName, TableName, ColName)> = self.conn.query(
+            format!("select constraint_name, column_name, referenced_table_name, referenced_column_name from information_schema.key_column_usage where table_schema = '{}' and table_name = '{}' and referenced_table_name is not null order by constraint_name, ordinal_position", self.schema, table_schema.table_name))?
+            .map(|row| Ok(from_row_opt(row?)?))
+            .collect::<anyhow::Result<_>>()?;
+
+        // 複数列からなる外部キーは除く
+        Ok(rows
+            .into_iter()
+            .group_by(|(constraint_name, _, _, _)| constraint_name.clone())
+            .into_iter()
+            .filter_map(|(_, cols)| cols.exactly_one().ok())
+            .map(|(_, col_name, referenced_table_name, referenced_col_name)| ForeignKey { col_name, referenced_table_name, referenced_col_name })
+            .collect())
+    }
+
+    fn diagnose(&mut self) -> anyhow::Result<ConnectionReport> {
+        let server_version: String = self.conn.first("select version()")?.map(from_row_opt).transpose()?.unwrap_or_default();
+
+        // 権限のないスキーマは information_schema に現れない
+        let schema_visible = self
+            .conn
+            .first_exec::<_, _, String>("select schema_name from information_schema.schemata where schema_name = ?", (&self.schema,))?
+            .is_some();
+        if !schema_visible {
+            return Ok(ConnectionReport::connected(server_version, false, vec![]));
+        }
+
+        let tables: Vec<(TableName, Option<u64>)> = self
+            .conn
+            .prep_exec(
+                "select table_name, table_rows from information_schema.tables where table_schema = ? and table_type = 'BASE TABLE' order by table_name",
+                (&self.schema,),
+            )?
+            .map(|row| row.and_then(|row| Ok(from_row_opt(row)?)))
+            .try_collect()?;
+
+        let cols: Vec<(TableName, String, String, String, String)> = self
+            .conn
+            .prep_exec(
+                "select table_name, column_name, data_type, column_type, column_key from information_schema.columns where table_schema = ? order by table_name, ordinal_position",
+                (&self.schema,),
+            )?
+            .map(|row| row.and_then(|row| Ok(from_row_opt(row)?)))
+            .try_collect()?;
+
+        let table_reports = tables
+            .into_iter()
+            .map(|(table_name, table_rows)| {
+                let table_cols = cols.iter().filter(|(col_table_name, ..)| col_table_name == &table_name).collect_vec();
+
+                let has_primary_key = table_cols.iter().any(|(.., column_key)| column_key == "PRI");
+                let unsupported_cols = table_cols
+                    .into_iter()
+                    .map(|(_, col_name, data_type, column_type, _)| ColumnSchema {
+                        col_name: col_name.clone(),
+                        data_type: data_type.clone(),
+                        column_type: column_type.clone(),
                     })
-                    .map(RowSnapshot::new)
-                    .collect()
+                    .filter(|column_schema| !is_supported(column_schema))
+                    .map(|column_schema| UnsupportedCol { col_name: column_schema.col_name, data_type: column_schema.data_type })
+                    .collect_vec();
+
+                // 行を読まずに SELECT 権限だけを確認する
+                let selectable = self.conn.query(format!("select 1 from {} limit 0", quote(&table_name))).is_ok();
+
+                TableReport { table_name, estimated_rows: table_rows.unwrap_or(0), selectable, has_primary_key, unsupported_cols }
             })
-            .map_err(|e| anyhow!(e))
+            .collect_vec();
+
+        Ok(ConnectionReport::connected(server_version, true, table_reports))
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn apply_ssl_opts(builder: &mut OptsBuilder, options: &ConnectionOptions) -> anyhow::Result<()> {
+    if options.ssl_mode == SslMode::Disabled {
+        return Ok(());
+    }
+    // ConnectionOptions::validate で、CA 証明書が指定されていることを確認済み
+    let ssl_ca_path =
+        options.ssl_ca_path.as_ref().ok_or_else(|| anyhow!("ssl ca path is required when ssl mode is {}", options.ssl_mode.as_str()))?;
+    builder.ssl_opts(Some((ssl_ca_path, None::<(String, String)>))).verify_peer(options.ssl_mode == SslMode::VerifyCa);
+    Ok(())
+}
+
+// macOS ではキーチェーンの証明書で検証されるため、ssl_ca_path は使われない
+#[cfg(target_os = "macos")]
+fn apply_ssl_opts(builder: &mut OptsBuilder, options: &ConnectionOptions) -> anyhow::Result<()> {
+    if options.ssl_mode == SslMode::Disabled {
+        return Ok(());
+    }
+    builder.ssl_opts(Some(None::<(String, String, Vec<String>)>)).verify_peer(options.ssl_mode == SslMode::VerifyCa);
+    Ok(())
+}
+
+#[cfg(windows)]
+fn apply_ssl_opts(_: &mut OptsBuilder, options: &ConnectionOptions) -> anyhow::Result<()> {
+    match options.ssl_mode {
+        SslMode::Disabled => Ok(()),
+        _ => Err(anyhow!("ssl is not supported on windows")),
     }
 }
 
 fn as_select_col(col: &ColumnSchema) -> String {
     match col.data_type.as_str() {
-        "bit" => format!("bin({})", col.col_name),
-        _ => col.col_name.to_string(),
+        "bit" => format!("bin({})", quote(&col.col_name)),
+        _ => quote(&col.col_name),
     }
 }
 
+// テーブル名や列名を識別子として囲む
+fn quote(identifier: &str) -> String {
+    format!("`{}`", identifier.replace('`', "``"))
+}
+
+fn is_supported(column_schema: &ColumnSchema) -> bool {
+    parse_col_value(column_schema, String::new()) != ParseError
+}
+
 fn parse_col_value(column_schema: &ColumnSchema, value: String) -> ColValue {
     match column_schema.data_type.as_str() {
         "tinyint" | "smallint" | "mediumint" | "int" | "bigint" => SimpleNumber(value),
@@ -134,26 +459,57 @@ fn parse_col_value(column_schema: &ColumnSchema, value: String) -> ColValue {
 mod adapter_tests {
     use itertools::Itertools;
 
+    use crate::domain::diagnosis::{TableReport, UnsupportedCol};
     use crate::domain::project::{create_project_id, Project};
     use crate::domain::project::Rdbms::Mysql;
+    use crate::domain::schema::TableSchema;
     use crate::domain::snapshot::ColValue::*;
     use crate::dump::adapter::TargetDbAdapter;
     use crate::dump::mysql80::TargetDbMysql80;
+    use crate::dump::testdata::create_schema;
 
     fn s(s: &str) -> String {
         s.to_string()
     }
 
+    #[test]
+    fn diagnose() -> anyhow::Result<()> {
+        create_schema("testdata_diagnose")?;
+
+        let project = Project::new(&create_project_id(), "test-project", Mysql, "user","","127.0.0.1","19001","testdata_diagnose");
+
+        let mut adapter = TargetDbMysql80::new(&project, "password")?;
+
+        adapter.conn.prep_exec("create table 01_ok ( id int auto_increment, name varchar(3), primary key (id) )", ())?;
+        adapter.conn.prep_exec("create table 02_no_primary_key ( id int, name varchar(3) )", ())?;
+        adapter.conn.prep_exec("create table 03_unsupported ( id int auto_increment, col_point point, primary key (id) )", ())?;
+
+        let report = adapter.diagnose()?;
+
+        assert!(report.connected);
+        assert!(report.server_version.unwrap().starts_with("8.0"));
+        assert!(report.schema_visible);
+        assert_eq!(
+            vec![
+                TableReport { table_name: s("01_ok"),             estimated_rows: 0, selectable: true, has_primary_key: true,  unsupported_cols: vec![] },
+                TableReport { table_name: s("02_no_primary_key"), estimated_rows: 0, selectable: true, has_primary_key: false, unsupported_cols: vec![] },
+                TableReport { table_name: s("03_unsupported"),    estimated_rows: 0, selectable: true, has_primary_key: true,  unsupported_cols: vec![UnsupportedCol { col_name: s("col_point"), data_type: s("point") }] },
+            ],
+            report.tables
+        );
+
+        assert!(adapter.get_col_schemata(&TableSchema { table_name: s("02_no_primary_key") }).is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn test() -> anyhow::Result<()> {
-        let project = Project::new(&create_project_id(), "test-project", Mysql, "user","password","127.0.0.1","19001","testdata");
+        create_schema("testdata_adapter")?;
 
-        let mut adapter = TargetDbMysql80::new(&project)?;
-        
-        // drop all
-        for table_schema in adapter.get_table_schemata()? {
-            adapter.conn.prep_exec(format!("drop table {}", table_schema.table_name), ())?;
-        }
+        let project = Project::new(&create_project_id(), "test-project", Mysql, "user","","127.0.0.1","19001","testdata_adapter");
+
+        let mut adapter = TargetDbMysql80::new(&project, "password")?;
 
         adapter.conn.prep_exec("create table 01_number_signed ( id int auto_increment, col_tinyint tinyint, col_smallint smallint, col_mediumint mediumint, col_int int, col_bigint bigint, primary key (id) )", ())?;
         adapter.conn.prep_exec("insert into 01_number_signed values (1, 127, 32767, 8388607, 2147483647, 9223372036854775807), (2, -128, -32768, -8388608, -2147483648, -9223372036854775808)", ())?;
@@ -467,6 +823,73 @@ mod adapter_tests {
     }
 }
 
+#[cfg(test)]
+mod create_opts_tests {
+    use std::time::Duration;
+
+    use crate::domain::project::Rdbms::Mysql;
+    use crate::domain::project::{create_project_id, ConnectionOptions, Project};
+    use crate::dump::mysql80::TargetDbMysql80;
+
+    #[test]
+    fn password_with_reserved_chars() -> anyhow::Result<()> {
+        let project = Project::new(&create_project_id(), "test-project", Mysql, "user", "", "127.0.0.1", "19001", "testdata");
+
+        let opts = TargetDbMysql80::create_opts(&project, "p@ss/w:rd")?;
+
+        assert_eq!(Some("p@ss/w:rd"), opts.get_pass());
+        assert_eq!(Some("127.0.0.1"), opts.get_ip_or_hostname());
+        assert_eq!(19001, opts.get_tcp_port());
+        assert_eq!(Some("testdata"), opts.get_db_name());
+
+        Ok(())
+    }
+
+    #[test]
+    fn options() -> anyhow::Result<()> {
+        let options = ConnectionOptions {
+            socket: Some("/tmp/mysql.sock".to_string()),
+            connect_timeout_sec: Some(5),
+            charset: Some("utf8mb4".to_string()),
+            init_sql: vec!["set time_zone = '+09:00'".to_string()],
+            ..Default::default()
+        };
+        let project = Project::new(&create_project_id(), "test-project", Mysql, "user", "", "127.0.0.1", "19001", "testdata").with_options(options);
+
+        let opts = TargetDbMysql80::create_opts(&project, "")?;
+
+        assert_eq!(None, opts.get_pass());
+        assert_eq!(Some("/tmp/mysql.sock"), opts.get_socket());
+        assert_eq!(Some(Duration::from_secs(5)), opts.get_tcp_connect_timeout());
+        assert_eq!(vec!["set names utf8mb4".to_string(), "set time_zone = '+09:00'".to_string()], opts.get_init());
+
+        Ok(())
+    }
+
+    #[test]
+    fn url() -> anyhow::Result<()> {
+        let options = ConnectionOptions { url: Some("mysql://admin@db.example.com:3307/app".to_string()), ..Default::default() };
+        let project = Project::new(&create_project_id(), "test-project", Mysql, "user", "", "127.0.0.1", "19001", "testdata").with_options(options);
+
+        let opts = TargetDbMysql80::create_opts(&project, "p@ss")?;
+
+        assert_eq!(Some("admin"), opts.get_user());
+        assert_eq!(Some("p@ss"), opts.get_pass());
+        assert_eq!(Some("db.example.com"), opts.get_ip_or_hostname());
+        assert_eq!(3307, opts.get_tcp_port());
+        assert_eq!(Some("app"), opts.get_db_name());
+
+        let options = ConnectionOptions { url: Some("mysql://admin@db.example.com:port/app".to_string()), ..Default::default() };
+        let project = Project::new(&create_project_id(), "test-project", Mysql, "user", "", "127.0.0.1", "19001", "testdata").with_options(options);
+        assert!(TargetDbMysql80::create_opts(&project, "").is_err());
+
+        let project = Project::new(&create_project_id(), "test-project", Mysql, "user", "", "127.0.0.1", "port", "testdata");
+        assert!(TargetDbMysql80::create_opts(&project, "").is_err());
+
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod parse_col_value_tests {
     use crate::domain::schema::ColumnSchema;
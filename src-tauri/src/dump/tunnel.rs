@@ -0,0 +1,133 @@
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader};
+use std::net::{TcpListener, TcpStream};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread::{sleep, spawn};
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+use itertools::Itertools;
+
+use crate::domain::project::SshJump;
+
+const OPEN_TIMEOUT: Duration = Duration::from_secs(10);
+const OPEN_ATTEMPTS: usize = 3;
+const STDERR_LINES: usize = 20;
+
+// ssh コマンドでローカルポートをフォワードし、Drop 時に閉じる
+pub struct Tunnel {
+    child: Child,
+    pub local_port: u16,
+}
+
+impl Tunnel {
+    // 空いているポートを探してから ssh が使うまでの間に他のプロセスに使われた場合は、ポートを変えてやり直す
+    pub fn open(ssh: &SshJump, remote_host: &str, remote_port: u16) -> anyhow::Result<Self> {
+        for _ in 1..OPEN_ATTEMPTS {
+            if let Some(tunnel) = Tunnel::try_open(ssh, remote_host, remote_port)? {
+                return Ok(tunnel);
+            }
+        }
+        Tunnel::try_open(ssh, remote_host, remote_port)?.ok_or_else(|| anyhow!("ssh tunnel to {} could not listen to a local port", ssh.host))
+    }
+
+    // ローカルポートを使えなかった場合は None を返す
+    fn try_open(ssh: &SshJump, remote_host: &str, remote_port: u16) -> anyhow::Result<Option<Self>> {
+        let local_port = find_free_port()?;
+
+        let mut child = Command::new("ssh")
+            .args(["-N", "-o", "BatchMode=yes", "-o", "ExitOnForwardFailure=yes", "-o", "StrictHostKeyChecking=accept-new"])
+            .args(["-i", &ssh.key_path, "-p", &ssh.port.to_string()])
+            .args(["-L", &format!("127.0.0.1:{local_port}:{remote_host}:{remote_port}")])
+            .arg(format!("{}@{}", ssh.user, ssh.host))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| anyhow!("failed to start ssh: {e}"))?;
+
+        // パイプが詰まって ssh が止まらないよう、接続中も読み続けて最後の数行だけを残す
+        let stderr = Arc::new(Mutex::new(VecDeque::new()));
+        let reader = child.stderr.take().map(|pipe| {
+            let stderr = stderr.clone();
+            spawn(move || {
+                for line in BufReader::new(pipe).lines().map_while(Result::ok) {
+                    let mut stderr = stderr.lock().unwrap();
+                    if stderr.len() == STDERR_LINES {
+                        stderr.pop_front();
+                    }
+                    stderr.push_back(line);
+                }
+            })
+        });
+
+        let started_at = Instant::now();
+        loop {
+            if let Some(status) = child.try_wait()? {
+                if let Some(reader) = reader {
+                    let _ = reader.join();
+                }
+                let stderr = stderr.lock().unwrap().iter().join("\n");
+                if stderr.contains("Address already in use") || stderr.contains("cannot listen to port") {
+                    return Ok(None);
+                }
+                return Err(anyhow!("ssh tunnel to {} exited ( {} ): {}", ssh.host, status, stderr.trim()));
+            }
+            if TcpStream::connect(("127.0.0.1", local_port)).is_ok() {
+                return Ok(Some(Self { child, local_port }));
+            }
+            if started_at.elapsed() > OPEN_TIMEOUT {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(anyhow!("ssh tunnel to {} timed out", ssh.host));
+            }
+            sleep(Duration::from_millis(100));
+        }
+    }
+}
+
+impl Drop for Tunnel {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn find_free_port() -> anyhow::Result<u16> {
+    let listener = TcpListener::bind(("127.0.0.1", 0))?;
+    Ok(listener.local_addr()?.port())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::fs::{copy, set_permissions, Permissions};
+    use std::os::unix::fs::PermissionsExt;
+
+    use crate::domain::project::Rdbms::Mysql;
+    use crate::domain::project::{create_project_id, ConnectionOptions, Project, SshJump};
+    use crate::dump::adapter::TargetDbAdapter;
+    use crate::dump::mysql80::TargetDbMysql80;
+
+    // compose の sshd を踏み台にして、compose ネットワーク内の testdata-mysql80 に接続する
+    #[test]
+    fn dump_through_tunnel() -> anyhow::Result<()> {
+        // 鍵は sshd の起動時に生成される
+        // ssh は権限の広い鍵ファイルを拒否するため、コピーして権限を絞る
+        let key_path = std::env::temp_dir().join(format!("{}.key", create_project_id()));
+        copy(concat!(env!("CARGO_MANIFEST_DIR"), "/../docker/sshd/keys/id_ed25519"), &key_path)?;
+        set_permissions(&key_path, Permissions::from_mode(0o600))?;
+
+        let ssh = SshJump::new("127.0.0.1", 19022, "tunnel", key_path.to_str().unwrap());
+        let options = ConnectionOptions { ssh: Some(ssh), ..Default::default() };
+        let project =
+            Project::new(&create_project_id(), "test-project", Mysql, "user", "", "testdata-mysql80", "3306", "testdata").with_options(options);
+
+        let mut adapter = TargetDbMysql80::new(&project, "password")?;
+        adapter.get_table_schemata()?;
+
+        std::fs::remove_file(key_path)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,105 @@
+use std::fmt::{Display, Formatter};
+
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub enum Error {
+    NotFound(String),
+    Connection(String),
+    UnsupportedType(String),
+    CorruptSnapshot(String),
+    Cancelled,
+    InvalidInput(String),
+    Internal(String),
+}
+
+impl Error {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::NotFound(_) => "NOT_FOUND",
+            Error::Connection(_) => "CONNECTION",
+            Error::UnsupportedType(_) => "UNSUPPORTED_TYPE",
+            Error::CorruptSnapshot(_) => "CORRUPT_SNAPSHOT",
+            Error::Cancelled => "CANCELLED",
+            Error::InvalidInput(_) => "INVALID_INPUT",
+            Error::Internal(_) => "INTERNAL",
+        }
+    }
+
+    pub fn invalid_input(e: anyhow::Error) -> Self {
+        Error::InvalidInput(format!("{e:#}"))
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::NotFound(target) => write!(f, "not found: {target}"),
+            Error::Connection(message) => write!(f, "connection failed: {message}"),
+            Error::UnsupportedType(message) => write!(f, "unsupported type: {message}"),
+            Error::CorruptSnapshot(message) => write!(f, "corrupt snapshot: {message}"),
+            Error::Cancelled => write!(f, "cancelled"),
+            Error::InvalidInput(message) => write!(f, "invalid input: {message}"),
+            Error::Internal(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+// 下位層は anyhow::Result を返すので、コマンドの境界でこの型に寄せる
+impl From<anyhow::Error> for Error {
+    fn from(e: anyhow::Error) -> Self {
+        match e.downcast::<Error>() {
+            Ok(e) => e,
+            Err(e) => match e.downcast_ref::<mysql::Error>() {
+                Some(mysql::Error::IoError(_)) | Some(mysql::Error::DriverError(_)) => Error::Connection(format!("{e:#}")),
+                _ => Error::Internal(format!("{e:#}")),
+            },
+        }
+    }
+}
+
+impl From<mysql::Error> for Error {
+    fn from(e: mysql::Error) -> Self {
+        Error::from(anyhow::Error::from(e))
+    }
+}
+
+// フロントエンドでは { code, message } として受け取る
+impl Serialize for Error {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Error", 2)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+    use serde_json::json;
+
+    use crate::error::Error;
+
+    #[test]
+    fn serialize() {
+        assert_eq!(
+            json!({ "code": "NOT_FOUND", "message": "not found: project 1234" }),
+            serde_json::to_value(Error::NotFound("project 1234".to_string())).unwrap()
+        );
+        assert_eq!(json!({ "code": "CANCELLED", "message": "cancelled" }), serde_json::to_value(Error::Cancelled).unwrap());
+    }
+
+    #[test]
+    fn from_anyhow() {
+        let e = anyhow!(Error::CorruptSnapshot("table users".to_string())).context("failed to load snapshot");
+        assert_eq!(Error::CorruptSnapshot("table users".to_string()), Error::from(e));
+
+        assert_eq!(Error::Internal("something wrong".to_string()), Error::from(anyhow!("something wrong")));
+    }
+}
@@ -0,0 +1,84 @@
+use mysql::Conn;
+
+use crate::db::project::find_project;
+use crate::db::snapshot::{all_snapshot_summaries, find_table_snapshot};
+use crate::domain::history::{ColStatsSeries, RowHistory};
+use crate::domain::project::ProjectId;
+use crate::domain::schema::{ColName, PrimaryValue, TableName};
+
+// スナップショットを作成順に、対象のテーブルだけをひとつずつ読み込んで履歴を作る
+pub fn find_row_history(conn: &mut Conn, project_id: &ProjectId, table_name: &TableName, primary_value: &PrimaryValue) -> anyhow::Result<RowHistory> {
+    find_project(conn, project_id)?;
+
+    let mut row_history = RowHistory::new(table_name, primary_value);
+    for snapshot_summary in all_snapshot_summaries(conn, project_id)? {
+        let table_snapshot = find_table_snapshot(conn, &snapshot_summary.snapshot_id, table_name)?;
+        row_history.push(snapshot_summary, table_snapshot.as_ref());
+    }
+
+    Ok(row_history)
+}
+
+// 値の出現数は、行数の多い順に top 件までを返す
+pub fn find_col_stats_series(
+    conn: &mut Conn,
+    project_id: &ProjectId,
+    table_name: &TableName,
+    col_name: &ColName,
+    top: usize,
+) -> anyhow::Result<ColStatsSeries> {
+    find_project(conn, project_id)?;
+
+    let mut col_stats_series = ColStatsSeries::new(table_name, col_name);
+    for snapshot_summary in all_snapshot_summaries(conn, project_id)? {
+        let table_snapshot = find_table_snapshot(conn, &snapshot_summary.snapshot_id, table_name)?;
+        col_stats_series.push(snapshot_summary, table_snapshot.as_ref(), top);
+    }
+
+    Ok(col_stats_series)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::create_connection;
+    use crate::db::project::insert_project;
+    use crate::db::snapshot::{insert_snapshot_summary, insert_table_snapshot};
+    use crate::domain::fixture::{n, s};
+    use crate::domain::history::RowHistoryKind;
+    use crate::domain::project::Rdbms::Mysql;
+    use crate::domain::project::{create_project_id, Project};
+    use crate::domain::snapshot::{create_snapshot_id, RowSnapshot, SnapshotSummary, TableSnapshot};
+    use crate::history::find_row_history;
+
+    #[test]
+    fn row_history() -> anyhow::Result<()> {
+        // setup
+
+        let mut conn = create_connection()?;
+        conn.prep_exec("delete from project", ())?;
+
+        let project_id = create_project_id();
+
+        let project = Project::new(&project_id, "test-project", Mysql, "user", "password", "127.0.0.1", "3306", "test-db");
+        insert_project(&mut conn, &project)?;
+
+        // 作成日時の順に並べ替えられる
+        for (name, create_at) in [("Jane", "2023-07-03 09:00:00"), ("John", "2023-07-03 08:00:00"), ("Jane", "2023-07-03 10:00:00")] {
+            let snapshot_id = create_snapshot_id();
+            insert_snapshot_summary(&mut conn, &project_id, &SnapshotSummary::new(&snapshot_id, create_at, create_at))?;
+
+            let row_snapshot = RowSnapshot::new(vec![n("42"), s(name)]);
+            let table_snapshot = TableSnapshot::new(&"users".to_string(), "id".to_string(), vec!["name".to_string()], vec![row_snapshot]);
+            insert_table_snapshot(&mut conn, &snapshot_id, &table_snapshot)?;
+        }
+
+        // history
+        let row_history = find_row_history(&mut conn, &project_id, &"users".to_string(), &"42".to_string())?;
+        assert_eq!(
+            vec![("2023-07-03 08:00:00", RowHistoryKind::Appeared), ("2023-07-03 09:00:00", RowHistoryKind::Changed)],
+            row_history.entries.iter().map(|entry| (entry.snapshot_summary.create_at.as_str(), entry.kind)).collect::<Vec<_>>()
+        );
+
+        Ok(())
+    }
+}
@@ -1,29 +1,65 @@
+use anyhow::anyhow;
+use clap::Parser;
 use tauri::Manager;
 
+use crate::cli::Cli;
 use crate::command::state::AppState;
 
+mod archive;
+mod cli;
 mod command;
+mod credential;
 mod db;
+mod diff;
 mod domain;
 mod dump;
+mod error;
+mod history;
+mod scheduler;
 
 fn main() -> anyhow::Result<()> {
+    if let Some(command) = Cli::parse().command {
+        return cli::run(command);
+    }
+
     tauri::Builder::default()
         .invoke_handler(tauri::generate_handler![
             command::project::all_projects_command,
             command::project::insert_project_command,
             command::project::update_project_command,
+            command::project::set_project_password_command,
+            command::project::test_project_connection_command,
             command::project::delete_project_command,
-            command::project::select_project_command,
             command::snapshot::all_snapshot_summaries_command,
             command::snapshot::update_snapshot_summary_command,
             command::snapshot::delete_snapshot_summary_command,
             command::snapshot::dump_snapshot_command,
+            command::snapshot::cancel_dump_command,
+            command::snapshot::running_dump_jobs_command,
             command::diff::find_snapshot_diff_command,
+            command::diff::find_snapshot_diff_summary_command,
+            command::diff::find_table_diff_page_command,
+            command::diff::find_live_diff_command,
+            command::diff::current_binlog_position_command,
+            command::diff::find_binlog_diff_command,
+            command::diff::find_three_way_diff_command,
+            command::diff::find_grouped_diff_command,
+            command::diff::export_diff_report_command,
+            command::diff::export_diff_changes_command,
+            command::history::find_row_history_command,
+            command::history::find_col_stats_series_command,
+            command::archive::export_snapshots_command,
+            command::archive::import_snapshots_command,
+            command::schedule::find_snapshot_schedule_command,
+            command::schedule::save_snapshot_schedule_command,
+            command::schedule::delete_snapshot_schedule_command,
         ])
         .setup(|app| {
-            let state = AppState::new()?;
+            // tauri.conf.json の identifier から決まるディレクトリを使う
+            let config_dir = app.path_resolver().app_config_dir().ok_or_else(|| anyhow!("config dir not found"))?;
+            let state = AppState::new(&config_dir)?;
             app.manage(state);
+            command::schedule::start_scheduler(app.handle());
             Ok(())
         })
         .run(tauri::generate_context!())
@@ -0,0 +1,127 @@
+use std::sync::atomic::AtomicBool;
+
+use chrono::NaiveDateTime;
+use mysql::Conn;
+
+use crate::credential::CredentialKey;
+use crate::db::schedule::{all_scheduled_snapshot_ids, insert_scheduled_snapshot};
+use crate::db::snapshot::{all_snapshot_summaries, delete_snapshot_summary};
+use crate::domain::project::{Project, ProjectId};
+use crate::domain::schedule::SnapshotSchedule;
+use crate::domain::snapshot::SnapshotId;
+use crate::dump::{dump, dump_if_changed};
+
+#[derive(Eq, PartialEq, Debug)]
+pub enum AutoSnapshot {
+    Created { snapshot_id: SnapshotId, deleted_snapshot_ids: Vec<SnapshotId> },
+    Skipped,
+}
+
+pub fn take_auto_snapshot(
+    conn: &mut Conn,
+    project: &Project,
+    key: &CredentialKey,
+    schedule: &SnapshotSchedule,
+    now: NaiveDateTime,
+) -> anyhow::Result<AutoSnapshot> {
+    let snapshot_name = schedule.snapshot_name(now);
+
+    let snapshot_id = match all_snapshot_summaries(conn, &project.project_id)?.last() {
+        Some(previous) => match dump_if_changed(conn, project, key, snapshot_name, &previous.snapshot_id)? {
+            Some(snapshot_id) => snapshot_id,
+            None => return Ok(AutoSnapshot::Skipped),
+        },
+        None => dump(conn, project, key, snapshot_name, &AtomicBool::new(false), |_| {})?,
+    };
+
+    insert_scheduled_snapshot(conn, &snapshot_id)?;
+    let deleted_snapshot_ids = apply_retention(conn, &project.project_id, schedule)?;
+
+    Ok(AutoSnapshot::Created { snapshot_id, deleted_snapshot_ids })
+}
+
+// 手動で作ったスナップショットは消さないよう、スケジュールで作ったものだけを古い順に消す
+fn apply_retention(conn: &mut Conn, project_id: &ProjectId, schedule: &SnapshotSchedule) -> anyhow::Result<Vec<SnapshotId>> {
+    let scheduled_snapshot_ids = all_scheduled_snapshot_ids(conn, project_id)?;
+    let expired = scheduled_snapshot_ids.len().saturating_sub(schedule.retention);
+
+    scheduled_snapshot_ids
+        .into_iter()
+        .take(expired)
+        .map(|snapshot_id| {
+            delete_snapshot_summary(conn, &snapshot_id)?;
+            Ok(snapshot_id)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+    use itertools::Itertools;
+
+    use crate::credential::CredentialKey;
+    use crate::db::create_connection;
+    use crate::db::project::insert_project;
+    use crate::db::snapshot::{all_snapshot_summaries, insert_snapshot_summary};
+    use crate::domain::project::Rdbms::Mysql;
+    use crate::domain::project::{create_project_id, Project};
+    use crate::domain::schedule::{SnapshotSchedule, Trigger};
+    use crate::domain::snapshot::{create_snapshot_id, SnapshotSummary};
+    use crate::scheduler::{take_auto_snapshot, AutoSnapshot};
+
+    fn at(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    #[test]
+    fn auto_snapshot() -> anyhow::Result<()> {
+        // setup
+
+        let mut conn = create_connection()?;
+        conn.prep_exec("delete from project", ())?;
+
+        let key = CredentialKey::load_or_create(&std::env::temp_dir().join(create_project_id()))?;
+
+        let project = Project::new(&create_project_id(), "test-project", Mysql, "user", &key.encrypt("password")?, "127.0.0.1", "19001", "testdata");
+        insert_project(&mut conn, &project)?;
+
+        // 自動スナップショットと同じ名前で始まっていても、手動のものは保持数に数えない
+        let manual = SnapshotSummary::new(&create_snapshot_id(), "auto-manual", "2000-01-01 00:00:00");
+        insert_snapshot_summary(&mut conn, &project.project_id, &manual)?;
+
+        let schedule = SnapshotSchedule {
+            project_id: project.project_id.clone(),
+            trigger: Trigger::Interval { seconds: 1 },
+            name_prefix: "auto-".to_string(),
+            retention: 1,
+            enabled: true,
+        };
+
+        // 空のスナップショットとは差分があるので作られる
+        let created1 = take_auto_snapshot(&mut conn, &project, &key, &schedule, at("2023-07-03 09:00:00"))?;
+        let AutoSnapshot::Created { snapshot_id: snapshot_id1, deleted_snapshot_ids } = created1 else {
+            panic!("auto snapshot is not created");
+        };
+        assert!(deleted_snapshot_ids.is_empty());
+
+        // 変更がなければ作られない
+        assert_eq!(AutoSnapshot::Skipped, take_auto_snapshot(&mut conn, &project, &key, &schedule, at("2023-07-03 09:00:01"))?);
+
+        // 保持数を超えた自動スナップショットは古いものから消え、手動のものは残る
+        let manual2 = SnapshotSummary::new(&create_snapshot_id(), "manual2", "2999-01-01 00:00:00");
+        insert_snapshot_summary(&mut conn, &project.project_id, &manual2)?;
+
+        let created2 = take_auto_snapshot(&mut conn, &project, &key, &schedule, at("2023-07-03 09:00:02"))?;
+        let AutoSnapshot::Created { deleted_snapshot_ids, .. } = created2 else {
+            panic!("auto snapshot is not created");
+        };
+        assert_eq!(vec![snapshot_id1], deleted_snapshot_ids);
+
+        let snapshot_names =
+            all_snapshot_summaries(&mut conn, &project.project_id)?.into_iter().map(|snapshot_summary| snapshot_summary.snapshot_name).collect_vec();
+        assert_eq!(vec!["auto-manual", "auto-2023-07-03 09:00:02", "manual2"], snapshot_names);
+
+        Ok(())
+    }
+}
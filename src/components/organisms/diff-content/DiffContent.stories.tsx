@@ -16,16 +16,25 @@ export const RowDeleted: Story = {
   args: {
     tableDiff: {
       tableName: 'users',
-      primaryValues: ['1'],
+      renamedFrom: null,
       primaryColName: 'id',
       colNames: ['name', 'age'],
-      rowDiffs1: {
-        '1': {
-          name: { status: 'deleted', value: '"John"' },
-          age: { status: 'deleted', value: '29' },
+      rows: [
+        {
+          primaryValue: '1',
+          oldPrimaryValue: null,
+          kind: 'removed',
+          cols: [
+            {
+              colName: 'name',
+              kind: 'removed',
+              oldValue: '"John"',
+              newValue: null,
+            },
+            { colName: 'age', kind: 'removed', oldValue: '29', newValue: null },
+          ],
         },
-      },
-      rowDiffs2: {},
+      ],
     },
   },
 }
@@ -34,16 +43,25 @@ export const RowAdded: Story = {
   args: {
     tableDiff: {
       tableName: 'users',
-      primaryValues: ['1'],
+      renamedFrom: null,
       primaryColName: 'id',
       colNames: ['name', 'age'],
-      rowDiffs1: {},
-      rowDiffs2: {
-        '1': {
-          name: { status: 'added', value: '"John"' },
-          age: { status: 'added', value: '29' },
+      rows: [
+        {
+          primaryValue: '1',
+          oldPrimaryValue: null,
+          kind: 'added',
+          cols: [
+            {
+              colName: 'name',
+              kind: 'added',
+              oldValue: null,
+              newValue: '"John"',
+            },
+            { colName: 'age', kind: 'added', oldValue: null, newValue: '29' },
+          ],
         },
-      },
+      ],
     },
   },
 }
@@ -52,21 +70,57 @@ export const RowModified: Story = {
   args: {
     tableDiff: {
       tableName: 'users',
-      primaryValues: ['1'],
+      renamedFrom: null,
       primaryColName: 'id',
       colNames: ['name', 'age'],
-      rowDiffs1: {
-        '1': {
-          name: { status: 'deleted', value: '"John"' },
-          age: { status: 'deleted', value: '29' },
+      rows: [
+        {
+          primaryValue: '1',
+          oldPrimaryValue: null,
+          kind: 'modified',
+          cols: [
+            {
+              colName: 'name',
+              kind: 'modified',
+              oldValue: '"John"',
+              newValue: '"Jane"',
+            },
+            {
+              colName: 'age',
+              kind: 'modified',
+              oldValue: '29',
+              newValue: '15',
+            },
+          ],
         },
-      },
-      rowDiffs2: {
-        '1': {
-          name: { status: 'added', value: '"Jane"' },
-          age: { status: 'added', value: '15' },
+      ],
+    },
+  },
+}
+
+export const RowRekeyed: Story = {
+  args: {
+    tableDiff: {
+      tableName: 'users',
+      renamedFrom: null,
+      primaryColName: 'id',
+      colNames: ['name', 'age'],
+      rows: [
+        {
+          primaryValue: '3',
+          oldPrimaryValue: '2',
+          kind: 'rekeyed',
+          cols: [
+            {
+              colName: 'name',
+              kind: 'unchanged',
+              oldValue: '"Jack"',
+              newValue: '"Jack"',
+            },
+            { colName: 'age', kind: 'modified', oldValue: '31', newValue: '32' },
+          ],
         },
-      },
+      ],
     },
   },
 }
@@ -75,20 +129,39 @@ export const RowsDeleted: Story = {
   args: {
     tableDiff: {
       tableName: 'users',
-      primaryValues: ['1', '2'],
+      renamedFrom: null,
       primaryColName: 'id',
       colNames: ['name', 'age'],
-      rowDiffs1: {
-        '1': {
-          name: { status: 'deleted', value: '"John"' },
-          age: { status: 'deleted', value: '29' },
+      rows: [
+        {
+          primaryValue: '1',
+          oldPrimaryValue: null,
+          kind: 'removed',
+          cols: [
+            {
+              colName: 'name',
+              kind: 'removed',
+              oldValue: '"John"',
+              newValue: null,
+            },
+            { colName: 'age', kind: 'removed', oldValue: '29', newValue: null },
+          ],
         },
-        '2': {
-          name: { status: 'deleted', value: '"Alice"' },
-          age: { status: 'deleted', value: '31' },
+        {
+          primaryValue: '2',
+          oldPrimaryValue: null,
+          kind: 'removed',
+          cols: [
+            {
+              colName: 'name',
+              kind: 'removed',
+              oldValue: '"Alice"',
+              newValue: null,
+            },
+            { colName: 'age', kind: 'removed', oldValue: '31', newValue: null },
+          ],
         },
-      },
-      rowDiffs2: {},
+      ],
     },
   },
 }
@@ -97,20 +170,39 @@ export const RowsAdded: Story = {
   args: {
     tableDiff: {
       tableName: 'users',
-      primaryValues: ['1', '2'],
+      renamedFrom: null,
       primaryColName: 'id',
       colNames: ['name', 'age'],
-      rowDiffs1: {},
-      rowDiffs2: {
-        '1': {
-          name: { status: 'added', value: '"John"' },
-          age: { status: 'added', value: '29' },
+      rows: [
+        {
+          primaryValue: '1',
+          oldPrimaryValue: null,
+          kind: 'added',
+          cols: [
+            {
+              colName: 'name',
+              kind: 'added',
+              oldValue: null,
+              newValue: '"John"',
+            },
+            { colName: 'age', kind: 'added', oldValue: null, newValue: '29' },
+          ],
         },
-        '2': {
-          name: { status: 'added', value: '"Alice"' },
-          age: { status: 'added', value: '31' },
+        {
+          primaryValue: '2',
+          oldPrimaryValue: null,
+          kind: 'added',
+          cols: [
+            {
+              colName: 'name',
+              kind: 'added',
+              oldValue: null,
+              newValue: '"Alice"',
+            },
+            { colName: 'age', kind: 'added', oldValue: null, newValue: '31' },
+          ],
         },
-      },
+      ],
     },
   },
 }
@@ -119,29 +211,49 @@ export const RowsModified: Story = {
   args: {
     tableDiff: {
       tableName: 'users',
-      primaryValues: ['1', '2'],
+      renamedFrom: null,
       primaryColName: 'id',
       colNames: ['name', 'age'],
-      rowDiffs1: {
-        '1': {
-          name: { status: 'deleted', value: '"John"' },
-          age: { status: 'deleted', value: '29' },
+      rows: [
+        {
+          primaryValue: '1',
+          oldPrimaryValue: null,
+          kind: 'modified',
+          cols: [
+            {
+              colName: 'name',
+              kind: 'modified',
+              oldValue: '"John"',
+              newValue: '"Jane"',
+            },
+            {
+              colName: 'age',
+              kind: 'modified',
+              oldValue: '29',
+              newValue: '15',
+            },
+          ],
         },
-        '2': {
-          name: { status: 'deleted', value: '"Alice"' },
-          age: { status: 'deleted', value: '31' },
+        {
+          primaryValue: '2',
+          oldPrimaryValue: null,
+          kind: 'modified',
+          cols: [
+            {
+              colName: 'name',
+              kind: 'modified',
+              oldValue: '"Alice"',
+              newValue: '"Bob"',
+            },
+            {
+              colName: 'age',
+              kind: 'modified',
+              oldValue: '31',
+              newValue: '42',
+            },
+          ],
         },
-      },
-      rowDiffs2: {
-        '1': {
-          name: { status: 'added', value: '"Jane"' },
-          age: { status: 'added', value: '15' },
-        },
-        '2': {
-          name: { status: 'added', value: '"Bob"' },
-          age: { status: 'added', value: '42' },
-        },
-      },
+      ],
     },
   },
 }
@@ -150,25 +262,44 @@ export const RowsModifiedAndDeleted: Story = {
   args: {
     tableDiff: {
       tableName: 'users',
-      primaryValues: ['1', '2'],
+      renamedFrom: null,
       primaryColName: 'id',
       colNames: ['name', 'age'],
-      rowDiffs1: {
-        '1': {
-          name: { status: 'deleted', value: '"John"' },
-          age: { status: 'deleted', value: '29' },
-        },
-        '2': {
-          name: { status: 'deleted', value: '"Alice"' },
-          age: { status: 'deleted', value: '31' },
+      rows: [
+        {
+          primaryValue: '1',
+          oldPrimaryValue: null,
+          kind: 'modified',
+          cols: [
+            {
+              colName: 'name',
+              kind: 'modified',
+              oldValue: '"John"',
+              newValue: '"Jane"',
+            },
+            {
+              colName: 'age',
+              kind: 'modified',
+              oldValue: '29',
+              newValue: '15',
+            },
+          ],
         },
-      },
-      rowDiffs2: {
-        '1': {
-          name: { status: 'added', value: '"Jane"' },
-          age: { status: 'added', value: '15' },
+        {
+          primaryValue: '2',
+          oldPrimaryValue: null,
+          kind: 'removed',
+          cols: [
+            {
+              colName: 'name',
+              kind: 'removed',
+              oldValue: '"Alice"',
+              newValue: null,
+            },
+            { colName: 'age', kind: 'removed', oldValue: '31', newValue: null },
+          ],
         },
-      },
+      ],
     },
   },
 }
@@ -177,25 +308,44 @@ export const RowsModifiedAndAdded: Story = {
   args: {
     tableDiff: {
       tableName: 'users',
-      primaryValues: ['1', '2'],
+      renamedFrom: null,
       primaryColName: 'id',
       colNames: ['name', 'age'],
-      rowDiffs1: {
-        '1': {
-          name: { status: 'deleted', value: '"John"' },
-          age: { status: 'deleted', value: '29' },
+      rows: [
+        {
+          primaryValue: '1',
+          oldPrimaryValue: null,
+          kind: 'modified',
+          cols: [
+            {
+              colName: 'name',
+              kind: 'modified',
+              oldValue: '"John"',
+              newValue: '"Jane"',
+            },
+            {
+              colName: 'age',
+              kind: 'modified',
+              oldValue: '29',
+              newValue: '15',
+            },
+          ],
         },
-      },
-      rowDiffs2: {
-        '1': {
-          name: { status: 'added', value: '"Jane"' },
-          age: { status: 'added', value: '15' },
+        {
+          primaryValue: '2',
+          oldPrimaryValue: null,
+          kind: 'added',
+          cols: [
+            {
+              colName: 'name',
+              kind: 'added',
+              oldValue: null,
+              newValue: '"Alice"',
+            },
+            { colName: 'age', kind: 'added', oldValue: null, newValue: '31' },
+          ],
         },
-        '2': {
-          name: { status: 'added', value: '"Alice"' },
-          age: { status: 'added', value: '31' },
-        },
-      },
+      ],
     },
   },
 }
@@ -204,21 +354,30 @@ export const ColModified: Story = {
   args: {
     tableDiff: {
       tableName: 'users',
-      primaryValues: ['1'],
+      renamedFrom: null,
       primaryColName: 'id',
       colNames: ['name', 'age'],
-      rowDiffs1: {
-        '1': {
-          name: { status: 'stay', value: '"John"' },
-          age: { status: 'deleted', value: '29' },
-        },
-      },
-      rowDiffs2: {
-        '1': {
-          name: { status: 'stay', value: '"John"' },
-          age: { status: 'added', value: '15' },
+      rows: [
+        {
+          primaryValue: '1',
+          oldPrimaryValue: null,
+          kind: 'modified',
+          cols: [
+            {
+              colName: 'name',
+              kind: 'unchanged',
+              oldValue: '"John"',
+              newValue: '"John"',
+            },
+            {
+              colName: 'age',
+              kind: 'modified',
+              oldValue: '29',
+              newValue: '15',
+            },
+          ],
         },
-      },
+      ],
     },
   },
 }
@@ -227,20 +386,25 @@ export const ColRemoved: Story = {
   args: {
     tableDiff: {
       tableName: 'users',
-      primaryValues: ['1'],
+      renamedFrom: null,
       primaryColName: 'id',
       colNames: ['name', 'age'],
-      rowDiffs1: {
-        '1': {
-          name: { status: 'stay', value: '"John"' },
-          age: { status: 'deleted', value: '29' },
+      rows: [
+        {
+          primaryValue: '1',
+          oldPrimaryValue: null,
+          kind: 'modified',
+          cols: [
+            {
+              colName: 'name',
+              kind: 'unchanged',
+              oldValue: '"John"',
+              newValue: '"John"',
+            },
+            { colName: 'age', kind: 'removed', oldValue: '29', newValue: null },
+          ],
         },
-      },
-      rowDiffs2: {
-        '1': {
-          name: { status: 'stay', value: '"John"' },
-        },
-      },
+      ],
     },
   },
 }
@@ -249,20 +413,25 @@ export const ColCreated: Story = {
   args: {
     tableDiff: {
       tableName: 'users',
-      primaryValues: ['1'],
+      renamedFrom: null,
       primaryColName: 'id',
       colNames: ['name', 'age'],
-      rowDiffs1: {
-        '1': {
-          name: { status: 'stay', value: '"John"' },
-        },
-      },
-      rowDiffs2: {
-        '1': {
-          name: { status: 'stay', value: '"John"' },
-          age: { status: 'added', value: '29' },
+      rows: [
+        {
+          primaryValue: '1',
+          oldPrimaryValue: null,
+          kind: 'modified',
+          cols: [
+            {
+              colName: 'name',
+              kind: 'unchanged',
+              oldValue: '"John"',
+              newValue: '"John"',
+            },
+            { colName: 'age', kind: 'added', oldValue: null, newValue: '29' },
+          ],
         },
-      },
+      ],
     },
   },
 }
@@ -271,21 +440,26 @@ export const ColMismatched: Story = {
   args: {
     tableDiff: {
       tableName: 'users',
-      primaryValues: ['1'],
+      renamedFrom: null,
       primaryColName: 'id',
       colNames: ['name', 'age', 'rate'],
-      rowDiffs1: {
-        '1': {
-          name: { status: 'stay', value: '"John"' },
-          age: { status: 'deleted', value: '29' },
+      rows: [
+        {
+          primaryValue: '1',
+          oldPrimaryValue: null,
+          kind: 'modified',
+          cols: [
+            {
+              colName: 'name',
+              kind: 'unchanged',
+              oldValue: '"John"',
+              newValue: '"John"',
+            },
+            { colName: 'age', kind: 'removed', oldValue: '29', newValue: null },
+            { colName: 'rate', kind: 'added', oldValue: null, newValue: '1' },
+          ],
         },
-      },
-      rowDiffs2: {
-        '1': {
-          name: { status: 'stay', value: '"John"' },
-          rate: { status: 'added', value: '1' },
-        },
-      },
+      ],
     },
   },
 }
@@ -294,21 +468,30 @@ export const RowModifiedStringId: Story = {
   args: {
     tableDiff: {
       tableName: 'users',
-      primaryValues: ['"EF974256-0BDE-4170-A2FC-4BDBBD696FB5"'],
+      renamedFrom: null,
       primaryColName: 'id',
       colNames: ['name', 'age'],
-      rowDiffs1: {
-        '"EF974256-0BDE-4170-A2FC-4BDBBD696FB5"': {
-          name: { status: 'deleted', value: '"John"' },
-          age: { status: 'deleted', value: '29' },
-        },
-      },
-      rowDiffs2: {
-        '"EF974256-0BDE-4170-A2FC-4BDBBD696FB5"': {
-          name: { status: 'added', value: '"Jane"' },
-          age: { status: 'added', value: '15' },
+      rows: [
+        {
+          primaryValue: '"EF974256-0BDE-4170-A2FC-4BDBBD696FB5"',
+          oldPrimaryValue: null,
+          kind: 'modified',
+          cols: [
+            {
+              colName: 'name',
+              kind: 'modified',
+              oldValue: '"John"',
+              newValue: '"Jane"',
+            },
+            {
+              colName: 'age',
+              kind: 'modified',
+              oldValue: '29',
+              newValue: '15',
+            },
+          ],
         },
-      },
+      ],
     },
   },
 }
@@ -1,5 +1,5 @@
 import React, { type FC, Fragment } from 'react'
-import { type TableDiff } from '../../../types'
+import { type ColDiff, type RowDiff, type TableDiff } from '../../../types'
 import styles from './DiffContent.module.scss'
 
 interface Props {
@@ -13,33 +13,51 @@ const colors = {
   none: styles.none,
 }
 
+// 変更前の行では削除された値を、変更後の行では追加された値を色付けする
+const cellOf = (
+  colDiff: ColDiff | undefined,
+  n: number
+): { value: string | null; color: string } => {
+  if (colDiff == null) {
+    return { value: null, color: colors.none }
+  }
+  const value = n === 1 ? colDiff.oldValue : colDiff.newValue
+  if (value == null) {
+    return { value: null, color: colors.none }
+  }
+  if (colDiff.kind === 'unchanged') {
+    return { value, color: colors.stay }
+  }
+  return { value, color: n === 1 ? colors.deleted : colors.added }
+}
+
+// 主キーの変わった行は、変更前と変更後の主キー値を並べる
+const primaryValueOf = (rowDiff: RowDiff): string =>
+  rowDiff.oldPrimaryValue != null
+    ? `${rowDiff.oldPrimaryValue} → ${rowDiff.primaryValue}`
+    : rowDiff.primaryValue
+
 interface TRProps {
-  primaryValue: string
   colNames: string[]
-  rowDiff?: Record<
-    string,
-    { status: 'stay' | 'added' | 'deleted' | 'none'; value: string }
-  >
+  rowDiff: RowDiff
   n: number
 }
 
 const TR: FC<TRProps> = (props) => {
   return (
     <tr>
-      {props.n === 1 && <td rowSpan={2}>{props.primaryValue}</td>}
-      {props.colNames.map((colName, i) =>
-        props.rowDiff != null ? (
-          colName in props.rowDiff ? (
-            <td key={i} className={colors[props.rowDiff[colName].status]}>
-              {props.rowDiff[colName].value}
-            </td>
-          ) : (
-            <td key={i} className={colors.none}></td>
-          )
-        ) : (
-          <td key={i} className={colors.none}></td>
+      {props.n === 1 && <td rowSpan={2}>{primaryValueOf(props.rowDiff)}</td>}
+      {props.colNames.map((colName, i) => {
+        const cell = cellOf(
+          props.rowDiff.cols.find((colDiff) => colDiff.colName === colName),
+          props.n
+        )
+        return (
+          <td key={i} className={cell.color}>
+            {cell.value}
+          </td>
         )
-      )}
+      })}
     </tr>
   )
 }
@@ -47,7 +65,11 @@ const TR: FC<TRProps> = (props) => {
 export const DiffContent: FC<Props> = (props) => {
   return (
     <div className={styles.component}>
-      <span className={styles.label}>{props.tableDiff.tableName}</span>
+      <span className={styles.label}>
+        {props.tableDiff.renamedFrom != null
+          ? `${props.tableDiff.renamedFrom} → ${props.tableDiff.tableName}`
+          : props.tableDiff.tableName}
+      </span>
       <table>
         <thead>
           <tr>
@@ -58,20 +80,18 @@ export const DiffContent: FC<Props> = (props) => {
           </tr>
         </thead>
         <tbody>
-          {props.tableDiff.primaryValues.map((primaryValue, i) => (
+          {props.tableDiff.rows.map((rowDiff, i) => (
             <Fragment key={i}>
               <TR
                 key={`${i}-1`}
-                primaryValue={primaryValue}
                 colNames={props.tableDiff.colNames}
-                rowDiff={props.tableDiff.rowDiffs1[primaryValue]}
+                rowDiff={rowDiff}
                 n={1}
               />
               <TR
                 key={`${i}-2`}
-                primaryValue={primaryValue}
                 colNames={props.tableDiff.colNames}
-                rowDiff={props.tableDiff.rowDiffs2[primaryValue]}
+                rowDiff={rowDiff}
                 n={2}
               />
             </Fragment>
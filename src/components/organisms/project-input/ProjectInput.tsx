@@ -1,18 +1,22 @@
 import React, { type FC, useState } from 'react'
 import styles from './ProjectInput.module.scss'
-import { createProjectId, type Project } from '../../../types'
+import {
+  createProjectId,
+  defaultConnectionOptions,
+  type Project,
+} from '../../../types'
 import { InputText } from '../../atoms/input-text/InputText'
 import { IconSave } from '../../atoms/icon-save/IconSave'
 
 interface Props {
   project?: Project
-  save: (project: Project) => void
+  save: (project: Project, password: string) => void
 }
 
 export const ProjectInput: FC<Props> = (props) => {
   const [name, setName] = useState(props.project?.name ?? '')
   const [user, setUser] = useState(props.project?.user ?? '')
-  const [password, setPassword] = useState(props.project?.password ?? '')
+  const [password, setPassword] = useState('')
   const [host, setHost] = useState(props.project?.host ?? '')
   const [port, setPort] = useState(props.project?.port ?? '')
   const [schema, setSchema] = useState(props.project?.schema ?? '')
@@ -95,16 +99,20 @@ export const ProjectInput: FC<Props> = (props) => {
         variant={'large'}
         onClick={() => {
           const projectId = props.project?.projectId ?? createProjectId()
-          props.save({
-            projectId,
-            name,
-            rdbms: 'MySQL',
-            user,
-            password,
-            host,
-            port,
-            schema,
-          })
+          props.save(
+            {
+              projectId,
+              name,
+              rdbms: 'MySQL',
+              user,
+              hasPassword: props.project?.hasPassword ?? false,
+              host,
+              port,
+              schema,
+              options: props.project?.options ?? defaultConnectionOptions,
+            },
+            password
+          )
         }}
       />
     </div>
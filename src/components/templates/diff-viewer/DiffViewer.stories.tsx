@@ -14,25 +14,39 @@ export default meta
 type Story = StoryObj<typeof meta>
 
 const tableDiff: Omit<TableDiff, 'tableName'> = {
-  primaryValues: ['1', '2'],
+  renamedFrom: null,
   primaryColName: 'id',
   colNames: ['name', 'age'],
-  rowDiffs1: {
-    '1': {
-      name: { status: 'deleted', value: '"John"' },
-      age: { status: 'deleted', value: '29' },
+  rows: [
+    {
+      primaryValue: '1',
+      oldPrimaryValue: null,
+      kind: 'modified',
+      cols: [
+        {
+          colName: 'name',
+          kind: 'modified',
+          oldValue: '"John"',
+          newValue: '"Jane"',
+        },
+        { colName: 'age', kind: 'modified', oldValue: '29', newValue: '15' },
+      ],
     },
-    '2': {
-      name: { status: 'deleted', value: '"Alice"' },
-      age: { status: 'deleted', value: '31' },
+    {
+      primaryValue: '2',
+      oldPrimaryValue: null,
+      kind: 'removed',
+      cols: [
+        {
+          colName: 'name',
+          kind: 'removed',
+          oldValue: '"Alice"',
+          newValue: null,
+        },
+        { colName: 'age', kind: 'removed', oldValue: '31', newValue: null },
+      ],
     },
-  },
-  rowDiffs2: {
-    '1': {
-      name: { status: 'added', value: '"Jane"' },
-      age: { status: 'added', value: '15' },
-    },
-  },
+  ],
 }
 
 const tableNames = [
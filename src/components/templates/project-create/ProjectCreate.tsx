@@ -7,7 +7,7 @@ import { IconBack } from '../../atoms/icon-back/IconBack'
 import { useNavigate } from 'react-router-dom'
 
 interface Props {
-  insert: (project: Project) => void
+  insert: (project: Project, password: string) => void
 }
 
 export const ProjectCreate: FC<Props> = (props) => {
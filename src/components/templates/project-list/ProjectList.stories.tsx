@@ -21,7 +21,7 @@ export const Component: Story = {
         name: 'My Laravel Project',
         rdbms: 'MySQL',
         user: 'admin',
-        password: 'admin-pw',
+        hasPassword: true,
         host: 'localhost',
         port: '3306',
         schema: 'my-laravel-project',
@@ -31,7 +31,7 @@ export const Component: Story = {
         name: 'Todo App',
         rdbms: 'MySQL',
         user: 'admin',
-        password: 'admin-pw',
+        hasPassword: true,
         host: 'localhost',
         port: '3306',
         schema: 'todo',
@@ -41,7 +41,7 @@ export const Component: Story = {
         name: '副業のやつ ( RoR )',
         rdbms: 'MySQL',
         user: 'admin',
-        password: 'admin-pw',
+        hasPassword: true,
         host: 'localhost',
         port: '3306',
         schema: 'data',
@@ -19,7 +19,7 @@ export const Component: Story = {
       name: 'My Laravel Project',
       rdbms: 'MySQL',
       user: 'admin',
-      password: 'admin-pw',
+      hasPassword: true,
       host: 'localhost',
       port: '3306',
       schema: 'my-laravel-project',
@@ -8,7 +8,7 @@ import { useNavigate } from 'react-router-dom'
 
 interface Props {
   project: Project
-  update: (project: Project) => void
+  update: (project: Project, password: string) => void
 }
 
 export const ProjectUpdate: FC<Props> = (props) => {
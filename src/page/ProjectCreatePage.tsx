@@ -7,9 +7,12 @@ import { ProjectCreate } from '../components/templates/project-create/ProjectCre
 export const ProjectCreatePage: FC = () => {
   const navigate = useNavigate()
 
-  const insert: (project: Project) => void = (project) => {
+  const insert: (project: Project, password: string) => void = (
+    project,
+    password
+  ) => {
     console.log(project)
-    invoke('insert_project_command', { projectJson: project })
+    invoke('insert_project_command', { projectJson: project, password })
       .then(() => {
         navigate('/project/list')
       })
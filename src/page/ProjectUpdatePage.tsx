@@ -11,8 +11,19 @@ export const ProjectUpdatePage: FC = () => {
   const project = location.state as Project
   console.log(project)
 
-  const update: (project: Project) => void = (project) => {
+  const update: (project: Project, password: string) => void = (
+    project,
+    password
+  ) => {
     invoke('update_project_command', { projectJson: project })
+      .then(async () => {
+        if (password !== '') {
+          await invoke('set_project_password_command', {
+            projectId: project.projectId,
+            password,
+          })
+        }
+      })
       .then(() => {
         navigate('/project/list')
       })
@@ -7,10 +7,44 @@ export interface Project {
   name: string
   rdbms: string
   user: string
-  password: string
+  hasPassword: boolean
   host: string
   port: string
   schema: string
+  options: ConnectionOptions
+}
+
+export interface ConnectionOptions {
+  url: string | null
+  sslMode: 'disabled' | 'required' | 'verify-ca'
+  sslCaPath: string | null
+  socket: string | null
+  connectTimeoutSec: number | null
+  readTimeoutSec: number | null
+  charset: string | null
+  initSql: string[]
+  ssh: SshJump | null
+  watermarkCols: Record<string, string>
+}
+
+export interface SshJump {
+  host: string
+  port: number
+  user: string
+  keyPath: string
+}
+
+export const defaultConnectionOptions: ConnectionOptions = {
+  url: null,
+  sslMode: 'disabled',
+  sslCaPath: null,
+  socket: null,
+  connectTimeoutSec: null,
+  readTimeoutSec: null,
+  charset: null,
+  initSql: [],
+  ssh: null,
+  watermarkCols: {},
 }
 
 export interface SnapshotSummary {
@@ -19,21 +53,231 @@ export interface SnapshotSummary {
   createAt: string
 }
 
+export interface DumpProgress {
+  jobId: string
+  snapshotId: string
+  tableCount: number
+  tablesDone: number
+  rowsFetched: number
+  currentTable: string | null
+}
+
+export interface DumpFinished {
+  jobId: string
+  status: 'completed' | 'cancelled' | 'failed'
+  snapshotId: string | null
+  error: CommandError | null
+}
+
+export type ScheduleTrigger =
+  | { type: 'interval'; seconds: number }
+  | { type: 'cron'; expression: string }
+
+export interface SnapshotSchedule {
+  projectId: string
+  trigger: ScheduleTrigger
+  namePrefix: string
+  retention: number
+  enabled: boolean
+}
+
+export interface AutoSnapshot {
+  projectId: string
+  snapshotId: string | null
+  skipped: boolean
+  deletedSnapshotIds: string[]
+  error: CommandError | null
+}
+
 type PrimaryValue = string
 type ColName = string
-type RowDiff = Record<
-  PrimaryValue,
-  Record<
-    ColName,
-    { status: 'stay' | 'added' | 'deleted' | 'none'; value: string }
-  >
->
+
+export type RowChangeKind = 'added' | 'removed' | 'modified' | 'rekeyed'
+export type ColChangeKind = 'unchanged' | 'modified' | 'added' | 'removed'
+
+export interface ColDiff {
+  colName: ColName
+  kind: ColChangeKind
+  oldValue: string | null
+  newValue: string | null
+}
+
+export interface RowDiff {
+  primaryValue: PrimaryValue
+  oldPrimaryValue: PrimaryValue | null
+  kind: RowChangeKind
+  cols: ColDiff[]
+}
 
 export interface TableDiff {
   tableName: string
-  primaryValues: PrimaryValue[]
+  renamedFrom: string | null
+  primaryColName: ColName
+  colNames: ColName[]
+  rows: RowDiff[]
+}
+
+export interface TableDiffPageRequest {
+  after?: PrimaryValue
+  offset?: number
+  limit: number
+  kinds?: RowChangeKind[]
+}
+
+export interface TableDiffPage {
+  tableName: string
+  renamedFrom: string | null
   primaryColName: ColName
   colNames: ColName[]
-  rowDiffs1: RowDiff
-  rowDiffs2: RowDiff
+  rows: RowDiff[]
+  total: number
+}
+
+export interface SnapshotDiff {
+  diffId: string
+  snapshotId1: string
+  snapshotId2: string
+  tableDiffs: TableDiff[]
+  summary: DiffSummary
+}
+
+export type ThreeWayChange = 'only1' | 'only2' | 'both' | 'conflict'
+
+export interface ThreeWayColDiff {
+  colName: ColName
+  change: ThreeWayChange
+  baseValue: string | null
+  value1: string | null
+  value2: string | null
+}
+
+export interface ThreeWayRowDiff {
+  primaryValue: PrimaryValue
+  change: ThreeWayChange
+  cols: ThreeWayColDiff[]
+}
+
+export interface ThreeWayTableDiff {
+  tableName: string
+  primaryColName: ColName
+  colNames: ColName[]
+  rows: ThreeWayRowDiff[]
+}
+
+export interface ThreeWayDiff {
+  snapshotIdBase: string
+  snapshotId1: string
+  snapshotId2: string
+  tableDiffs: ThreeWayTableDiff[]
+}
+
+export interface GroupedRow {
+  tableName: string
+  primaryValue: PrimaryValue
+  kind: RowChangeKind
+  cols: ColDiff[]
+  children: GroupedRow[]
+}
+
+export interface DiffGroup {
+  description: string
+  root: GroupedRow
+}
+
+export type ReportFormat = 'markdown' | 'html'
+
+export type ChangeFormat = 'ndjson' | 'json-patch'
+
+export interface ReportOptions {
+  ignoreRules?: string[]
+  maxRowsPerTable?: number | null
+}
+
+export interface TableDiffSummary {
+  tableName: string
+  unchanged: boolean
+  renamedFrom: string | null
+  inserted: number
+  deleted: number
+  updated: number
+  rekeyed: number
+  colModifications: Array<{ colName: ColName; count: number }>
+}
+
+export interface DiffSummary {
+  tables: TableDiffSummary[]
+  total: {
+    changedTables: number
+    unchangedTables: number
+    inserted: number
+    deleted: number
+    updated: number
+    rekeyed: number
+  }
+}
+
+export type RowHistoryKind = 'appeared' | 'changed' | 'disappeared'
+
+export interface RowHistoryEntry {
+  snapshotId: string
+  snapshotName: string
+  createAt: string
+  kind: RowHistoryKind
+  cols: Array<{
+    colName: ColName
+    oldValue: string | null
+    newValue: string | null
+  }>
+}
+
+export interface RowHistory {
+  tableName: string
+  primaryValue: PrimaryValue
+  entries: RowHistoryEntry[]
+}
+
+export interface ColStats {
+  rowCount: number
+  nullCount: number
+  distinctCount: number
+  min: string | null
+  max: string | null
+  valueCounts: Array<{ value: string; count: number }>
+}
+
+export interface ColStatsSeries {
+  tableName: string
+  colName: ColName
+  points: Array<{
+    snapshotId: string
+    snapshotName: string
+    createAt: string
+    stats: ColStats | null
+  }>
+}
+
+export interface BinlogPosition {
+  file: string
+  position: number
+}
+
+export interface BinlogDiff {
+  from: BinlogPosition
+  to: BinlogPosition
+  tableDiffs: TableDiff[]
+  summary: DiffSummary
+}
+
+export type ErrorCode =
+  | 'NOT_FOUND'
+  | 'CONNECTION'
+  | 'UNSUPPORTED_TYPE'
+  | 'CORRUPT_SNAPSHOT'
+  | 'CANCELLED'
+  | 'INVALID_INPUT'
+  | 'INTERNAL'
+
+export interface CommandError {
+  code: ErrorCode
+  message: string
 }